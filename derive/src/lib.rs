@@ -0,0 +1,142 @@
+//! Derive macros for `bsonrs::IntoValue` and `bsonrs::FromValue`.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Error};
+
+#[proc_macro_derive(IntoValue)]
+pub fn derive_into_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    into_value(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(FromValue)]
+pub fn derive_from_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    from_value(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn into_value(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let inserts = fields.named.iter().map(|f| {
+                    let ident = f.ident.as_ref().unwrap();
+                    let key = ident.to_string();
+                    quote! {
+                        doc.insert_value(#key.to_string(), ::bsonrs::convert::IntoValue::into_value(v.#ident));
+                    }
+                });
+
+                quote! {
+                    let mut doc = ::bsonrs::Document::new();
+                    #(#inserts)*
+                    ::bsonrs::Value::Document(doc)
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                quote! { ::bsonrs::convert::IntoValue::into_value(v.0) }
+            }
+            _ => return Err(Error::new_spanned(name, "IntoValue can only be derived for structs with named fields or newtype structs"))
+        },
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let key = ident.to_string();
+                match variant.fields {
+                    Fields::Unit => Ok(quote! { #name::#ident => ::bsonrs::Value::String(#key.to_string()), }),
+                    _ => Err(Error::new_spanned(variant, "IntoValue can only be derived for enums without fields"))
+                }
+            }).collect::<syn::Result<Vec<_>>>()?;
+
+            quote! {
+                match v {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => return Err(Error::new_spanned(name, "IntoValue can't be derived for unions"))
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<#name #ty_generics> for ::bsonrs::Value #where_clause {
+            fn from(v: #name #ty_generics) -> ::bsonrs::Value {
+                #body
+            }
+        }
+    })
+}
+
+fn from_value(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields = fields.named.iter().map(|f| {
+                    let ident = f.ident.as_ref().unwrap();
+                    let key = ident.to_string();
+                    quote! {
+                        #ident: ::bsonrs::convert::take_field(&mut doc, #key)?,
+                    }
+                });
+
+                quote! {
+                    let mut doc = match value {
+                        ::bsonrs::Value::Document(doc) => doc,
+                        _ => return Err(::bsonrs::doc::Error::UnexpectedType),
+                    };
+
+                    Ok(#name {
+                        #(#fields)*
+                    })
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                quote! { ::bsonrs::convert::FromValue::from_value(value).map(#name) }
+            }
+            _ => return Err(Error::new_spanned(name, "FromValue can only be derived for structs with named fields or newtype structs"))
+        },
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let key = ident.to_string();
+                match variant.fields {
+                    Fields::Unit => Ok(quote! { #key => Ok(#name::#ident), }),
+                    _ => Err(Error::new_spanned(variant, "FromValue can only be derived for enums without fields"))
+                }
+            }).collect::<syn::Result<Vec<_>>>()?;
+
+            quote! {
+                match value {
+                    ::bsonrs::Value::String(s) => match s.as_str() {
+                        #(#arms)*
+                        _ => Err(::bsonrs::doc::Error::UnexpectedType),
+                    },
+                    _ => Err(::bsonrs::doc::Error::UnexpectedType),
+                }
+            }
+        }
+        Data::Union(_) => return Err(Error::new_spanned(name, "FromValue can't be derived for unions"))
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::bsonrs::convert::FromValue for #name #ty_generics #where_clause {
+            fn from_value(value: ::bsonrs::Value) -> ::bsonrs::doc::Result<Self> {
+                #body
+            }
+        }
+    })
+}
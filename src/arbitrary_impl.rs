@@ -0,0 +1,160 @@
+//! `Arbitrary` implementations, for fuzzing and property tests.
+//!
+//! Every generated `Document` is valid BSON that survives an encode and
+//! decode round trip, also under `DecodeOptions::strict()`, and compares
+//! equal afterwards:
+//!
+//! - keys, regular expressions and other null terminated strings contain
+//!   no `\0`;
+//! - doubles are never NaN, since NaN isn't equal to itself;
+//! - dates fall in the years 1 to 9999;
+//! - UUID and MD5 binary is 16 bytes;
+//! - documents and arrays nest at most five levels deep.
+use arbitrary::{Arbitrary, Unstructured, Result};
+
+use crate::value::{Value, Array, TimeStamp};
+use crate::doc::Document;
+use crate::spec::BinarySubtype;
+use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
+
+/// How deeply generated documents may nest, counting the outermost one.
+const MAX_DEPTH: usize = 5;
+
+/// 0001-01-01T00:00:00Z and 9999-12-31T23:59:59.999Z in milliseconds.
+const MIN_MILLIS: i64 = -62_135_596_800_000;
+const MAX_MILLIS: i64 = 253_402_300_799_999;
+
+fn cstring(u: &mut Unstructured<'_>) -> Result<String> {
+    let s = String::arbitrary(u)?;
+    Ok(s.replace('\0', ""))
+}
+
+fn document(u: &mut Unstructured<'_>, depth: usize) -> Result<Document> {
+    let mut doc = Document::new();
+
+    while u.arbitrary()? {
+        let key = cstring(u)?;
+        let value = value(u, depth)?;
+        doc.insert_value(key, value);
+    }
+
+    Ok(doc)
+}
+
+/// `depth` is that of the document or array holding the value.
+fn value(u: &mut Unstructured<'_>, depth: usize) -> Result<Value> {
+    // The last three variants nest, and are left out at the depth limit
+    let variants = if depth < MAX_DEPTH { 20 } else { 17 };
+
+    Ok(match u.choose_index(variants)? {
+        0 => {
+            let f = f64::arbitrary(u)?;
+            Value::Double(if f.is_nan() { 0.0 } else { f })
+        }
+        1 => Value::String(String::arbitrary(u)?),
+        2 => Value::Boolean(u.arbitrary()?),
+        3 => Value::Null,
+        4 => Value::RegExp(cstring(u)?, cstring(u)?),
+        5 => Value::JavaScriptCode(String::arbitrary(u)?),
+        6 => Value::Int32(u.arbitrary()?),
+        7 => Value::Int64(u.arbitrary()?),
+        8 => Value::TimeStamp(u.arbitrary()?),
+        9 => {
+            let subtype = BinarySubtype::from(u8::arbitrary(u)?);
+
+            let data = match subtype {
+                BinarySubtype::Uuid | BinarySubtype::UuidOld | BinarySubtype::Md5 => {
+                    <[u8; 16]>::arbitrary(u)?.to_vec()
+                }
+                _ => Vec::arbitrary(u)?,
+            };
+
+            Value::Binary(subtype, data)
+        }
+        10 => Value::ObjectId(u.arbitrary()?),
+        11 => {
+            let millis = u.int_in_range(MIN_MILLIS..=MAX_MILLIS)?;
+            Value::datetime_from_millis(millis).unwrap_or(Value::Null)
+        }
+        12 => Value::Symbol(String::arbitrary(u)?),
+        13 => Value::Decimal128(u.arbitrary()?),
+        14 => Value::MinKey,
+        15 => Value::MaxKey,
+        16 => Value::Undefined,
+        17 => Value::Document(document(u, depth + 1)?),
+        18 => {
+            let mut array = Array::new();
+
+            while u.arbitrary()? {
+                array.push(value(u, depth + 1)?);
+            }
+
+            Value::Array(array)
+        }
+        _ => Value::JavaScriptCodeWithScope(String::arbitrary(u)?, Box::new(document(u, depth + 1)?)),
+    })
+}
+
+impl<'a> Arbitrary<'a> for Document {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Document> {
+        document(u, 1)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Value> {
+        value(u, 1)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ObjectId {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<ObjectId> {
+        Ok(ObjectId::with_bytes(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Decimal128 {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Decimal128> {
+        Ok(Decimal128::from_bytes(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for TimeStamp {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<TimeStamp> {
+        Ok(TimeStamp::from(u64::arbitrary(u)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use crate::Document;
+    use crate::decode::DecodeOptions;
+
+    #[test]
+    fn round_trip() {
+        // A fixed xorshift stream stands in for fuzzer input
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let bytes: Vec<u8> = (0..1 << 16).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect();
+
+        let mut u = Unstructured::new(&bytes);
+        let mut generated = 0;
+
+        while !u.is_empty() {
+            let doc = Document::arbitrary(&mut u).unwrap();
+            let encoded = doc.to_vec().unwrap();
+
+            assert_eq!(Document::from_slice_with_options(&encoded, DecodeOptions::strict()).unwrap(), doc);
+            generated += 1;
+        }
+
+        assert!(generated > 10);
+    }
+}
@@ -0,0 +1,143 @@
+//! Conversion traits between Rust types and `Value`
+//!
+//! `IntoValue` is implemented for everything that converts into a `Value`,
+//! and `FromValue` for types that can be extracted back out of one. With the
+//! `derive` feature, `#[derive(IntoValue, FromValue)]` implements both for
+//! structs with named fields (as documents), newtype structs (as their inner
+//! value) and fieldless enums (as the variant name):
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # fn main() {
+//! use bsonrs::{doc, IntoValue, FromValue};
+//!
+//! #[derive(IntoValue, FromValue)]
+//! struct Point {
+//!     x: f64,
+//!     y: f64,
+//! }
+//!
+//! let doc = doc!{"center": Point { x: 1.0, y: 2.0 }};
+//! let point: Point = doc.get_as("center").unwrap();
+//! assert_eq!(point.y, 2.0);
+//! # }
+//! # #[cfg(not(feature = "derive"))]
+//! # fn main() {}
+//! ```
+use chrono::{DateTime, Utc};
+
+use crate::value::{Value, Array};
+use crate::doc::{Document, Error, Result};
+use crate::object_id::ObjectId;
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl<T: Into<Value>> IntoValue for T {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+}
+
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+macro_rules! from_value_impls {
+    ($($T:ty => $variant:ident;)+) => {
+        $(
+            impl FromValue for $T {
+                fn from_value(value: Value) -> Result<Self> {
+                    match value {
+                        Value::$variant(v) => Ok(v),
+                        _ => Err(Error::UnexpectedType),
+                    }
+                }
+            }
+        )+
+    }
+}
+
+from_value_impls! {
+    f64 => Double;
+    String => String;
+    Array => Array;
+    Document => Document;
+    bool => Boolean;
+    i32 => Int32;
+    i64 => Int64;
+    ObjectId => ObjectId;
+    DateTime<Utc> => UTCDatetime;
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            v => T::from_value(v).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Array(arr) => arr.into_iter().map(T::from_value).collect(),
+            _ => Err(Error::UnexpectedType),
+        }
+    }
+}
+
+/// Take `key` out of `doc` and convert it. A missing key is treated as
+/// `Null`, so `Option` fields may be absent; any other type reports
+/// `Error::NotPresent`. Used by the `FromValue` derive.
+#[doc(hidden)]
+pub fn take_field<T: FromValue>(doc: &mut Document, key: &str) -> Result<T> {
+    match doc.remove(key) {
+        Some(v) => T::from_value(v),
+        None => T::from_value(Value::Null).map_err(|_| Error::NotPresent),
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod test {
+    use crate::{doc, Value, Document, IntoValue, FromValue};
+    use crate::doc::Error;
+
+    #[derive(IntoValue, FromValue, Debug, PartialEq, Clone)]
+    struct Point {
+        x: f64,
+        y: f64,
+        label: Option<String>,
+    }
+
+    #[derive(IntoValue, FromValue, Debug, PartialEq, Clone)]
+    struct Meters(f64);
+
+    #[derive(IntoValue, FromValue, Debug, PartialEq, Clone)]
+    enum Shape {
+        Circle,
+        Square,
+    }
+
+    #[test]
+    fn derive() {
+        let point = Point { x: 1.0, y: 2.0, label: None };
+        let doc = doc!{"center": point.clone(), "radius": Meters(3.0), "shape": Shape::Circle};
+
+        assert_eq!(doc, doc!{"center": {"x": 1.0, "y": 2.0, "label": null}, "radius": 3.0, "shape": "Circle"});
+        assert_eq!(doc.get_as::<Point>("center"), Ok(point));
+        assert_eq!(doc.get_as::<Meters>("radius"), Ok(Meters(3.0)));
+        assert_eq!(doc.get_as::<Shape>("shape"), Ok(Shape::Circle));
+        assert_eq!(Shape::from_value(Value::from("Triangle")), Err(Error::UnexpectedType));
+        assert_eq!(Point::from_value(doc!{"x": 1.0}.into()), Err(Error::NotPresent));
+        assert_eq!(Point::from_value(Document::new().into_value()), Err(Error::NotPresent));
+    }
+}
@@ -0,0 +1,478 @@
+//! Decimal128
+//!
+//! IEEE 754-2008 128-bit decimal floating point, in the binary integer
+//! decimal (BID) encoding used by BSON. Values are kept as their 16 wire
+//! bytes; `Display` and `FromStr` follow the string conversion rules of the
+//! BSON Decimal128 specification.
+//!
+//! ```
+//! use bsonrs::decimal128::Decimal128;
+//!
+//! let d: Decimal128 = "1.50".parse().unwrap();
+//! assert_eq!(d.to_string(), "1.50");
+//! assert_eq!(d.parts(), Some((false, 150, -2)));
+//! ```
+use std::{fmt, error, result};
+use std::str::FromStr;
+use std::convert::TryFrom;
+
+const EXPONENT_BIAS: i32 = 6176;
+const EXPONENT_MIN: i32 = -6176;
+const EXPONENT_MAX: i32 = 6111;
+const MAX_DIGITS: usize = 34;
+const COEFFICIENT_MASK: u128 = (1 << 113) - 1;
+const MAX_COEFFICIENT: u128 = 9_999_999_999_999_999_999_999_999_999_999_999;
+
+const SIGN_BIT: u128 = 1 << 127;
+const INFINITY: u128 = 0x1E << 122;
+const NAN: u128 = 0x1F << 122;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal128 {
+    bytes: [u8; 16],
+}
+
+impl Decimal128 {
+    pub const NAN: Decimal128 = Decimal128 { bytes: NAN.to_le_bytes() };
+    pub const INFINITY: Decimal128 = Decimal128 { bytes: INFINITY.to_le_bytes() };
+    pub const NEG_INFINITY: Decimal128 = Decimal128 { bytes: (INFINITY | SIGN_BIT).to_le_bytes() };
+
+    /// Construct from the 16 little-endian bytes used on the wire.
+    pub fn from_bytes(bytes: [u8; 16]) -> Decimal128 {
+        Decimal128 { bytes }
+    }
+
+    /// The 16 little-endian bytes used on the wire.
+    pub fn bytes(&self) -> [u8; 16] {
+        self.bytes
+    }
+
+    /// Build the finite value `(-1)^negative * coefficient * 10^exponent`.
+    /// Returns `None` if the coefficient has more than 34 digits or the
+    /// exponent is outside `-6176..=6111`.
+    pub fn from_parts(negative: bool, coefficient: u128, exponent: i32) -> Option<Decimal128> {
+        if coefficient > MAX_COEFFICIENT || !(EXPONENT_MIN..=EXPONENT_MAX).contains(&exponent) {
+            return None;
+        }
+
+        let mut bits = ((exponent + EXPONENT_BIAS) as u128) << 113 | coefficient;
+        if negative {
+            bits |= SIGN_BIT;
+        }
+
+        Some(Decimal128 { bytes: bits.to_le_bytes() })
+    }
+
+    /// Sign, coefficient and exponent of a finite value, or `None` for NaN
+    /// and infinities.
+    pub fn parts(&self) -> Option<(bool, u128, i32)> {
+        let bits = self.bits();
+        let negative = bits & SIGN_BIT != 0;
+
+        if (bits >> 122) & 0x1E == 0x1E {
+            return None;
+        }
+
+        if (bits >> 125) & 0x3 == 0x3 {
+            // The large-coefficient form always exceeds 34 digits, which the
+            // spec treats as zero.
+            let exponent = ((bits >> 111) & 0x3FFF) as i32 - EXPONENT_BIAS;
+            return Some((negative, 0, exponent));
+        }
+
+        let exponent = ((bits >> 113) & 0x3FFF) as i32 - EXPONENT_BIAS;
+        let mut coefficient = bits & COEFFICIENT_MASK;
+
+        if coefficient > MAX_COEFFICIENT {
+            coefficient = 0;
+        }
+
+        Some((negative, coefficient, exponent))
+    }
+
+    /// The value as an `i128`, if it is a whole number in range.
+    pub fn to_i128(&self) -> Option<i128> {
+        let (negative, magnitude) = self.integer()?;
+
+        if negative {
+            // Allows `i128::MIN`, whose magnitude is one past `i128::MAX`
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    /// The value as a `u128`, if it is a non-negative whole number in range.
+    pub fn to_u128(&self) -> Option<u128> {
+        match self.integer()? {
+            (false, magnitude) | (true, magnitude @ 0) => Some(magnitude),
+            _ => None,
+        }
+    }
+
+    /// Sign and magnitude of a whole number.
+    fn integer(&self) -> Option<(bool, u128)> {
+        let (negative, coefficient, exponent) = self.parts()?;
+
+        if exponent >= 0 {
+            let scale = 10u128.checked_pow(exponent as u32)?;
+            return coefficient.checked_mul(scale).map(|m| (negative, m));
+        }
+
+        // Trailing zeros may still make a negative exponent whole
+        let scale = match 10u128.checked_pow(exponent.unsigned_abs()) {
+            Some(scale) => scale,
+            None => return if coefficient == 0 { Some((negative, 0)) } else { None },
+        };
+
+        if coefficient % scale == 0 {
+            Some((negative, coefficient / scale))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_nan(&self) -> bool {
+        self.bits() & NAN == NAN
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        self.bits() & NAN == INFINITY
+    }
+
+    pub fn is_sign_negative(&self) -> bool {
+        self.bits() & SIGN_BIT != 0
+    }
+
+    fn bits(&self) -> u128 {
+        u128::from_le_bytes(self.bytes)
+    }
+}
+
+impl fmt::Display for Decimal128 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let (negative, coefficient, exponent) = match self.parts() {
+            Some(parts) => parts,
+            None if self.is_nan() => return fmt.write_str("NaN"),
+            None if self.is_sign_negative() => return fmt.write_str("-Infinity"),
+            None => return fmt.write_str("Infinity"),
+        };
+
+        if negative {
+            fmt.write_str("-")?;
+        }
+
+        let digits = coefficient.to_string();
+        let adjusted = exponent + digits.len() as i32 - 1;
+
+        if exponent <= 0 && adjusted >= -6 {
+            if exponent == 0 {
+                return fmt.write_str(&digits);
+            }
+
+            let scale = -exponent as usize;
+
+            if digits.len() > scale {
+                let (int, frac) = digits.split_at(digits.len() - scale);
+                write!(fmt, "{}.{}", int, frac)
+            } else {
+                write!(fmt, "0.{}{}", "0".repeat(scale - digits.len()), digits)
+            }
+        } else {
+            let (first, rest) = digits.split_at(1);
+            fmt.write_str(first)?;
+
+            if !rest.is_empty() {
+                write!(fmt, ".{}", rest)?;
+            }
+
+            write!(fmt, "E{}{}", if adjusted < 0 { "-" } else { "+" }, adjusted.abs())
+        }
+    }
+}
+
+impl fmt::Debug for Decimal128 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Decimal128({})", self)
+    }
+}
+
+impl FromStr for Decimal128 {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> result::Result<Decimal128, ParseError> {
+        let err = || ParseError { input: s.to_string() };
+
+        let (negative, rest) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        match rest.to_ascii_lowercase().as_str() {
+            "nan" => return Ok(Decimal128::NAN),
+            "inf" | "infinity" if negative => return Ok(Decimal128::NEG_INFINITY),
+            "inf" | "infinity" => return Ok(Decimal128::INFINITY),
+            _ => ()
+        }
+
+        let (mantissa, mut exponent) = match rest.find(['e', 'E']) {
+            Some(i) => {
+                let exp = &rest[i + 1..];
+                let digits = exp.strip_prefix('+').unwrap_or(exp);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+                    return Err(err());
+                }
+                (&rest[..i], exp.parse::<i64>().map_err(|_| err())?)
+            }
+            None => (rest, 0),
+        };
+
+        let (int, frac) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+            None => (mantissa, ""),
+        };
+
+        if int.is_empty() && frac.is_empty() {
+            return Err(err());
+        }
+
+        if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+
+        exponent -= frac.len() as i64;
+
+        let mut digits: String = int.chars().chain(frac.chars()).skip_while(|&c| c == '0').collect();
+
+        // Drop trailing zeros that don't fit, raising the exponent to match
+        while digits.len() > MAX_DIGITS && digits.ends_with('0') {
+            digits.pop();
+            exponent += 1;
+        }
+
+        if digits.len() > MAX_DIGITS {
+            return Err(err());
+        }
+
+        let mut coefficient: u128 = if digits.is_empty() { 0 } else { digits.parse().map_err(|_| err())? };
+
+        // Clamp the exponent into range without changing the value
+        while exponent > i64::from(EXPONENT_MAX) && coefficient != 0 && coefficient * 10 <= MAX_COEFFICIENT {
+            coefficient *= 10;
+            exponent -= 1;
+        }
+
+        while exponent < i64::from(EXPONENT_MIN) && coefficient != 0 && coefficient % 10 == 0 {
+            coefficient /= 10;
+            exponent += 1;
+        }
+
+        if coefficient == 0 {
+            exponent = exponent.clamp(i64::from(EXPONENT_MIN), i64::from(EXPONENT_MAX));
+        }
+
+        let exponent = i32::try_from(exponent).map_err(|_| err())?;
+        Decimal128::from_parts(negative, coefficient, exponent).ok_or_else(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub input: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid Decimal128 string `{}`", self.input)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// A value that can't be represented exactly on the other side of a
+/// conversion to or from another decimal type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionError;
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "decimal value out of range for the target type")
+    }
+}
+
+impl error::Error for ConversionError {}
+
+#[cfg(feature = "rust_decimal")]
+impl From<rust_decimal::Decimal> for Decimal128 {
+    fn from(d: rust_decimal::Decimal) -> Decimal128 {
+        let mantissa = d.mantissa();
+
+        // A 96-bit mantissa has at most 29 digits and the scale at most 28
+        Decimal128::from_parts(mantissa < 0, mantissa.unsigned_abs(), -(d.scale() as i32))
+            .expect("rust_decimal values always fit")
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl TryFrom<Decimal128> for rust_decimal::Decimal {
+    type Error = ConversionError;
+
+    fn try_from(d: Decimal128) -> result::Result<rust_decimal::Decimal, ConversionError> {
+        let (negative, mut coefficient, exponent) = d.parts().ok_or(ConversionError)?;
+
+        let mut scale = if exponent > 0 {
+            let factor = 10u128.checked_pow(exponent as u32).ok_or(ConversionError)?;
+            coefficient = coefficient.checked_mul(factor).ok_or(ConversionError)?;
+            0
+        } else {
+            exponent.unsigned_abs()
+        };
+
+        while scale > rust_decimal::Decimal::MAX_SCALE && coefficient % 10 == 0 {
+            coefficient /= 10;
+            scale -= 1;
+        }
+
+        let mantissa = i128::try_from(coefficient).map_err(|_| ConversionError)?;
+        let mantissa = if negative { -mantissa } else { mantissa };
+
+        rust_decimal::Decimal::try_from_i128_with_scale(mantissa, scale).map_err(|_| ConversionError)
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl TryFrom<&bigdecimal::BigDecimal> for Decimal128 {
+    type Error = ConversionError;
+
+    fn try_from(d: &bigdecimal::BigDecimal) -> result::Result<Decimal128, ConversionError> {
+        // The parser already drops trailing zeros and clamps the exponent
+        // where it can do so exactly
+        let (digits, scale) = d.as_bigint_and_exponent();
+        format!("{}E{}", digits, -scale).parse().map_err(|_| ConversionError)
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl TryFrom<bigdecimal::BigDecimal> for Decimal128 {
+    type Error = ConversionError;
+
+    fn try_from(d: bigdecimal::BigDecimal) -> result::Result<Decimal128, ConversionError> {
+        Decimal128::try_from(&d)
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl TryFrom<Decimal128> for bigdecimal::BigDecimal {
+    type Error = ConversionError;
+
+    /// Fails only for NaN and infinities.
+    fn try_from(d: Decimal128) -> result::Result<bigdecimal::BigDecimal, ConversionError> {
+        use bigdecimal::num_bigint::BigInt;
+
+        let (negative, coefficient, exponent) = d.parts().ok_or(ConversionError)?;
+        let digits = BigInt::from(coefficient);
+        let digits = if negative { -digits } else { digits };
+
+        Ok(bigdecimal::BigDecimal::new(digits, -i64::from(exponent)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Decimal128;
+
+    fn round_trip(s: &str) -> String {
+        s.parse::<Decimal128>().unwrap().to_string()
+    }
+
+    #[test]
+    fn to_string() {
+        assert_eq!(round_trip("0"), "0");
+        assert_eq!(round_trip("-0"), "-0");
+        assert_eq!(round_trip("1.50"), "1.50");
+        assert_eq!(round_trip("0.001234"), "0.001234");
+        assert_eq!(round_trip("0.0000001234"), "1.234E-7");
+        assert_eq!(round_trip("1E+3"), "1E+3");
+        assert_eq!(round_trip("123456789012345678901234567890123400000"), "1.234567890123456789012345678901234E+38");
+        assert!("12345678901234567890123456789012345678".parse::<Decimal128>().is_err());
+        assert_eq!(round_trip("-Infinity"), "-Infinity");
+        assert_eq!(round_trip("NaN"), "NaN");
+        assert_eq!(round_trip("1E+6144"), "1.000000000000000000000000000000000E+6144");
+        assert!("1.2.3".parse::<Decimal128>().is_err());
+        assert!("1E+7000".parse::<Decimal128>().is_err());
+        assert!("".parse::<Decimal128>().is_err());
+    }
+
+    #[test]
+    fn integers() {
+        let parse = |s: &str| s.parse::<Decimal128>().unwrap();
+
+        assert_eq!(parse("42").to_i128(), Some(42));
+        assert_eq!(parse("-4.20E+1").to_i128(), Some(-42));
+        assert_eq!(parse("1E+3").to_u128(), Some(1000));
+        assert_eq!(parse("-0").to_u128(), Some(0));
+        assert_eq!(parse("-1").to_u128(), None);
+        assert_eq!(parse("1.5").to_i128(), None);
+        assert_eq!(parse("1E+40").to_i128(), None);
+        assert_eq!(parse("0E-6000").to_i128(), Some(0));
+        assert_eq!(Decimal128::NAN.to_i128(), None);
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn rust_decimal() {
+        use std::convert::TryFrom;
+        use rust_decimal::Decimal;
+
+        let parse = |s: &str| s.parse::<Decimal128>().unwrap();
+
+        for s in &["0", "-19.99", "0.0000000000000000000000000001", "79228162514264337593543950335"] {
+            let d: Decimal = s.parse().unwrap();
+            assert_eq!(Decimal128::from(d), parse(s));
+            assert_eq!(Decimal::try_from(parse(s)).unwrap(), d);
+        }
+
+        assert_eq!(Decimal::try_from(parse("1.5E+3")).unwrap(), Decimal::from(1500));
+        assert_eq!(Decimal::try_from(parse("1.000000000000000000000000000000")).unwrap(), Decimal::from(1));
+        assert!(Decimal::try_from(parse("1E+30")).is_err());
+        assert!(Decimal::try_from(parse("1E-29")).is_err());
+        assert!(Decimal::try_from(Decimal128::NAN).is_err());
+
+        let doc = crate::doc!{"price": (crate::Value::from(Decimal::new(1999, 2))), "huge": (parse("1E+30"))};
+        assert_eq!(doc.get_decimal("price").unwrap(), Decimal::new(1999, 2));
+        assert!(doc.get_decimal("huge").is_err());
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    #[test]
+    fn bigdecimal() {
+        use std::convert::TryFrom;
+        use bigdecimal::BigDecimal;
+
+        let parse = |s: &str| s.parse::<Decimal128>().unwrap();
+
+        for s in &["0", "-19.99", "1E+6111", "-1.234E-6000"] {
+            let d: BigDecimal = s.parse().unwrap();
+            assert_eq!(Decimal128::try_from(&d).unwrap(), parse(s));
+            assert_eq!(BigDecimal::try_from(parse(s)).unwrap(), d);
+        }
+
+        let too_precise: BigDecimal = "1.00000000000000000000000000000000001".parse().unwrap();
+        assert!(Decimal128::try_from(too_precise).is_err());
+        assert!(BigDecimal::try_from(Decimal128::INFINITY).is_err());
+    }
+
+    #[test]
+    fn bytes() {
+        // 1 with exponent 0 from the BSON corpus: 0x3040000000000000 0x0000000000000001
+        let one: Decimal128 = "1".parse().unwrap();
+        let mut expected = [0u8; 16];
+        expected[0] = 0x01;
+        expected[14] = 0x40;
+        expected[15] = 0x30;
+
+        assert_eq!(one.bytes(), expected);
+        assert_eq!(Decimal128::from_bytes(expected), one);
+    }
+}
@@ -1,20 +1,22 @@
 use std::{io, error, fmt, string};
-use std::io::{Read, Cursor};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Read;
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use chrono::Utc;
-use chrono::offset::{TimeZone, LocalResult};
-use serde::de::Deserialize;
+use serde::de::{Deserialize, DeserializeOwned};
 
 use crate::spec::{ElementType, BinarySubtype};
-use crate::value::{Value, Array};
-use crate::doc::Document;
-use crate::serde_impl::decode::Decoder;
+use crate::value::{Value, Array, TimeStamp, clamped_datetime};
+use crate::doc::{self, Document};
+use crate::serde_impl::decode::{Decoder, RawDecoder};
+use crate::raw::{self, RawDocument, RawValue, RawIter};
 use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
 
-const MAX_BSON_SIZE: i32 = 16 * 1024 * 1024;
+pub(crate) const MAX_BSON_SIZE: i32 = 16 * 1024 * 1024;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DecodeError {
     IoError(io::Error),
     FromUtf8Error(string::FromUtf8Error),
@@ -31,7 +33,62 @@ pub enum DecodeError {
     InvalidValue(String),
     InvalidTimestamp(i64),
     AmbiguousTimestamp(i64),
-    Unknown(String)
+    /// Documents and arrays nested deeper than `DecodeOptions::max_depth`.
+    DepthLimitExceeded(usize),
+    /// A key repeated within one document under `DuplicateKeys::Error`.
+    DuplicateKey(String),
+    /// A missing or mistyped field met while picking a `Document` apart.
+    DocumentError(doc::Error),
+    Unknown(String),
+    /// Where in the buffer decoding went wrong, as `validate` finds it: the
+    /// byte offset counted from the start of the document, and the key of
+    /// the innermost element the problem is in. Errors from decoding BSON
+    /// bytes come back wrapped in this; `into_inner` strips it off again.
+    Context {
+        offset: usize,
+        key: Option<String>,
+        error: Box<DecodeError>,
+    },
+    /// A serde error from inside a document or array, with the path to the
+    /// field it was raised for, such as `server.listeners[2].port`.
+    Field {
+        path: String,
+        error: Box<DecodeError>,
+    },
+}
+
+impl DecodeError {
+    /// The byte offset the error was found at, if known.
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            DecodeError::Context { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// The key of the element the error was found in, if known.
+    pub fn key(&self) -> Option<&str> {
+        match *self {
+            DecodeError::Context { ref key, .. } => key.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The path of the field that failed to deserialize.
+    pub fn path(&self) -> Option<&str> {
+        match *self {
+            DecodeError::Field { ref path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The error without its position or field path.
+    pub fn into_inner(self) -> DecodeError {
+        match self {
+            DecodeError::Context { error, .. } | DecodeError::Field { error, .. } => error.into_inner(),
+            other => other,
+        }
+    }
 }
 
 impl From<io::Error> for DecodeError {
@@ -40,6 +97,12 @@ impl From<io::Error> for DecodeError {
     }
 }
 
+impl From<doc::Error> for DecodeError {
+    fn from(err: doc::Error) -> DecodeError {
+        DecodeError::DocumentError(err)
+    }
+}
+
 impl From<string::FromUtf8Error> for DecodeError {
     fn from(err: string::FromUtf8Error) -> DecodeError {
         DecodeError::FromUtf8Error(err)
@@ -72,36 +135,28 @@ impl fmt::Display for DecodeError {
             DecodeError::InvalidValue(ref desc) => desc.fmt(fmt),
             DecodeError::InvalidTimestamp(ref i) => write!(fmt, "no such local time {}", i),
             DecodeError::AmbiguousTimestamp(ref i) => write!(fmt, "ambiguous local time {}", i),
+            DecodeError::DepthLimitExceeded(limit) => write!(fmt, "documents nested deeper than {} levels", limit),
+            DecodeError::DuplicateKey(ref key) => write!(fmt, "Duplicate key `{}`", key),
+            DecodeError::DocumentError(ref inner) => inner.fmt(fmt),
             DecodeError::Unknown(ref inner) => inner.fmt(fmt),
+            DecodeError::Context { offset, key: Some(ref key), ref error } => {
+                write!(fmt, "invalid BSON at byte {} (key `{}`): {}", offset, key, error)
+            }
+            DecodeError::Context { offset, key: None, ref error } => {
+                write!(fmt, "invalid BSON at byte {}: {}", offset, error)
+            }
+            DecodeError::Field { ref path, ref error } => write!(fmt, "{} at \"{}\"", error, path),
         }
     }
 }
 
 impl error::Error for DecodeError {
-    fn description(&self) -> &str {
-        match *self {
-            DecodeError::IoError(ref inner) => inner.description(),
-            DecodeError::FromUtf8Error(ref inner) => inner.description(),
-            DecodeError::UnrecognizedElementType(_) => "Unrecognized element type",
-            DecodeError::InvalidArrayKey(_, _) => "Invalid array key",
-            DecodeError::ExpectedField(_) => "Expected a field",
-            DecodeError::UnknownField(_) => "Found an unknown field",
-            DecodeError::SyntaxError(ref inner) => inner,
-            DecodeError::EndOfStream => "End of stream",
-            DecodeError::InvalidType(ref desc) => desc,
-            DecodeError::InvalidLength(_, ref desc) => desc,
-            DecodeError::DuplicatedField(_) => "Duplicated field",
-            DecodeError::UnknownVariant(_) => "Unknown variant",
-            DecodeError::InvalidValue(ref desc) => desc,
-            DecodeError::InvalidTimestamp(..) => "no such local time",
-            DecodeError::AmbiguousTimestamp(..) => "ambiguous local time",
-            DecodeError::Unknown(ref inner) => inner,
-        }
-    }
-    fn cause(&self) -> Option<&dyn error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             DecodeError::IoError(ref inner) => Some(inner),
             DecodeError::FromUtf8Error(ref inner) => Some(inner),
+            DecodeError::DocumentError(ref inner) => Some(inner),
+            DecodeError::Context { ref error, .. } | DecodeError::Field { ref error, .. } => Some(&**error),
             _ => None,
         }
     }
@@ -109,205 +164,1691 @@ impl error::Error for DecodeError {
 
 pub type DecodeResult<T> = Result<T, DecodeError>;
 
-pub(crate) fn read_string(reader: &mut impl Read) -> DecodeResult<String> {
-    let len = reader.read_i32::<LittleEndian>()?;
+/// A problem found by `validate`, with the offset into the buffer of the
+/// element, document or value it was found in. Decoding reports the same
+/// position as `DecodeError::Context`.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub offset: usize,
+    /// The key of the innermost element the problem is in, as far as keys
+    /// could be read.
+    pub key: Option<String>,
+    pub error: DecodeError,
+}
 
-    if len < 1 || len > MAX_BSON_SIZE {
-        return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for UTF-8 string", len)));
+impl fmt::Display for ValidationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.key {
+            Some(ref key) => write!(fmt, "invalid BSON at byte {} (key `{}`): {}", self.offset, key, self.error),
+            None => write!(fmt, "invalid BSON at byte {}: {}", self.offset, self.error),
+        }
     }
+}
 
-    let mut buf = Vec::with_capacity(len as usize - 1);
-    reader.take(len as u64 -1).read_to_end(&mut buf)?;
-    let s = String::from_utf8_lossy(&buf).to_string();
+impl error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
 
-    reader.read_u8()?; // The last 0x00
+impl From<ValidationError> for DecodeError {
+    fn from(err: ValidationError) -> DecodeError {
+        DecodeError::Context { offset: err.offset, key: err.key, error: Box::new(err.error) }
+    }
+}
 
-    Ok(s)
+/// Options controlling how BSON data is decoded. Start from
+/// `DecodeOptions::default()` or `DecodeOptions::strict()` and set the
+/// fields to change; more options may be added.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// Deserialize `Null` into the zero value of the requested type (`0`,
+    /// `false`, `""`, empty sequences and maps) instead of failing.
+    pub null_as_default: bool,
+    /// Accept a `Double` holding a whole number where an integer is
+    /// expected. Integers are always accepted for floats, and for wider or
+    /// narrower integer types when the value fits.
+    pub lenient_numerics: bool,
+    /// How deeply documents and arrays may nest, counting the top-level
+    /// document as 1, before decoding fails with `DepthLimitExceeded`.
+    /// Guards against running out of stack on hostile input. Defaults to
+    /// `DEFAULT_MAX_DEPTH`.
+    pub max_depth: usize,
+    /// What to do with strings that aren't valid UTF-8.
+    pub utf8: Utf8Policy,
+    /// What to do when a document repeats a key.
+    pub duplicate_keys: DuplicateKeys,
+    /// Reject values the BSON spec rules out even though they can be read:
+    /// booleans other than 0 and 1, and UUID or MD5 binary that isn't 16
+    /// bytes long. Lengths are checked either way. Defaults to `false`.
+    pub strict_values: bool,
 }
 
-pub(crate) fn read_cstring(reader: &mut impl Read) -> DecodeResult<String> {
-    let mut v = Vec::new();
+/// Handling of invalid UTF-8 in strings, JavaScript code and symbols.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Policy {
+    /// Replace invalid sequences with U+FFFD.
+    #[default]
+    Lossy,
+    /// Fail with `DecodeError::FromUtf8Error`.
+    Strict,
+    /// Keep an invalid string's bytes as `Generic` binary. Code and
+    /// symbols can't be kept that way and fail as with `Strict`.
+    Bytes,
+}
 
-    loop {
-        let c = reader.read_u8()?;
-        if c == 0 {
-            break;
+/// Handling of keys that appear more than once in the same document. BSON
+/// allows them, but they can be used to make different consumers see
+/// different values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Keep the last value, at the position of the first.
+    #[default]
+    LastWins,
+    /// Keep the first value and ignore the rest.
+    FirstWins,
+    /// Fail with `DecodeError::DuplicateKey`.
+    Error,
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 100;
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        DecodeOptions {
+            null_as_default: false,
+            lenient_numerics: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            utf8: Utf8Policy::default(),
+            duplicate_keys: DuplicateKeys::default(),
+            strict_values: false,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Everything the BSON spec disallows is an error: invalid UTF-8 in any
+    /// string, and the values `strict_values` covers. This is what the
+    /// MongoDB BSON corpus expects of a decoder.
+    pub fn strict() -> DecodeOptions {
+        DecodeOptions {
+            utf8: Utf8Policy::Strict,
+            strict_values: true,
+            ..DecodeOptions::default()
+        }
+    }
+}
+
+fn check_boolean(byte: u8, options: DecodeOptions) -> DecodeResult<bool> {
+    match byte {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ if options.strict_values => Err(DecodeError::InvalidValue(format!("invalid boolean value {}", byte))),
+        _ => Ok(true),
+    }
+}
+
+fn check_binary(subtype: BinarySubtype, len: usize, options: DecodeOptions) -> DecodeResult<()> {
+    let fixed = matches!(subtype, BinarySubtype::Uuid | BinarySubtype::UuidOld | BinarySubtype::Md5);
+
+    if options.strict_values && fixed && len != 16 {
+        return Err(DecodeError::InvalidLength(len, format!("{:?} binary must be 16 bytes, not {}", subtype, len)));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn check_depth(depth: usize, options: DecodeOptions) -> DecodeResult<()> {
+    if depth > options.max_depth {
+        return Err(DecodeError::DepthLimitExceeded(options.max_depth));
+    }
+
+    Ok(())
+}
+
+/// Where a length prefixed value started, and the bound to restore once it
+/// has been read.
+struct Frame {
+    start: usize,
+    outer: usize,
+}
+
+/// Input to the decoder. Reads stop at the declared end of the innermost
+/// document, array or code with scope, so that a wrong length is caught
+/// where it is instead of garbling whatever follows.
+trait Source {
+    fn pos(&self) -> usize;
+
+    fn end(&self) -> usize;
+
+    fn set_end(&mut self, end: usize);
+
+    /// Fill `buf` from the input.
+    fn next_into(&mut self, buf: &mut [u8]) -> DecodeResult<()>;
+
+    fn next_bytes(&mut self, len: usize) -> DecodeResult<Cow<'_, [u8]>>;
+
+    /// The bytes up to the next null, which is consumed but not returned.
+    fn next_cstr(&mut self) -> DecodeResult<Cow<'_, [u8]>>;
+
+    /// Fail unless `len` more bytes fit before the end of the innermost
+    /// document.
+    fn check_remaining(&self, len: usize) -> DecodeResult<()> {
+        if len > self.end() - self.pos() {
+            return Err(DecodeError::EndOfStream);
+        }
+
+        Ok(())
+    }
+
+    fn next_u8(&mut self) -> DecodeResult<u8> {
+        let mut buf = [0; 1];
+        self.next_into(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn next_i32(&mut self) -> DecodeResult<i32> {
+        let mut buf = [0; 4];
+        self.next_into(&mut buf)?;
+        Ok(i32::from_le_bytes(buf))
+    }
+
+    fn next_i64(&mut self) -> DecodeResult<i64> {
+        let mut buf = [0; 8];
+        self.next_into(&mut buf)?;
+        Ok(i64::from_le_bytes(buf))
+    }
+
+    /// The contents of a length prefixed, null terminated string.
+    fn next_string_bytes(&mut self) -> DecodeResult<Cow<'_, [u8]>> {
+        let len = self.next_i32()?;
+
+        if len < 1 || len > MAX_BSON_SIZE {
+            return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for UTF-8 string", len)));
         }
-        v.push(c);
+
+        self.check_remaining(len as usize)?;
+
+        let mut data = self.next_bytes(len as usize)?;
+
+        if data.last() != Some(&0) {
+            return Err(DecodeError::InvalidValue("string is not null terminated".to_string()));
+        }
+
+        match data {
+            Cow::Borrowed(ref mut b) => *b = &b[..b.len() - 1],
+            Cow::Owned(ref mut v) => {
+                v.pop();
+            }
+        }
+
+        Ok(data)
     }
 
-    Ok(String::from_utf8(v)?)
+    fn next_string(&mut self, policy: Utf8Policy) -> DecodeResult<String> {
+        let data = self.next_string_bytes()?;
+
+        match policy {
+            Utf8Policy::Lossy => Ok(String::from_utf8_lossy(&data).into_owned()),
+            Utf8Policy::Strict | Utf8Policy::Bytes => String::from_utf8(data.into_owned()).map_err(From::from),
+        }
+    }
+
+    /// Keys and regular expressions, which are always strict UTF-8.
+    fn next_cstring(&mut self) -> DecodeResult<String> {
+        let data = self.next_cstr()?;
+        Ok(String::from_utf8(data.into_owned())?)
+    }
+
+    /// Read a length prefix of at least `min` and bound reads to it.
+    fn open(&mut self, min: i32, what: &str) -> DecodeResult<Frame> {
+        let start = self.pos();
+        let len = self.next_i32()?;
+
+        if len < min || len > MAX_BSON_SIZE {
+            return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for {}", len, what)));
+        }
+
+        if len as usize > self.end() - start {
+            return Err(DecodeError::InvalidLength(
+                len as usize,
+                format!("{} of {} bytes runs past the end of its parent", what, len)
+            ));
+        }
+
+        let outer = self.end();
+        self.set_end(start + len as usize);
+
+        Ok(Frame { start, outer })
+    }
+
+    /// Check that everything up to the declared end was read.
+    fn close(&mut self, frame: Frame, what: &str) -> DecodeResult<()> {
+        if self.pos() != self.end() {
+            return Err(DecodeError::InvalidLength(
+                self.end() - frame.start,
+                format!("{} ends after {} bytes", what, self.pos() - frame.start)
+            ));
+        }
+
+        self.set_end(frame.outer);
+
+        Ok(())
+    }
 }
 
-#[inline]
-pub(crate) fn read_i32(reader: &mut impl Read) -> DecodeResult<i32> {
-    reader.read_i32::<LittleEndian>().map_err(From::from)
+/// A `Source` indexing straight into a buffer. Nothing is copied until a
+/// value is built, and keys are found with `memchr`.
+struct Slice<'a> {
+    data: &'a [u8],
+    pos: usize,
+    end: usize,
 }
 
-#[inline]
-pub(crate) fn read_i64(reader: &mut impl Read) -> DecodeResult<i64> {
-    reader.read_i64::<LittleEndian>().map_err(From::from)
+impl<'a> Slice<'a> {
+    fn new(data: &'a [u8]) -> Slice<'a> {
+        Slice {
+            data,
+            pos: 0,
+            end: data.len(),
+        }
+    }
 }
 
-#[inline]
-pub(crate) fn read_u64(reader: &mut impl Read) -> DecodeResult<u64> {
-    reader.read_u64::<LittleEndian>().map_err(From::from)
+impl<'a> Source for Slice<'a> {
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.end
+    }
+
+    fn set_end(&mut self, end: usize) {
+        self.end = end;
+    }
+
+    fn next_into(&mut self, buf: &mut [u8]) -> DecodeResult<()> {
+        let data = self.next_bytes(buf.len())?;
+        buf.copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn next_bytes(&mut self, len: usize) -> DecodeResult<Cow<'_, [u8]>> {
+        self.check_remaining(len)?;
+
+        let data = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+
+        Ok(Cow::Borrowed(data))
+    }
+
+    fn next_cstr(&mut self) -> DecodeResult<Cow<'_, [u8]>> {
+        let rest = &self.data[self.pos..self.end];
+        let len = memchr::memchr(0, rest).ok_or(DecodeError::EndOfStream)?;
+
+        self.pos += len + 1;
+
+        Ok(Cow::Borrowed(&rest[..len]))
+    }
 }
 
-fn decode_array(reader: &mut impl Read) -> DecodeResult<Array> {
+fn decode_array(reader: &mut impl Source, options: DecodeOptions, depth: usize) -> DecodeResult<Array> {
+    check_depth(depth, options)?;
+
     let mut arr = Array::new();
 
-    // disregard the length: using Read::take causes infinite type recursion
-    read_i32(reader)?;
+    let frame = reader.open(5, "array")?;
 
     loop {
-        let tag = reader.read_u8()?;
+        let tag = reader.next_u8()?;
         if tag == 0 {
             break;
         }
 
         // check that the key is as expected
-        let key = read_cstring(reader)?;
-        match key.parse::<usize>() {
-            Err(..) => return Err(DecodeError::InvalidArrayKey(arr.len(), key)),
-            Ok(idx) => {
-                if idx != arr.len() {
-                    return Err(DecodeError::InvalidArrayKey(arr.len(), key));
-                }
-            }
+        let key = reader.next_cstring()?;
+        if key.parse::<usize>() != Ok(arr.len()) {
+            return Err(DecodeError::InvalidArrayKey(arr.len(), key));
         }
 
-        let val = decode_bson(reader, tag)?;
+        let val = decode_bson(reader, tag, options, depth)?;
         arr.push(val)
     }
 
+    reader.close(frame, "array")?;
+
     Ok(arr)
 }
 
-fn decode_bson(reader: &mut impl Read, tag: u8) -> DecodeResult<Value> {
+/// `depth` is that of the document or array holding the value.
+fn decode_bson(reader: &mut impl Source, tag: u8, options: DecodeOptions, depth: usize) -> DecodeResult<Value> {
     match ElementType::from(tag) {
         Some(ElementType::Double) => {
-            Ok(Value::Double(reader.read_f64::<LittleEndian>()?))
+            Ok(Value::Double(f64::from_bits(reader.next_i64()? as u64)))
         }
         Some(ElementType::Utf8String) => {
-            read_string(reader).map(Value::String)
+            match options.utf8 {
+                Utf8Policy::Bytes => {
+                    let buf = reader.next_string_bytes()?.into_owned();
+
+                    Ok(String::from_utf8(buf)
+                        .map(Value::String)
+                        .unwrap_or_else(|err| Value::Binary(BinarySubtype::Generic, err.into_bytes())))
+                }
+                policy => reader.next_string(policy).map(Value::String),
+            }
         }
         Some(ElementType::Document) => {
-            decode_document(reader).map(Value::Document)
+            decode_document_at(reader, options, depth + 1).map(Value::Document)
         }
         Some(ElementType::Array) => {
-            decode_array(reader).map(Value::Array)
+            decode_array(reader, options, depth + 1).map(Value::Array)
         }
         Some(ElementType::Binary) => {
-            let len = read_i32(reader)?;
-            let subtype = BinarySubtype::from(reader.read_u8()?);
-            let mut data = Vec::with_capacity(len as usize);
-            
-            reader.take(len as u64).read_to_end(&mut data)?;
-            
+            let mut len = reader.next_i32()?;
+
+            if len < 0 {
+                return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for binary", len)));
+            }
+
+            // Checked before allocating, so a bogus length can't ask for
+            // more memory than the document could hold
+            if len as usize >= reader.end() - reader.pos() {
+                return Err(DecodeError::InvalidLength(
+                    len as usize,
+                    format!("binary of {} bytes runs past the end of its document", len)
+                ));
+            }
+
+            let subtype = BinarySubtype::from(reader.next_u8()?);
+
+            // The old binary subtype repeats the payload length inside the data
+            if subtype == BinarySubtype::BinaryOld {
+                if len < 4 {
+                    return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for old binary", len)));
+                }
+
+                let inner = reader.next_i32()?;
+                if inner != len - 4 {
+                    return Err(DecodeError::InvalidLength(
+                        inner as usize,
+                        format!("invalid inner length {} for old binary of length {}", inner, len)
+                    ));
+                }
+
+                len = inner;
+            }
+
+            check_binary(subtype, len as usize, options)?;
+            let data = reader.next_bytes(len as usize)?.into_owned();
+
             Ok(Value::Binary(subtype, data))
         }
         Some(ElementType::ObjectId) => {
             let mut objid = [0; 12];
-            
-            for x in &mut objid {
-                *x = reader.read_u8()?;
-            }
-            
+            reader.next_into(&mut objid)?;
+
             Ok(Value::ObjectId(ObjectId::with_bytes(objid)))
         }
         Some(ElementType::Boolean) => {
-            Ok(Value::Boolean(reader.read_u8()? != 0))
+            check_boolean(reader.next_u8()?, options).map(Value::Boolean)
         }
         Some(ElementType::NullValue) => {
             Ok(Value::Null)
         }
+        Some(ElementType::MinKey) => {
+            Ok(Value::MinKey)
+        }
+        Some(ElementType::MaxKey) => {
+            Ok(Value::MaxKey)
+        }
+        Some(ElementType::Undefiend) => {
+            Ok(Value::Undefined)
+        }
         Some(ElementType::RegularExpression) => {
-            let pat = read_cstring(reader)?;
-            let opt = read_cstring(reader)?;
-            
+            let pat = reader.next_cstring()?;
+            let opt = reader.next_cstring()?;
+
             Ok(Value::RegExp(pat, opt))
         }
         Some(ElementType::JavaScriptCode) => {
-            read_string(reader).map(Value::JavaScriptCode)
+            reader.next_string(options.utf8).map(Value::JavaScriptCode)
         }
         Some(ElementType::JavaScriptCodeWithScope) => {
-            // disregard the length:
-            //     using Read::take causes infinite type recursion
-            read_i32(reader)?;
+            let frame = reader.open(14, "code with scope")?;
+
+            let code = reader.next_string(options.utf8)?;
+            let scope = decode_document_at(reader, options, depth + 1)?;
+
+            reader.close(frame, "code with scope")?;
 
-            let code = read_string(reader)?;
-            let scope = decode_document(reader)?;
-            
-            Ok(Value::JavaScriptCodeWithScope(code, scope))
+            Ok(Value::JavaScriptCodeWithScope(code, Box::new(scope)))
         }
         Some(ElementType::Int32) => {
-            read_i32(reader).map(Value::Int32)
+            reader.next_i32().map(Value::Int32)
         }
         Some(ElementType::Int64) => {
-            read_i64(reader).map(Value::Int64)
+            reader.next_i64().map(Value::Int64)
         }
         Some(ElementType::TimeStamp) => {
-            read_u64(reader).map(Value::TimeStamp)
+            reader.next_i64().map(|v| Value::TimeStamp(TimeStamp::from(v as u64)))
         }
         Some(ElementType::UTCDatetime) => {
-            let time = read_i64(reader)?;
-
-            let temp_msec = time % 1000;
-            let msec = if temp_msec < 0 {
-                1000 - temp_msec
-            } else {
-                temp_msec
-            };
-
-            match Utc.timestamp_opt(time / 1000, (msec as u32) * 1_000_000) {
-                LocalResult::None => Err(DecodeError::InvalidTimestamp(time)),
-                LocalResult::Ambiguous(..) => Err(DecodeError::AmbiguousTimestamp(time)),
-                LocalResult::Single(t) => Ok(Value::UTCDatetime(t))
-            }
+            reader.next_i64().map(|time| Value::UTCDatetime(clamped_datetime(time)))
         }
         Some(ElementType::Symbol) => {
-            read_string(reader).map(Value::Symbol)
+            reader.next_string(options.utf8).map(Value::Symbol)
+        }
+        Some(ElementType::Decimal128) => {
+            let mut bytes = [0; 16];
+            reader.next_into(&mut bytes)?;
+
+            Ok(Value::Decimal128(Decimal128::from_bytes(bytes)))
         }
-        Some(ElementType::Undefiend) | Some(ElementType::DBPointer) | Some(ElementType::MaxKey) | Some(ElementType::MinKey) | None => {
+        Some(ElementType::DBPointer) | None => {
             Err(DecodeError::UnrecognizedElementType(tag))
         }
     }
 }
 
 pub fn decode_document(reader: &mut impl Read) -> DecodeResult<Document> {
+    decode_document_with_options(reader, DecodeOptions::default())
+}
+
+/// Only `max_depth`, `utf8` and `duplicate_keys` apply here; the other
+/// options concern deserializing into Rust types. The document is read
+/// into memory using its length prefix, and nothing past its declared
+/// length is read from `reader`.
+pub fn decode_document_with_options(reader: &mut impl Read, options: DecodeOptions) -> DecodeResult<Document> {
+    let buf = read_document_bytes(reader)?;
+    decode_document_slice(&buf, options)
+}
+
+/// The bytes of the next document in `reader`, read in one go using its
+/// length prefix.
+fn read_document_bytes(reader: &mut impl Read) -> DecodeResult<Vec<u8>> {
+    let mut prefix = [0; 4];
+    reader.read_exact(&mut prefix)?;
+
+    let len = crate::stream::frame_len(prefix)?;
+
+    let mut buf = vec![0; len];
+    buf[..4].copy_from_slice(&prefix);
+    reader.read_exact(&mut buf[4..])?;
+
+    Ok(buf)
+}
+
+/// Decode a buffer holding exactly one document, failing if anything
+/// follows it. Errors come back in `DecodeError::Context`.
+pub(crate) fn decode_document_slice(slice: &[u8], options: DecodeOptions) -> DecodeResult<Document> {
+    decode_document_slice_at(slice, options, 1).map_err(|err| locate(slice, options, err))
+}
+
+/// Attach to `err` the position in `slice` where decoding it failed.
+/// `validate` stops where decoding does, so it is left to find it; only
+/// failed decodes pay for the second pass.
+fn locate(slice: &[u8], options: DecodeOptions, err: DecodeError) -> DecodeError {
+    match validate(slice, options) {
+        Err(invalid) => DecodeError::Context { offset: invalid.offset, key: invalid.key, error: Box::new(err) },
+        Ok(()) => err,
+    }
+}
+
+/// `decode_document_slice` for a document nested `depth` deep.
+pub(crate) fn decode_document_slice_at(slice: &[u8], options: DecodeOptions, depth: usize) -> DecodeResult<Document> {
+    decode_slice(slice, "document", |reader| decode_document_at(reader, options, depth))
+}
+
+pub(crate) fn decode_array_slice_at(slice: &[u8], options: DecodeOptions, depth: usize) -> DecodeResult<Array> {
+    decode_slice(slice, "array", |reader| decode_array(reader, options, depth))
+}
+
+/// Decode a value of `element_type` from exactly the bytes it takes up.
+/// `depth` is that of the document or array holding it.
+pub(crate) fn decode_value_slice(element_type: ElementType, bytes: &[u8], options: DecodeOptions, depth: usize) -> DecodeResult<Value> {
+    decode_slice(bytes, "value", |reader| decode_bson(reader, element_type as u8, options, depth))
+}
+
+fn decode_slice<'a, T>(slice: &'a [u8], what: &str, decode: impl FnOnce(&mut Slice<'a>) -> DecodeResult<T>) -> DecodeResult<T> {
+    let mut reader = Slice::new(slice);
+    let decoded = decode(&mut reader)?;
+
+    if reader.pos != slice.len() {
+        return Err(DecodeError::InvalidLength(
+            reader.pos,
+            format!("{} bytes of trailing data after the {}", slice.len() - reader.pos, what)
+        ));
+    }
+
+    Ok(decoded)
+}
+
+fn decode_document_at(reader: &mut impl Source, options: DecodeOptions, depth: usize) -> DecodeResult<Document> {
+    check_depth(depth, options)?;
+
     let mut doc = Document::new();
 
-    // disregard the length: using Read::take causes infinite type recursion
-    read_i32(reader)?;
+    let frame = reader.open(5, "document")?;
 
     loop {
-        let tag = reader.read_u8()?;
+        let tag = reader.next_u8()?;
 
         if tag == 0 {
             break;
         }
 
-        let key = read_cstring(reader)?;
-        let val = decode_bson(reader, tag)?;
+        let key = reader.next_cstring()?;
+        let val = decode_bson(reader, tag, options, depth)?;
+
+        if doc.contains_key(&key) {
+            match options.duplicate_keys {
+                DuplicateKeys::LastWins => (),
+                DuplicateKeys::FirstWins => continue,
+                DuplicateKeys::Error => return Err(DecodeError::DuplicateKey(key)),
+            }
+        }
 
         doc.insert(key, val);
     }
 
+    reader.close(frame, "document")?;
+
     Ok(doc)
 }
 
+/// Read one document from an async reader. The whole document is read into
+/// memory using its length prefix and then decoded, so the executor is never
+/// blocked waiting on a partial document.
+#[cfg(feature = "tokio")]
+pub async fn decode_document_async<R>(reader: &mut R) -> DecodeResult<Document>
+    where R: tokio::io::AsyncRead + Unpin + ?Sized
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut prefix = [0; 4];
+    reader.read_exact(&mut prefix).await?;
+
+    let len = crate::stream::frame_len(prefix)?;
+
+    let mut buf = vec![0; len];
+    buf[..4].copy_from_slice(&prefix);
+    reader.read_exact(&mut buf[4..]).await?;
+
+    Document::from_slice(&buf)
+}
+
+/// Split a buffer of back-to-back documents on their length prefixes.
+#[cfg(feature = "rayon")]
+fn split_documents(mut bytes: &[u8]) -> DecodeResult<Vec<&[u8]>> {
+    let mut docs = Vec::new();
+
+    while !bytes.is_empty() {
+        if bytes.len() < 4 {
+            return Err(DecodeError::EndOfStream);
+        }
+
+        let len = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        if len < 5 || len as usize > bytes.len() {
+            return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for document", len)));
+        }
+
+        let (doc, rest) = bytes.split_at(len as usize);
+        docs.push(doc);
+        bytes = rest;
+    }
+
+    Ok(docs)
+}
+
+/// Decode a buffer holding many concatenated documents, spreading the work
+/// across the rayon thread pool. Documents are returned in buffer order; if
+/// any of them fails, the first error in buffer order is returned.
+#[cfg(feature = "rayon")]
+pub fn par_decode(bytes: &[u8]) -> DecodeResult<Vec<Document>> {
+    use rayon::prelude::*;
+
+    // Collected as results first, since collecting into a `DecodeResult`
+    // would return whichever error a thread hit first
+    let results: Vec<DecodeResult<Document>> = split_documents(bytes)?
+        .into_par_iter()
+        .map(Document::from_slice)
+        .collect();
+
+    results.into_iter().collect()
+}
+
 pub fn from_bson<'de, T>(value: Value) -> DecodeResult<T>
     where T: Deserialize<'de>
 {
-    let de = Decoder::new(value);
+    from_bson_with_options(value, DecodeOptions::default())
+}
+
+pub fn from_bson_with_options<'de, T>(value: Value, options: DecodeOptions) -> DecodeResult<T>
+    where T: Deserialize<'de>
+{
+    let de = Decoder::with_options(value, options);
     Deserialize::deserialize(de)
 }
 
+/// Deserialize a `T` from a `Document`, without wrapping it in a `Value`
+/// first.
+pub fn from_document<'de, T>(doc: Document) -> DecodeResult<T>
+    where T: Deserialize<'de>
+{
+    from_document_with_options(doc, DecodeOptions::default())
+}
+
+pub fn from_document_with_options<'de, T>(doc: Document, options: DecodeOptions) -> DecodeResult<T>
+    where T: Deserialize<'de>
+{
+    from_bson_with_options(Value::Document(doc), options)
+}
+
 pub fn from_slice<'de, T>(slice: &[u8]) -> DecodeResult<T>
     where T: Deserialize<'de>
 {
-    let mut reader = Cursor::new(slice);
-    let doc = decode_document(&mut reader)?;
-    from_bson(Value::Document(doc))
+    from_slice_with_options(slice, DecodeOptions::default())
+}
+
+pub fn from_slice_with_options<'de, T>(slice: &[u8], options: DecodeOptions) -> DecodeResult<T>
+    where T: Deserialize<'de>
+{
+    let doc = decode_document_slice(slice, options)?;
+    from_document_with_options(doc, options)
+}
+
+/// Deserialize a `T` from the next document in `reader`. The document's
+/// bytes are read in one go using its length prefix and deserialized
+/// straight from that buffer, without building a `Document` in between.
+pub fn from_reader<T>(reader: &mut impl Read) -> DecodeResult<T>
+    where T: DeserializeOwned
+{
+    from_reader_with_options(reader, DecodeOptions::default())
+}
+
+pub fn from_reader_with_options<T>(reader: &mut impl Read, options: DecodeOptions) -> DecodeResult<T>
+    where T: DeserializeOwned
+{
+    let buf = read_document_bytes(reader)?;
+    from_slice_borrowed_with_options(&buf, options)
+}
+
+/// Look up a single top-level field in an encoded document. Element headers
+/// are scanned and the values of other fields skipped, so only the matching
+/// value is decoded.
+pub fn get_field(bytes: &[u8], key: &str) -> DecodeResult<Option<Value>> {
+    get_field_with_options(bytes, key, DecodeOptions::default())
+}
+
+/// The value is decoded as `decode_document_with_options` would decode it
+/// inside the document, so `max_depth`, `utf8` and `duplicate_keys` apply
+/// to it. Of the other fields only repeats of `key` are looked for.
+pub fn get_field_with_options(bytes: &[u8], key: &str, options: DecodeOptions) -> DecodeResult<Option<Value>> {
+    let mut found = None;
+
+    for element in RawIter::new(bytes) {
+        let (k, element_type, value) = element?;
+
+        if k != key {
+            continue;
+        }
+
+        if found.is_some() && options.duplicate_keys == DuplicateKeys::Error {
+            return Err(DecodeError::DuplicateKey(key.to_string()));
+        }
+
+        found = Some((element_type, value));
+
+        if options.duplicate_keys == DuplicateKeys::FirstWins {
+            break;
+        }
+    }
+
+    found.map(|(element_type, value)| decode_value_slice(element_type, value, options, 1)).transpose()
+}
+
+/// Check that `bytes` holds exactly one well formed document without
+/// decoding it: lengths, terminators, element types, array keys, UTF-8 and
+/// nesting depth are checked, and no values are built. It succeeds if
+/// decoding with the same `options` would, so UTF-8 is only checked in
+/// string values under `Utf8Policy::Strict`; keys and regular expressions
+/// are always checked. Repeated keys are only looked for under
+/// `DuplicateKeys::Error`, and booleans and binary subtypes under
+/// `strict_values`.
+///
+/// ```
+/// use bsonrs::doc;
+/// use bsonrs::decode::{self, DecodeOptions};
+///
+/// let mut bytes = doc!{"a": [1, 2]}.to_vec().unwrap();
+/// assert!(decode::validate(&bytes, DecodeOptions::default()).is_ok());
+///
+/// // Corrupt the length prefix of the array, which starts at byte 7
+/// bytes[7] = 3;
+/// assert_eq!(decode::validate(&bytes, DecodeOptions::default()).unwrap_err().offset, 7);
+/// ```
+pub fn validate(bytes: &[u8], options: DecodeOptions) -> Result<(), ValidationError> {
+    validate_document(bytes, 0, false, options, 1)
+}
+
+/// `offset` is where `bytes` starts in the buffer being validated.
+fn validate_document(bytes: &[u8], offset: usize, array: bool, options: DecodeOptions, depth: usize) -> Result<(), ValidationError> {
+    let at = |pos: usize| move |error| ValidationError { offset: offset + pos, key: None, error };
+
+    check_depth(depth, options).map_err(at(0))?;
+    raw::check_document(bytes).map_err(at(0))?;
+
+    let mut pos = 4;
+    let mut index = 0;
+    let mut keys = HashSet::new();
+
+    loop {
+        // `check_document` ensured the last byte is the terminator
+        let tag = bytes[pos];
+
+        if tag == 0 {
+            if pos + 1 != bytes.len() {
+                return Err(at(pos)(DecodeError::InvalidLength(bytes.len(), format!("document ends at byte {}", pos + 1))));
+            }
+
+            return Ok(());
+        }
+
+        let (key, key_len) = raw::read_cstr(&bytes[pos + 1..]).map_err(at(pos + 1))?;
+
+        if array {
+            if key.parse::<usize>() != Ok(index) {
+                return Err(at(pos + 1)(DecodeError::InvalidArrayKey(index, key.to_string())));
+            }
+
+            index += 1;
+        }
+
+        // Errors from here on are about this element, and name its key
+        // unless a nested one was more precise
+        let in_element = |err: ValidationError| ValidationError { key: err.key.or_else(|| Some(key.to_string())), ..err };
+
+        if !array && options.duplicate_keys == DuplicateKeys::Error && !keys.insert(key) {
+            return Err(in_element(at(pos + 1)(DecodeError::DuplicateKey(key.to_string()))));
+        }
+
+        let element_type = ElementType::from(tag).ok_or(DecodeError::UnrecognizedElementType(tag)).map_err(at(pos)).map_err(in_element)?;
+
+        let start = pos + 1 + key_len;
+        let len = raw::value_len(element_type, &bytes[start..]).map_err(at(start)).map_err(in_element)?;
+
+        // The value must leave room for the document's trailing null
+        if start + len >= bytes.len() {
+            return Err(in_element(at(start)(DecodeError::EndOfStream)));
+        }
+
+        validate_value(element_type, &bytes[start..start + len], offset + start, options, depth).map_err(in_element)?;
+
+        pos = start + len;
+    }
+}
+
+/// `depth` is that of the document or array holding the value.
+fn validate_value(element_type: ElementType, bytes: &[u8], offset: usize, options: DecodeOptions, depth: usize) -> Result<(), ValidationError> {
+    let at = |error| ValidationError { offset, key: None, error };
+
+    match element_type {
+        ElementType::Document => validate_document(bytes, offset, false, options, depth + 1),
+        ElementType::Array => validate_document(bytes, offset, true, options, depth + 1),
+        ElementType::Utf8String => validate_str(bytes, options.utf8 == Utf8Policy::Strict).map(|_| ()).map_err(at),
+        ElementType::JavaScriptCode | ElementType::Symbol => {
+            validate_str(bytes, options.utf8 != Utf8Policy::Lossy).map(|_| ()).map_err(at)
+        }
+        ElementType::JavaScriptCodeWithScope => {
+            let code = bytes.get(4..).ok_or(DecodeError::EndOfStream).map_err(at)?;
+            let code_len = validate_str(code, options.utf8 != Utf8Policy::Lossy).map_err(at)?;
+            let scope = 8 + code_len + 1;
+
+            validate_document(&bytes[scope..], offset + scope, false, options, depth + 1)
+        }
+        _ => {
+            match RawValue::from_bytes(element_type, bytes).map_err(at)? {
+                RawValue::Boolean(_) => check_boolean(bytes[0], options).map(|_| ()).map_err(at),
+                RawValue::Binary(subtype, data) => check_binary(subtype, data.len(), options).map_err(at),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+/// Returns the length of the string, excluding its terminator.
+fn validate_str(bytes: &[u8], check_utf8: bool) -> DecodeResult<usize> {
+    let data = raw::read_str_bytes(bytes)?;
+
+    if check_utf8 {
+        raw::utf8(data)?;
+    }
+
+    Ok(data.len())
+}
+
+/// Whether any document in `bytes`, at any depth, repeats a key. The
+/// buffer is checked as by `validate` with the same `options`, and problems
+/// found before the first repeat are returned as errors.
+pub fn has_duplicate_keys(bytes: &[u8], options: DecodeOptions) -> DecodeResult<bool> {
+    let options = DecodeOptions { duplicate_keys: DuplicateKeys::Error, ..options };
+
+    match validate(bytes, options) {
+        Ok(()) => Ok(false),
+        Err(ValidationError { error: DecodeError::DuplicateKey(_), .. }) => Ok(true),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Like `from_slice`, but deserializes straight from the buffer without
+/// building a `Document` first. `&'de str` and `&'de [u8]` fields borrow from
+/// `slice`. The buffer is checked by `validate` up front, so it fails with
+/// the same error `from_slice` would.
+///
+/// ```
+/// use serde_derive::Deserialize;
+/// use bsonrs::{doc, decode};
+///
+/// #[derive(Deserialize)]
+/// struct Person<'a> {
+///     name: &'a str,
+///     age: i32,
+/// }
+///
+/// let bytes = doc!{"name": "Ada", "age": 36}.to_vec().unwrap();
+/// let person: Person = decode::from_slice_borrowed(&bytes).unwrap();
+///
+/// assert_eq!(person.name, "Ada");
+/// ```
+pub fn from_slice_borrowed<'de, T>(slice: &'de [u8]) -> DecodeResult<T>
+    where T: Deserialize<'de>
+{
+    from_slice_borrowed_with_options(slice, DecodeOptions::default())
+}
+
+pub fn from_slice_borrowed_with_options<'de, T>(slice: &'de [u8], options: DecodeOptions) -> DecodeResult<T>
+    where T: Deserialize<'de>
+{
+    // Fail where `from_slice` would, before any of it is deserialized
+    validate(slice, options)?;
+
+    let doc = RawDocument::new(slice)?;
+    let de = RawDecoder::with_options(RawValue::Document(doc), options);
+    Deserialize::deserialize(de)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use serde::de;
+    use serde_derive::Deserialize;
+
+    use crate::decode::{from_bson, from_bson_with_options, from_slice_borrowed_with_options, validate, has_duplicate_keys, DecodeOptions, DecodeError, Utf8Policy, DuplicateKeys, MAX_BSON_SIZE};
+    use crate::doc;
+    use crate::{Value, Document, BinarySubtype};
+    use crate::raw::{RawDocument, RawValue};
+    use crate::value::TimeStamp;
+    use chrono::{DateTime, Utc};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Sloppy {
+        a: i64,
+        b: String,
+        c: Vec<i32>,
+        d: Option<bool>,
+    }
+
+    #[test]
+    fn null_as_default() {
+        let value = Value::Document(doc!{"a": null, "b": null, "c": null, "d": null});
+
+        assert!(from_bson::<Sloppy>(value.clone()).is_err());
+
+        let options = DecodeOptions { null_as_default: true, ..Default::default() };
+        let sloppy: Sloppy = from_bson_with_options(value, options).unwrap();
+
+        assert_eq!(sloppy, Sloppy { a: 0, b: String::new(), c: vec![], d: None });
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Numbers {
+        a: i64,
+        b: u8,
+        c: f64,
+        d: i32,
+    }
+
+    #[test]
+    fn lenient_numerics() {
+        let doc = doc!{"a": 3.0, "b": 255.0, "c": 7, "d": (Value::Int64(-2))};
+        let expected = Numbers { a: 3, b: 255, c: 7.0, d: -2 };
+
+        assert!(from_bson::<Numbers>(Value::Document(doc.clone())).is_err());
+
+        let options = DecodeOptions { lenient_numerics: true, ..Default::default() };
+        let numbers: Numbers = from_bson_with_options(Value::Document(doc.clone()), options).unwrap();
+        assert_eq!(numbers, expected);
+
+        let bytes = doc.to_vec().unwrap();
+        let numbers: Numbers = from_slice_borrowed_with_options(&bytes, options).unwrap();
+        assert_eq!(numbers, expected);
+
+        // Only exact, in-range values are converted
+        for &bad in &[3.5, 256.0, f64::NAN] {
+            let doc = doc!{"a": 1, "b": bad, "c": 1.0, "d": 1};
+            assert!(from_bson_with_options::<Numbers>(Value::Document(doc), options).is_err());
+        }
+    }
+
+    #[test]
+    fn min_max_key() {
+        let doc = doc!{"lower": (Value::MinKey), "upper": (Value::MaxKey)};
+        let bytes = doc.to_vec().unwrap();
+
+        assert_eq!(bytes[4], 0xFF);
+        assert_eq!(crate::Document::from_slice(&bytes).unwrap(), doc);
+
+        #[cfg(feature = "json")]
+        {
+            let json = Value::Document(doc.clone()).to_json();
+            assert_eq!(json, serde_json::json!({"lower": {"$minKey": 1}, "upper": {"$maxKey": 1}}));
+            assert_eq!(Value::from_json(json), Value::Document(doc));
+        }
+    }
+
+    #[test]
+    fn undefined() {
+        // {"a": undefined, "b": undefined}
+        let bytes = [0x0B, 0, 0, 0, 0x06, b'a', 0, 0x06, b'b', 0, 0];
+        let doc = crate::Document::from_slice(&bytes).unwrap();
+
+        assert_eq!(doc, doc!{"a": (Value::Undefined), "b": (Value::Undefined)});
+        assert_eq!(doc.to_vec().unwrap(), bytes);
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Legacy {
+            a: Option<i32>,
+            b: Value,
+        }
+
+        let legacy: Legacy = from_bson(Value::Document(doc)).unwrap();
+        assert_eq!(legacy, Legacy { a: None, b: Value::Undefined });
+    }
+
+    #[test]
+    fn from_slice_borrowed() {
+        use crate::spec::BinarySubtype;
+        use crate::object_id::ObjectId;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum Kind<'a> {
+            Plain,
+            Named(&'a str),
+            Pair(i32, i32),
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Borrowed<'a> {
+            name: &'a str,
+            data: &'a [u8],
+            tags: Vec<&'a str>,
+            id: Value,
+            missing: Option<i32>,
+            kinds: Vec<Kind<'a>>,
+        }
+
+        let id = ObjectId::new();
+        let bytes = doc!{
+            "name": "hello",
+            "data": (BinarySubtype::Generic, vec![1, 2, 3]),
+            "tags": ["a", "b"],
+            "id": (id.clone()),
+            "missing": null,
+            "kinds": ["Plain", {"Named": "x"}, {"Pair": [1, 2]}]
+        }.to_vec().unwrap();
+
+        let borrowed: Borrowed = super::from_slice_borrowed(&bytes).unwrap();
+
+        assert_eq!(borrowed, Borrowed {
+            name: "hello",
+            data: &[1, 2, 3],
+            tags: vec!["a", "b"],
+            id: Value::ObjectId(id),
+            missing: None,
+            kinds: vec![Kind::Plain, Kind::Named("x"), Kind::Pair(1, 2)],
+        });
+
+        // The strings point into the input
+        let range = bytes.as_ptr_range();
+        assert!(range.contains(&borrowed.name.as_ptr()));
+        assert!(range.contains(&borrowed.data.as_ptr()));
+
+        let value = Value::Document(doc!{"a": null, "b": null, "c": null, "d": null});
+        let bytes = crate::encode::to_vec(&value).unwrap();
+        let options = DecodeOptions { null_as_default: true, ..Default::default() };
+        let sloppy: Sloppy = super::from_slice_borrowed_with_options(&bytes, options).unwrap();
+
+        assert_eq!(sloppy, Sloppy { a: 0, b: String::new(), c: vec![], d: None });
+        assert!(super::from_slice_borrowed::<Sloppy>(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn from_reader() {
+        let mut bytes = doc!{"a": 1, "b": "x", "c": [1, 2], "d": null}.to_vec().unwrap();
+        bytes.extend(doc!{"a": 2, "b": "y", "c": [3], "d": true}.to_vec().unwrap());
+
+        let mut reader = Cursor::new(bytes);
+
+        let first: Sloppy = super::from_reader(&mut reader).unwrap();
+        assert_eq!(first, Sloppy { a: 1, b: "x".to_string(), c: vec![1, 2], d: None });
+
+        let second: Sloppy = super::from_reader(&mut reader).unwrap();
+        assert_eq!(second, Sloppy { a: 2, b: "y".to_string(), c: vec![3], d: Some(true) });
+
+        assert!(super::from_reader::<Sloppy>(&mut reader).is_err());
+    }
+
+    #[test]
+    fn from_reader_options() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Fields {
+            n: i32,
+            i: i32,
+            f: i32,
+            b: bool,
+        }
+
+        let mut flag = doc!{"n": null, "i": 5i64, "f": 2.0, "b": true}.to_vec().unwrap();
+        let at = flag.len() - 2;
+        assert_eq!(flag[at], 1);
+        flag[at] = 2;
+
+        let uuid = doc!{"u": (BinarySubtype::Uuid, vec![1, 2, 3])}.to_vec().unwrap();
+        let nested = doc!{"a": {"a": [1, {"a": "x"}]}, "b": 2}.to_vec().unwrap();
+
+        let mut text = doc!{"s": "ab"}.to_vec().unwrap();
+        let at = text.len() - 3;
+        assert_eq!(text[at], b'b');
+        text[at] = 0x80;
+
+        let all = [
+            DecodeOptions::default(),
+            DecodeOptions::strict(),
+            DecodeOptions { null_as_default: true, lenient_numerics: true, ..Default::default() },
+            DecodeOptions { max_depth: 3, duplicate_keys: DuplicateKeys::FirstWins, ..Default::default() },
+            DecodeOptions { max_depth: 2, duplicate_keys: DuplicateKeys::Error, utf8: Utf8Policy::Bytes, ..Default::default() },
+        ];
+
+        fn agree<T>(bytes: &[u8], options: DecodeOptions)
+            where T: de::DeserializeOwned + PartialEq + std::fmt::Debug
+        {
+            let decoded = super::from_slice_with_options::<T>(bytes, options).map_err(|e| e.into_inner().to_string());
+            let read = super::from_reader_with_options::<T>(&mut Cursor::new(bytes), options).map_err(|e| e.into_inner().to_string());
+            assert_eq!(read, decoded, "{:?}", options);
+        }
+
+        for options in all {
+            agree::<Fields>(&flag, options);
+            agree::<Document>(&flag, options);
+            agree::<Document>(&uuid, options);
+            agree::<Document>(&nested, options);
+            agree::<Document>(&text, options);
+        }
+
+        let lenient = all[2];
+        assert_eq!(super::from_reader_with_options::<Fields>(&mut Cursor::new(&flag), lenient).unwrap(), Fields { n: 0, i: 5, f: 2, b: true });
+    }
+
+    #[test]
+    fn get_field() {
+        let bytes = doc!{"big": [1, 2, 3], "_id": 7, "nested": {"_id": 8}}.to_vec().unwrap();
+
+        assert_eq!(super::get_field(&bytes, "_id").unwrap(), Some(Value::Int32(7)));
+        assert_eq!(super::get_field(&bytes, "nested").unwrap(), Some(doc!{"_id": 8}.into()));
+        assert_eq!(super::get_field(&bytes, "missing").unwrap(), None);
+        assert!(super::get_field(&bytes[..bytes.len() - 1], "_id").is_err());
+
+        // Rename "b" to "a"
+        let mut repeated = doc!{"a": 1, "b": 2}.to_vec().unwrap();
+        let b = repeated.iter().position(|&c| c == b'b').unwrap();
+        repeated[b] = b'a';
+
+        let policy = |duplicate_keys| DecodeOptions { duplicate_keys, ..Default::default() };
+        assert_eq!(super::get_field(&repeated, "a").unwrap(), Some(Value::Int32(2)));
+        assert_eq!(super::get_field_with_options(&repeated, "a", policy(DuplicateKeys::FirstWins)).unwrap(), Some(Value::Int32(1)));
+        assert!(matches!(super::get_field_with_options(&repeated, "a", policy(DuplicateKeys::Error)), Err(DecodeError::DuplicateKey(_))));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_round_trip() {
+        use crate::encode::encode_document_async;
+
+        let doc = doc!{"a": 1, "b": [1, 2, {"c": "d"}]};
+
+        let mut buf = Vec::new();
+        encode_document_async(&mut buf, &doc).await.unwrap();
+        encode_document_async(&mut buf, &doc).await.unwrap();
+        assert_eq!(&buf[..buf.len() / 2], &doc.to_vec().unwrap()[..]);
+
+        let mut reader = &buf[..];
+        assert_eq!(super::decode_document_async(&mut reader).await.unwrap(), doc);
+        assert_eq!(super::decode_document_async(&mut reader).await.unwrap(), doc);
+        assert!(super::decode_document_async(&mut reader).await.is_err());
+
+        let mut reader = &buf[..10];
+        assert!(super::decode_document_async(&mut reader).await.is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_decode() {
+        let docs: Vec<_> = (0..100).map(|i| doc!{"i": i}).collect();
+
+        let mut buf = Vec::new();
+        for doc in &docs {
+            buf.extend(doc.to_vec().unwrap());
+        }
+
+        assert_eq!(super::par_decode(&buf).unwrap(), docs);
+        assert!(super::par_decode(&buf[..buf.len() - 1]).is_err());
+
+        // Each document is 12 bytes, with its element type at offset 4
+        buf[10 * 12 + 4] = 77;
+        buf[90 * 12 + 4] = 78;
+
+        for _ in 0..20 {
+            assert!(matches!(super::par_decode(&buf).map_err(DecodeError::into_inner), Err(DecodeError::UnrecognizedElementType(77))));
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        use crate::decode::get_field_with_options;
+
+        fn nested(depth: usize) -> Vec<u8> {
+            let mut doc = doc!{};
+
+            for _ in 1..depth {
+                doc = doc!{"a": doc};
+            }
+
+            doc.to_vec().unwrap()
+        }
+
+        assert!(Document::from_slice(&nested(100)).is_ok());
+        assert!(matches!(Document::from_slice(&nested(101)).map_err(DecodeError::into_inner), Err(DecodeError::DepthLimitExceeded(100))));
+
+        let options = DecodeOptions { max_depth: 3, ..Default::default() };
+        assert!(Document::from_slice_with_options(&nested(3), options).is_ok());
+        assert!(matches!(Document::from_slice_with_options(&nested(4), options).map_err(DecodeError::into_inner), Err(DecodeError::DepthLimitExceeded(3))));
+
+        let arrays = doc!{"a": [[1]]}.to_vec().unwrap();
+        assert!(Document::from_slice_with_options(&arrays, options).is_ok());
+        let arrays = doc!{"a": [[[1]]]}.to_vec().unwrap();
+        assert!(Document::from_slice_with_options(&arrays, options).is_err());
+
+        let scope = doc!{"f": (Value::JavaScriptCodeWithScope("f()".to_string(), Box::new(doc!{"x": {}})))}.to_vec().unwrap();
+        assert!(Document::from_slice_with_options(&scope, options).is_ok());
+        assert!(Document::from_slice_with_options(&scope, DecodeOptions { max_depth: 2, ..options }).is_err());
+        assert!(from_slice_borrowed_with_options::<Document>(&scope, options).is_ok());
+        assert!(from_slice_borrowed_with_options::<Document>(&scope, DecodeOptions { max_depth: 2, ..options }).is_err());
+
+        // Deserializing straight from the buffer has the same limit
+        assert!(from_slice_borrowed_with_options::<Document>(&nested(3), options).is_ok());
+        let err = from_slice_borrowed_with_options::<Document>(&nested(4), options).unwrap_err();
+        assert!(matches!(err.into_inner(), DecodeError::DepthLimitExceeded(3)));
+        assert!(from_slice_borrowed_with_options::<de::IgnoredAny>(&nested(101), DecodeOptions::default()).is_err());
+
+        // Far too deep to recurse through without the limit
+        let deep = deeply_nested(200_000);
+        assert!(matches!(Document::from_slice(&deep).map_err(DecodeError::into_inner), Err(DecodeError::DepthLimitExceeded(100))));
+        let raw = RawDocument::new(&deep).unwrap();
+        assert!(matches!(raw.to_document(), Err(DecodeError::DepthLimitExceeded(100))));
+        assert!(matches!(RawValue::Document(raw).to_value(), Err(DecodeError::DepthLimitExceeded(100))));
+        assert!(raw.get_document("a").unwrap().unwrap().to_document_with_options(options).is_err());
+        assert!(RawDocument::new(&nested(3)).unwrap().to_document_with_options(options).is_ok());
+        let array = RawDocument::new(&arrays).unwrap().get_array("a").unwrap().unwrap();
+        assert!(array.to_array_with_options(options).is_ok());
+        assert!(array.to_array_with_options(DecodeOptions { max_depth: 2, ..options }).is_err());
+        assert!(matches!(super::get_field(&deep, "a"), Err(DecodeError::DepthLimitExceeded(100))));
+        assert!(get_field_with_options(&nested(4), "a", options).is_err());
+        assert!(get_field_with_options(&nested(3), "a", options).unwrap().is_some());
+    }
+
+    /// `{"a": {"a": ...}}`, `depth` documents deep. Built directly, as
+    /// encoding a `Document` this deep would recurse as far.
+    fn deeply_nested(depth: usize) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(depth * 8);
+
+        for level in (1..depth).rev() {
+            bytes.extend_from_slice(&(5 + 8 * level as i32).to_le_bytes());
+            bytes.extend_from_slice(&[0x03, b'a', 0]);
+        }
+
+        bytes.extend_from_slice(&[5, 0, 0, 0, 0]);
+        bytes.resize(bytes.len() + depth - 1, 0);
+        bytes
+    }
+
+    #[test]
+    fn utf8_policy() {
+        // Replace the last byte of each "ab" with a lone continuation byte
+        fn corrupt(doc: Document) -> Vec<u8> {
+            let mut bytes = doc.to_vec().unwrap();
+
+            for i in 0..bytes.len() - 1 {
+                if bytes[i] == b'a' && bytes[i + 1] == b'b' {
+                    bytes[i + 1] = 0x80;
+                }
+            }
+
+            bytes
+        }
+
+        let string = corrupt(doc!{"s": "ab"});
+        let symbol = corrupt(doc!{"c": (Value::Symbol("ab".to_string()))});
+
+        assert_eq!(Document::from_slice(&string).unwrap().get_str("s"), Ok("a\u{FFFD}"));
+
+        let strict = DecodeOptions { utf8: Utf8Policy::Strict, ..Default::default() };
+        assert!(matches!(Document::from_slice_with_options(&string, strict).map_err(DecodeError::into_inner), Err(DecodeError::FromUtf8Error(_))));
+
+        let bytes = DecodeOptions { utf8: Utf8Policy::Bytes, ..Default::default() };
+        let doc = Document::from_slice_with_options(&string, bytes).unwrap();
+        assert_eq!(doc.get("s"), Some(&Value::Binary(BinarySubtype::Generic, vec![b'a', 0x80])));
+        assert!(Document::from_slice_with_options(&symbol, bytes).is_err());
+
+        // Deserializing straight from the buffer gives the same results
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Text {
+            s: String,
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Code {
+            c: Value,
+        }
+
+        fn agree<T>(bytes: &[u8], options: DecodeOptions)
+            where T: de::DeserializeOwned + PartialEq + std::fmt::Debug
+        {
+            let decoded = super::from_slice_with_options::<T>(bytes, options).map_err(|e| e.into_inner().to_string());
+            let borrowed = from_slice_borrowed_with_options::<T>(bytes, options).map_err(|e| e.into_inner().to_string());
+            assert_eq!(borrowed, decoded);
+        }
+
+        for utf8 in [Utf8Policy::Lossy, Utf8Policy::Strict, Utf8Policy::Bytes] {
+            let options = DecodeOptions { utf8, ..Default::default() };
+
+            agree::<Text>(&string, options);
+            agree::<Document>(&string, options);
+            agree::<Code>(&symbol, options);
+            agree::<Document>(&corrupt(doc!{"a": [{"s": "ab"}]}), options);
+
+            let decoded = Document::from_slice_with_options(&string, options).ok().and_then(|doc| doc.get("s").cloned());
+            assert_eq!(super::get_field_with_options(&string, "s", options).ok().flatten(), decoded);
+        }
+
+        assert_eq!(from_slice_borrowed_with_options::<Text>(&string, DecodeOptions::default()).unwrap().s, "a\u{FFFD}");
+    }
+
+    #[test]
+    fn strict_values() {
+        let mut boolean = doc!{"b": true}.to_vec().unwrap();
+        boolean[7] = 2;
+
+        let uuid = doc!{"u": (Value::Binary(BinarySubtype::Uuid, vec![0; 15]))}.to_vec().unwrap();
+        let strict = DecodeOptions::strict();
+
+        assert_eq!(Document::from_slice(&boolean).unwrap().get_bool("b"), Ok(true));
+        assert!(Document::from_slice(&uuid).is_ok());
+        assert!(validate(&boolean, DecodeOptions::default()).is_ok());
+
+        for bytes in [&boolean, &uuid] {
+            assert!(Document::from_slice_with_options(bytes, strict).is_err());
+            assert!(validate(bytes, strict).is_err());
+        }
+
+        let md5 = doc!{"m": (Value::Binary(BinarySubtype::Md5, vec![0; 16]))}.to_vec().unwrap();
+        assert!(Document::from_slice_with_options(&md5, strict).is_ok());
+    }
+
+    #[test]
+    fn validate_buffers() {
+        let options = DecodeOptions::default();
+        let doc = doc!{
+            "s": "x",
+            "d": {"a": [1, {"b": 2.5}]},
+            "bin": (Value::Binary(BinarySubtype::Generic, vec![1, 2])),
+            "f": (Value::JavaScriptCodeWithScope("f()".to_string(), Box::new(doc!{"x": 1}))),
+            "r": (Value::RegExp("^a".to_string(), "i".to_string())),
+            "n": null
+        };
+        let bytes = doc.to_vec().unwrap();
+
+        assert!(validate(&bytes, options).is_ok());
+
+        // Trailing garbage, truncation and a bad terminator
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(validate(&longer, options).is_err());
+        assert!(validate(&bytes[..bytes.len() - 1], options).is_err());
+        let mut unterminated = bytes.clone();
+        *unterminated.last_mut().unwrap() = 1;
+        assert!(validate(&unterminated, options).is_err());
+
+        // Unknown element type right after the length prefix
+        let mut unknown = bytes.clone();
+        unknown[4] = 0x30;
+        let err = validate(&unknown, options).unwrap_err();
+        assert_eq!(err.offset, 4);
+        assert!(matches!(err.error, DecodeError::UnrecognizedElementType(0x30)));
+
+        // Array keys must count up from 0
+        let mut keys = doc!{"a": [1, 2]}.to_vec().unwrap();
+        keys[19] = b'5';
+        assert!(matches!(validate(&keys, options).unwrap_err().error, DecodeError::InvalidArrayKey(1, _)));
+
+        let nested = doc!{"a": {"b": {}}}.to_vec().unwrap();
+        assert!(validate(&nested, DecodeOptions { max_depth: 3, ..options }).is_ok());
+        let err = validate(&nested, DecodeOptions { max_depth: 2, ..options }).unwrap_err();
+        assert_eq!(err.offset, 14);
+        assert!(matches!(err.error, DecodeError::DepthLimitExceeded(2)));
+
+        // Invalid UTF-8 in a string only fails under the strict policy
+        let mut string = doc!{"s": "ab"}.to_vec().unwrap();
+        string[12] = 0x80;
+        assert!(validate(&string, options).is_ok());
+        assert!(validate(&string, DecodeOptions { utf8: Utf8Policy::Strict, ..options }).is_err());
+        string[5] = 0x80;
+        assert!(validate(&string, options).is_err());
+    }
+
+    #[test]
+    fn declared_lengths() {
+        let bytes = doc!{"a": {"b": 1}, "c": 2}.to_vec().unwrap();
+        assert_eq!(bytes[7], 12);
+
+        // The subdocument at byte 7 claims one byte too few, then one too many
+        let mut short = bytes.clone();
+        short[7] = 11;
+        assert!(matches!(Document::from_slice(&short).map_err(DecodeError::into_inner), Err(DecodeError::EndOfStream)));
+        let mut long = bytes.clone();
+        long[7] = 13;
+        assert!(matches!(Document::from_slice(&long).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(13, _))));
+
+        // A subdocument running past the end of its parent
+        let mut overrun = bytes.clone();
+        overrun[7] = 40;
+        assert!(matches!(Document::from_slice(&overrun).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(40, _))));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(Document::from_slice(&trailing).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(..))));
+        assert!(super::from_slice::<Document>(&trailing).is_err());
+
+        let mut scope = doc!{"f": (Value::JavaScriptCodeWithScope("f()".to_string(), Box::new(doc!{"x": 1})))}.to_vec().unwrap();
+        scope[7] += 1;
+        assert!(matches!(Document::from_slice(&scope).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(..))));
+
+        // Reading from a stream stops at the end of the first document
+        let mut stream = bytes.clone();
+        stream.extend_from_slice(&bytes);
+        let mut reader = Cursor::new(stream);
+        assert!(Document::decode(&mut reader).is_ok());
+        assert_eq!(reader.position() as usize, bytes.len());
+        assert!(Document::decode(&mut reader).is_ok());
+
+        // Even when the reader could supply them, no more than 16 MiB are read
+        let mut huge = vec![0; MAX_BSON_SIZE as usize + 1];
+        huge[..4].copy_from_slice(&(MAX_BSON_SIZE + 1).to_le_bytes());
+        huge[4..11].copy_from_slice(&[0x10, b'a', 0, 1, 0, 0, 0]);
+        let mut reader = Cursor::new(&huge);
+        assert!(matches!(Document::decode(&mut reader).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(len, _)) if len == huge.len()));
+        assert_eq!(reader.position(), 4);
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        // Rename "b" to "a", at the top level and inside "d"
+        let mut bytes = doc!{"a": 1, "b": 2, "c": 3, "d": {"a": 4, "b": 5}}.to_vec().unwrap();
+        for byte in bytes.iter_mut().filter(|b| **b == b'b') {
+            *byte = b'a';
+        }
+
+        let options = DecodeOptions::default();
+        assert_eq!(Document::from_slice(&bytes).unwrap(), doc!{"a": 2, "c": 3, "d": {"a": 5}});
+
+        let first = DecodeOptions { duplicate_keys: DuplicateKeys::FirstWins, ..options };
+        assert_eq!(Document::from_slice_with_options(&bytes, first).unwrap(), doc!{"a": 1, "c": 3, "d": {"a": 4}});
+
+        let error = DecodeOptions { duplicate_keys: DuplicateKeys::Error, ..options };
+        assert!(matches!(Document::from_slice_with_options(&bytes, error).map_err(DecodeError::into_inner), Err(DecodeError::DuplicateKey(ref key)) if key == "a"));
+        assert!(validate(&bytes, options).is_ok());
+        assert_eq!(validate(&bytes, error).unwrap_err().offset, 12);
+
+        assert!(has_duplicate_keys(&bytes, options).unwrap());
+        let unique = doc!{"a": 1, "d": {"a": 4}}.to_vec().unwrap();
+        assert!(!has_duplicate_keys(&unique, options).unwrap());
+        assert!(has_duplicate_keys(&unique[1..], options).is_err());
+
+        // Deserializing from the buffer resolves repeats the same way
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Inner {
+            a: i32,
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Outer {
+            a: i32,
+            c: i32,
+            d: Inner,
+        }
+
+        assert_eq!(super::from_slice_borrowed::<Outer>(&bytes).unwrap(), Outer { a: 2, c: 3, d: Inner { a: 5 } });
+        assert_eq!(from_slice_borrowed_with_options::<Outer>(&bytes, first).unwrap(), Outer { a: 1, c: 3, d: Inner { a: 4 } });
+        assert!(matches!(from_slice_borrowed_with_options::<Outer>(&bytes, error).map_err(DecodeError::into_inner), Err(DecodeError::DuplicateKey(ref key)) if key == "a"));
+
+        for options in [options, first, error] {
+            let decoded = super::from_slice_with_options::<Outer>(&bytes, options).map_err(|e| e.to_string());
+            let read = super::from_reader_with_options::<Outer>(&mut Cursor::new(&bytes), options).map_err(|e| e.to_string());
+            assert_eq!(read, decoded);
+
+            let decoded = Document::from_slice_with_options(&bytes, options).map_err(|e| e.to_string());
+            assert_eq!(from_slice_borrowed_with_options::<Document>(&bytes, options).map_err(|e| e.to_string()), decoded);
+        }
+    }
+
+    #[test]
+    fn binary_lengths() {
+        let bytes = doc!{"b": (BinarySubtype::Generic, vec![1, 2, 3])}.to_vec().unwrap();
+        assert_eq!(bytes[7], 3);
+
+        let mut negative = bytes.clone();
+        negative[7..11].copy_from_slice(&(-1i32).to_le_bytes());
+        assert!(matches!(Document::from_slice(&negative).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(..))));
+        assert!(validate(&negative, DecodeOptions::default()).is_err());
+
+        let mut oversized = bytes.clone();
+        oversized[7..11].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(matches!(Document::from_slice(&oversized).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(len, _)) if len == i32::MAX as usize));
+
+        // The old subtype's inner length must be four less than the outer
+        let old = doc!{"b": (BinarySubtype::BinaryOld, vec![1, 2, 3])}.to_vec().unwrap();
+        assert_eq!(Document::from_slice(&old).unwrap().get_binary_with_subtype("b", BinarySubtype::BinaryOld), Ok(&vec![1, 2, 3]));
+        let mut mismatch = old.clone();
+        mismatch[12] = 4;
+        assert!(matches!(Document::from_slice(&mismatch).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(4, _))));
+        assert!(RawDocument::new(&mismatch).unwrap().get("b").is_err());
+
+        // Too short to hold the inner length at all
+        let short = [15, 0, 0, 0, 5, b'b', 0, 2, 0, 0, 0, 2, 1, 2, 0];
+        assert!(matches!(Document::from_slice(&short).map_err(DecodeError::into_inner), Err(DecodeError::InvalidLength(2, _))));
+        assert!(matches!(RawDocument::new(&short).unwrap().get("b"), Err(DecodeError::InvalidLength(2, _))));
+    }
+
+    #[test]
+    fn slice_matches_reader() {
+        use crate::ObjectId;
+        use crate::decimal128::Decimal128;
+
+        let doc = doc!{
+            "f": 1.5,
+            "s": "str",
+            "d": {"a": [1, "two", {"x": null}]},
+            "bin": (BinarySubtype::Generic, vec![1, 2]),
+            "old": (BinarySubtype::BinaryOld, vec![3]),
+            "id": (ObjectId::with_bytes([7; 12])),
+            "b": true,
+            "r": (Value::RegExp("^a".to_string(), "i".to_string())),
+            "c": (Value::JavaScriptCode("f()".to_string())),
+            "cs": (Value::JavaScriptCodeWithScope("g()".to_string(), Box::new(doc!{"y": 2}))),
+            "i": 3,
+            "l": 4i64,
+            "t": (Value::TimeStamp(TimeStamp::new(0, 5))),
+            "sym": (Value::Symbol("s".to_string())),
+            "dec": (Decimal128::from_bytes([1; 16])),
+            "min": (Value::MinKey),
+            "max": (Value::MaxKey),
+            "u": (Value::Undefined)
+        };
+        let bytes = doc.to_vec().unwrap();
+
+        assert_eq!(Document::from_slice(&bytes).unwrap(), doc);
+        assert_eq!(Document::decode(&mut Cursor::new(&bytes)).unwrap(), doc);
+
+        // Truncated keys and strings fail the same way on both paths
+        let short = doc!{"key": "value"}.to_vec().unwrap();
+        for len in [5, 7, 9, 11, 13] {
+            let mut cut = short[..len].to_vec();
+            cut[0] = len as u8;
+
+            assert!(matches!(Document::from_slice(&cut).map_err(DecodeError::into_inner), Err(DecodeError::EndOfStream)), "{}", len);
+            assert!(matches!(Document::decode(&mut Cursor::new(&cut)).map_err(DecodeError::into_inner), Err(DecodeError::EndOfStream)), "{}", len);
+        }
+    }
+
+    #[test]
+    fn error_context() {
+        let mut bytes = doc!{"a": 1, "b": {"c": 1}}.to_vec().unwrap();
+        bytes[18] = 77;
+
+        let errors = [
+            Document::from_slice(&bytes).unwrap_err(),
+            Document::decode(&mut Cursor::new(&bytes)).unwrap_err(),
+            super::from_slice::<Document>(&bytes).unwrap_err(),
+            super::from_slice_borrowed::<Document>(&bytes).unwrap_err(),
+            validate(&bytes, DecodeOptions::default()).unwrap_err().into(),
+        ];
+
+        for err in errors {
+            assert_eq!(err.offset(), Some(18));
+            assert_eq!(err.key(), Some("c"));
+            assert_eq!(err.to_string(), "invalid BSON at byte 18 (key `c`): Unrecognized element type `77`");
+            assert!(matches!(err.into_inner(), DecodeError::UnrecognizedElementType(77)));
+        }
+
+        let mut bytes = doc!{"a": [1, 2]}.to_vec().unwrap();
+        bytes[19] = b'5';
+
+        let err = Document::from_slice(&bytes).unwrap_err();
+        assert_eq!(err.offset(), Some(19));
+        assert_eq!(err.key(), Some("a"));
+        assert_eq!(err.to_string(), "invalid BSON at byte 19 (key `a`): Invalid array key: expected `1`, got `5`");
+        assert!(matches!(err.into_inner(), DecodeError::InvalidArrayKey(1, _)));
+
+        let err = validate(&bytes, DecodeOptions::default()).unwrap_err();
+        assert_eq!((err.offset, err.key.as_deref()), (19, Some("a")));
+    }
+
+    #[test]
+    fn error_path() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Listener {
+            port: u16,
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Server {
+            listeners: Vec<Listener>,
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Config {
+            server: Server,
+        }
+
+        let doc = doc!{"server": {"listeners": [{"port": 80}, {"port": 443}, {"port": "x"}]}};
+        let bytes = doc.to_vec().unwrap();
+
+        let errors = [
+            from_bson::<Config>(doc.into()).unwrap_err(),
+            crate::decode::from_slice_borrowed::<Config>(&bytes).unwrap_err(),
+        ];
+
+        for err in errors {
+            assert_eq!(err.path(), Some("server.listeners[2].port"));
+            assert_eq!(err.to_string(), r#"invalid type: string "x", expected u16 at "server.listeners[2].port""#);
+            assert!(matches!(err.into_inner(), DecodeError::InvalidType(_)));
+        }
+    }
+
+    #[test]
+    fn error_sources() {
+        use std::error::Error;
+
+        fn port(bytes: &[u8]) -> Result<i32, DecodeError> {
+            Ok(Document::from_slice(bytes)?.get_i32("port")?)
+        }
+
+        let err = port(&doc!{"port": "80"}.to_vec().unwrap()).unwrap_err();
+        assert!(matches!(err, DecodeError::DocumentError(crate::doc::Error::UnexpectedType)));
+        assert_eq!(err.source().unwrap().to_string(), "value has an unexpected type");
+
+        let bytes = [14, 0, 0, 0, 2, b'a', 0, 2, 0, 0, 0, 0xff, 0, 0];
+        let err = Document::from_slice_with_options(&bytes, DecodeOptions::strict()).unwrap_err();
+        assert!(matches!(err.source().unwrap().downcast_ref(), Some(DecodeError::FromUtf8Error(_))));
+        assert!(err.source().unwrap().source().is_some());
+    }
+
+    #[test]
+    fn datetime_range() {
+        for &millis in &[-1, -999, -1000, -1001, -62_135_596_800_000, 1_500_000_000_123, 253_402_300_799_999] {
+            let value = Value::datetime_from_millis(millis).unwrap();
+            let doc = doc!{"d": value};
+            let bytes = doc.to_vec().unwrap();
+
+            assert_eq!(&bytes[7..15], &millis.to_le_bytes());
+            let decoded = Document::from_slice(&bytes).unwrap();
+            assert_eq!(decoded.get("d").and_then(Value::datetime_millis), Some(millis));
+            assert_eq!(RawDocument::new(&bytes).unwrap().get_utc_datetime("d").unwrap().map(|d| d.timestamp_millis()), Some(millis));
+        }
+
+        assert_eq!(Value::datetime_from_millis(-1).unwrap().to_string(), r#"{ "$date": { "$numberLong": "-1" } }"#);
+
+        // Past what chrono can hold, datetimes are clamped rather than failing
+        for &(millis, clamped) in &[(i64::MIN, DateTime::<Utc>::MIN_UTC), (i64::MAX, DateTime::<Utc>::MAX_UTC)] {
+            assert!(Value::datetime_from_millis(millis).is_none());
+
+            let mut bytes = doc!{"d": 0i64}.to_vec().unwrap();
+            bytes[4] = 0x09;
+            bytes[7..15].copy_from_slice(&millis.to_le_bytes());
+
+            assert!(validate(&bytes, DecodeOptions::strict()).is_ok());
+            assert_eq!(Document::from_slice(&bytes).unwrap(), doc!{"d": clamped});
+            assert_eq!(RawDocument::new(&bytes).unwrap().get_utc_datetime("d").unwrap(), Some(clamped));
+
+            #[cfg(feature = "json")]
+            assert_eq!(Document::from_extjson_str(&format!(r#"{{"d": {{"$date": {{"$numberLong": "{}"}}}}}}"#, millis)).unwrap(), doc!{"d": clamped});
+        }
+    }
 }
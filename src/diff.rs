@@ -0,0 +1,183 @@
+//! Field-level differences between two documents
+//!
+//! `Document::diff` walks both documents, descending into subdocuments
+//! present on both sides, and reports every field that was added, removed or
+//! changed by its dotted path. Arrays are compared as a whole. The result can
+//! be rendered as a `$set`/`$unset` update document.
+//!
+//! ```
+//! use bsonrs::doc;
+//!
+//! let old = doc!{"name": "a", "address": {"city": "x", "zip": 1}};
+//! let new = doc!{"name": "a", "address": {"city": "y"}, "tags": ["t"]};
+//!
+//! let diff = old.diff(&new);
+//!
+//! assert_eq!(diff.to_patch(), doc!{
+//!     "$set": {"address.city": "y", "tags": ["t"]},
+//!     "$unset": {"address.zip": ""}
+//! });
+//! ```
+use std::slice;
+
+use crate::value::Value;
+use crate::doc::Document;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added { path: String, value: Value },
+    Removed { path: String, old: Value },
+    Changed { path: String, old: Value, new: Value },
+}
+
+impl Change {
+    /// Dotted path of the field, built from the keys leading to it. Keys
+    /// containing `.` make it ambiguous.
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } | Change::Removed { path, .. } | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Changes from one document to another, in the order fields appear: those
+/// of the old document first, then the ones only in the new one.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DocumentDiff {
+    changes: Vec<Change>,
+    /// The keys leading to each change, which a dotted path can't always
+    /// be split back into.
+    keys: Vec<Vec<String>>,
+}
+
+impl DocumentDiff {
+    pub fn new(old: &Document, new: &Document) -> DocumentDiff {
+        let mut diff = DocumentDiff::default();
+        diff_documents(&mut Vec::new(), old, new, &mut diff);
+        diff
+    }
+
+    fn push(&mut self, keys: &[String], change: Change) {
+        self.keys.push(keys.to_vec());
+        self.changes.push(change);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Change> {
+        self.changes.iter()
+    }
+
+    pub fn into_changes(self) -> Vec<Change> {
+        self.changes
+    }
+
+    /// The changes along with the keys leading to each.
+    pub(crate) fn into_keyed_changes(self) -> impl Iterator<Item = (Vec<String>, Change)> {
+        self.keys.into_iter().zip(self.changes)
+    }
+
+    /// An update document with the added and changed fields under `$set`
+    /// and the removed ones under `$unset`. Either is left out when empty.
+    pub fn to_patch(&self) -> Document {
+        let mut set = Document::new();
+        let mut unset = Document::new();
+
+        for change in &self.changes {
+            match change {
+                Change::Added { path, value } | Change::Changed { path, new: value, .. } => {
+                    set.insert_value(path.clone(), value.clone());
+                }
+                Change::Removed { path, .. } => {
+                    unset.insert(path.clone(), "");
+                }
+            }
+        }
+
+        let mut patch = Document::new();
+
+        if !set.is_empty() {
+            patch.insert("$set", set);
+        }
+
+        if !unset.is_empty() {
+            patch.insert("$unset", unset);
+        }
+
+        patch
+    }
+}
+
+impl<'a> IntoIterator for &'a DocumentDiff {
+    type Item = &'a Change;
+    type IntoIter = slice::Iter<'a, Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// `keys` lead to `old` and `new`, and are left as they were.
+fn diff_documents(keys: &mut Vec<String>, old: &Document, new: &Document, diff: &mut DocumentDiff) {
+    for (key, old_value) in old {
+        keys.push(key.clone());
+
+        match (old_value, new.get(key)) {
+            (_, None) => diff.push(keys, Change::Removed { path: keys.join("."), old: old_value.clone() }),
+            (Value::Document(a), Some(Value::Document(b))) => diff_documents(keys, a, b, diff),
+            (_, Some(new_value)) if new_value != old_value => {
+                diff.push(keys, Change::Changed { path: keys.join("."), old: old_value.clone(), new: new_value.clone() });
+            }
+            _ => (),
+        }
+
+        keys.pop();
+    }
+
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            keys.push(key.clone());
+            diff.push(keys, Change::Added { path: keys.join("."), value: new_value.clone() });
+            keys.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Change;
+    use crate::doc;
+    use crate::Value;
+
+    #[test]
+    fn diff() {
+        let old = doc!{"a": 1, "b": {"c": 2, "d": 3}, "e": [1, 2], "f": {"g": 1}};
+        let new = doc!{"a": 1, "b": {"c": 4}, "e": [1, 2, 3], "f": 5, "h": null};
+
+        let diff = old.diff(&new);
+        let changes: Vec<_> = diff.iter().cloned().collect();
+
+        assert_eq!(changes, vec![
+            Change::Changed { path: "b.c".to_string(), old: Value::Int32(2), new: Value::Int32(4) },
+            Change::Removed { path: "b.d".to_string(), old: Value::Int32(3) },
+            Change::Changed { path: "e".to_string(), old: old.get("e").unwrap().clone(), new: new.get("e").unwrap().clone() },
+            Change::Changed { path: "f".to_string(), old: Value::Document(doc!{"g": 1}), new: Value::Int32(5) },
+            Change::Added { path: "h".to_string(), value: Value::Null },
+        ]);
+        assert_eq!(diff.iter().map(Change::path).collect::<Vec<_>>(), vec!["b.c", "b.d", "e", "f", "h"]);
+
+        assert_eq!(diff.to_patch(), doc!{
+            "$set": {"b.c": 4, "e": [1, 2, 3], "f": 5, "h": null},
+            "$unset": {"b.d": ""}
+        });
+
+        assert!(old.diff(&old).is_empty());
+        assert_eq!(old.diff(&old).to_patch(), doc!{});
+    }
+}
@@ -1,35 +1,144 @@
-use std::result;
+use std::{result, error};
 use std::fmt;
-use std::io::{Write, Read, Cursor};
+use std::mem;
+use std::io::{Write, Read};
 use std::iter::{FromIterator, Extend};
 use std::cmp::Ordering;
-use std::ops::RangeFull;
+use std::ops::{RangeFull, Index};
+#[cfg(feature = "json")]
+use std::str::FromStr;
 
 use indexmap::IndexMap;
 use chrono::{DateTime, Utc};
-use byteorder::WriteBytesExt;
-
-use crate::value::{Value, Array};
-use crate::encode::{encode_document, encode_bson, write_i32, EncodeResult};
-use crate::decode::{decode_document, DecodeResult};
-use crate::spec::BinarySubtype;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::value::{Value, Array, TimeStamp, NULL, write_json_str};
+#[cfg(feature = "json")]
+use crate::value::{JsonError, NonFiniteFloats};
+use crate::encode::{encode_document, encode_document_into, to_document, EncodeResult, EncodeOptions};
+use crate::decode::{decode_document, decode_document_slice, from_document, DecodeResult, DecodeOptions};
+use crate::spec::{BinarySubtype, ElementType};
 use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
+use crate::convert::FromValue;
+use crate::path::Path;
+use crate::diff::DocumentDiff;
+use crate::flatten::{self, FlattenOptions};
+use crate::normalize::{self, NormalizeOptions};
+use crate::sanitize::{self, SanitizePolicy};
+use crate::projection::Projection;
 
 pub use indexmap::map::{IntoIter, Iter, IterMut, Entry, Keys, Values, ValuesMut, Drain};
 
 #[derive(PartialEq, Debug)]
+#[non_exhaustive]
 pub enum Error {
     NotPresent,
     UnexpectedType,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NotPresent => write!(fmt, "key not present"),
+            Error::UnexpectedType => write!(fmt, "value has an unexpected type"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
 pub type Result<T> = result::Result<T, Error>;
 
+/// Error from the `try_get_*` getters, which unlike `Error` says which key
+/// failed and what was found there. The `TryFrom<Value>` conversions return
+/// it too, with an empty `key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetError {
+    pub key: String,
+    pub expected: ElementType,
+    /// The type of the value found, `None` if the key is absent.
+    pub found: Option<ElementType>,
+    /// The subtype of a binary found where generic binary was expected.
+    pub found_subtype: Option<BinarySubtype>,
+}
+
+impl GetError {
+    pub(crate) fn new(key: &str, expected: ElementType, found: Option<&Value>) -> GetError {
+        // Binary is only ever expected with the generic subtype
+        let found_subtype = match found {
+            Some(Value::Binary(subtype, _)) if expected == ElementType::Binary => Some(*subtype),
+            _ => None,
+        };
+
+        GetError {
+            key: key.to_string(),
+            expected,
+            found: found.map(Value::element_type),
+            found_subtype,
+        }
+    }
+}
+
+impl fmt::Display for GetError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let found = match self.found {
+            Some(found) => found,
+            None => return write!(fmt, "key `{}` not present, expected {:?}", self.key, self.expected),
+        };
+
+        if !self.key.is_empty() {
+            write!(fmt, "key `{}`: ", self.key)?;
+        }
+
+        match self.found_subtype {
+            Some(subtype) => write!(fmt, "expected {:?}({:?}), found {:?}({:?})", self.expected, BinarySubtype::Generic, found, subtype),
+            None => write!(fmt, "expected {:?}, found {:?}", self.expected, found),
+        }
+    }
+}
+
+impl error::Error for GetError {}
+
+impl From<GetError> for Error {
+    fn from(err: GetError) -> Error {
+        match err.found {
+            Some(_) => Error::UnexpectedType,
+            None => Error::NotPresent,
+        }
+    }
+}
+
+/// Keys keep the order they were inserted in, but `==` ignores it: two
+/// documents are equal when they hold the same keys with equal values.
 #[derive(Clone, PartialEq, Eq, Default)]
 pub struct Document {
     inner: IndexMap<String, Value>
 }
 
+// Remove a key and return its value unwrapped, leaving the document as it
+// was if the value has another type.
+macro_rules! typed_removes {
+    ($($name:ident -> $ty:ty, $pat:pat => $value:expr;)+) => {
+        $(
+            pub fn $name(&mut self, key: &str) -> Result<$ty> {
+                let slot = self.get_mut(key).ok_or(Error::NotPresent)?;
+
+                match mem::replace(slot, Value::Null) {
+                    $pat => {
+                        self.remove(key);
+                        Ok($value)
+                    }
+                    other => {
+                        *slot = other;
+                        Err(Error::UnexpectedType)
+                    }
+                }
+            }
+        )+
+    };
+}
+
 impl Document {
     pub fn new() -> Document {
         Document {
@@ -95,6 +204,15 @@ impl Document {
         self.insert_value(key.into(), value.into())
     }
 
+    /// Insert the value if there is one; `None` leaves the document alone
+    /// rather than storing `Null`.
+    pub fn insert_if_some(&mut self, key: impl Into<String>, value: Option<impl Into<Value>>) -> Option<Value> {
+        match value {
+            Some(value) => self.insert(key, value),
+            None => None,
+        }
+    }
+
     pub fn insert_full(&mut self, key: impl Into<String>, value: impl Into<Value>) -> (usize, Option<Value>) {
         self.insert_value_full(key.into(), value.into())
     }
@@ -111,6 +229,21 @@ impl Document {
         self.inner.swap_remove_full(key)
     }
 
+    typed_removes! {
+        remove_f64 -> f64, Value::Double(v) => v;
+        remove_i32 -> i32, Value::Int32(v) => v;
+        remove_i64 -> i64, Value::Int64(v) => v;
+        remove_str -> String, Value::String(v) => v;
+        remove_array -> Array, Value::Array(v) => v;
+        remove_document -> Document, Value::Document(v) => v;
+        remove_bool -> bool, Value::Boolean(v) => v;
+        remove_binary -> Vec<u8>, Value::Binary(BinarySubtype::Generic, v) => v;
+        remove_object_id -> ObjectId, Value::ObjectId(v) => v;
+        remove_decimal128 -> Decimal128, Value::Decimal128(v) => v;
+        remove_time_stamp -> TimeStamp, Value::TimeStamp(v) => v;
+        remove_utc_datetime -> DateTime<Utc>, Value::UTCDatetime(v) => v;
+    }
+
     pub fn pop(&mut self) -> Option<(String, Value)> {
         self.inner.pop()
     }
@@ -185,6 +318,35 @@ impl Document {
         }
     }
 
+    /// An `Int32`, `Int64` or `Double` as an `i64`. A `Double` must be a
+    /// whole number in range, anything else is `UnexpectedType`.
+    pub fn get_as_i64(&self, key: &str) -> Result<i64> {
+        // -2^63 and 2^63, both exact as f64
+        const I64_MIN: f64 = -9_223_372_036_854_775_808.0;
+        const I64_END: f64 = 9_223_372_036_854_775_808.0;
+
+        match self.get(key) {
+            Some(&Value::Int32(v)) => Ok(v.into()),
+            Some(&Value::Int64(v)) => Ok(v),
+            Some(&Value::Double(v)) if v.fract() == 0.0 && (I64_MIN..I64_END).contains(&v) => Ok(v as i64),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    /// An `Int32`, `Int64` or `Double` as an `f64`. An `Int64` must be
+    /// exactly representable, anything else is `UnexpectedType`.
+    pub fn get_as_f64(&self, key: &str) -> Result<f64> {
+        match self.get(key) {
+            Some(&Value::Double(v)) => Ok(v),
+            Some(&Value::Int32(v)) => Ok(v.into()),
+            // i64::MAX rounds up to 2^63, which casts back saturated
+            Some(&Value::Int64(v)) if v != i64::MAX && v as f64 as i64 == v => Ok(v as f64),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
     pub fn get_str(&self, key: &str) -> Result<&str> {
         match self.get(key) {
             Some(&Value::String(ref v)) => Ok(v),
@@ -229,6 +391,48 @@ impl Document {
         }
     }
 
+    /// Binary data of the given subtype; `get_binary` is the `Generic` case.
+    pub fn get_binary_with_subtype(&self, key: &str, subtype: BinarySubtype) -> Result<&Vec<u8>> {
+        match self.get(key) {
+            Some(&Value::Binary(t, ref v)) if t == subtype => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    /// The pattern and options of a regular expression.
+    pub fn get_regexp(&self, key: &str) -> Result<(&str, &str)> {
+        match self.get(key) {
+            Some(Value::RegExp(pat, opt)) => Ok((pat, opt)),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_code(&self, key: &str) -> Result<&str> {
+        match self.get(key) {
+            Some(Value::JavaScriptCode(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_code_with_scope(&self, key: &str) -> Result<(&str, &Document)> {
+        match self.get(key) {
+            Some(Value::JavaScriptCodeWithScope(code, scope)) => Ok((code, scope)),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_symbol(&self, key: &str) -> Result<&str> {
+        match self.get(key) {
+            Some(Value::Symbol(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
     pub fn get_object_id(&self, key: &str) -> Result<&ObjectId> {
         match self.get(key) {
             Some(&Value::ObjectId(ref v)) => Ok(v),
@@ -237,7 +441,33 @@ impl Document {
         }
     }
 
-    pub fn get_time_stamp(&self, key: &str) -> Result<u64> {
+    pub fn get_decimal128(&self, key: &str) -> Result<Decimal128> {
+        match self.get(key) {
+            Some(&Value::Decimal128(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    /// A `Decimal128` as a `rust_decimal::Decimal`, failing with
+    /// `UnexpectedType` if it can't be represented exactly.
+    #[cfg(feature = "rust_decimal")]
+    pub fn get_decimal(&self, key: &str) -> Result<rust_decimal::Decimal> {
+        use std::convert::TryFrom;
+
+        let d = self.get_decimal128(key)?;
+        rust_decimal::Decimal::try_from(d).map_err(|_| Error::UnexpectedType)
+    }
+
+    #[cfg(feature = "uuid")]
+    pub fn get_uuid(&self, key: &str) -> Result<uuid::Uuid> {
+        match self.get(key) {
+            Some(v) => v.as_uuid().ok_or(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_time_stamp(&self, key: &str) -> Result<TimeStamp> {
         match self.get(key) {
             Some(&Value::TimeStamp(v)) => Ok(v),
             Some(_) => Err(Error::UnexpectedType),
@@ -253,6 +483,216 @@ impl Document {
         }
     }
 
+    pub fn get_f64_mut(&mut self, key: &str) -> Result<&mut f64> {
+        match self.get_mut(key) {
+            Some(Value::Double(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_i32_mut(&mut self, key: &str) -> Result<&mut i32> {
+        match self.get_mut(key) {
+            Some(Value::Int32(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_i64_mut(&mut self, key: &str) -> Result<&mut i64> {
+        match self.get_mut(key) {
+            Some(Value::Int64(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_str_mut(&mut self, key: &str) -> Result<&mut String> {
+        match self.get_mut(key) {
+            Some(Value::String(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_array_mut(&mut self, key: &str) -> Result<&mut Array> {
+        match self.get_mut(key) {
+            Some(Value::Array(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_document_mut(&mut self, key: &str) -> Result<&mut Document> {
+        match self.get_mut(key) {
+            Some(Value::Document(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_bool_mut(&mut self, key: &str) -> Result<&mut bool> {
+        match self.get_mut(key) {
+            Some(Value::Boolean(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_binary_mut(&mut self, key: &str) -> Result<&mut Vec<u8>> {
+        match self.get_mut(key) {
+            Some(Value::Binary(BinarySubtype::Generic, v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_object_id_mut(&mut self, key: &str) -> Result<&mut ObjectId> {
+        match self.get_mut(key) {
+            Some(Value::ObjectId(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_time_stamp_mut(&mut self, key: &str) -> Result<&mut TimeStamp> {
+        match self.get_mut(key) {
+            Some(Value::TimeStamp(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    pub fn get_utc_datetime_mut(&mut self, key: &str) -> Result<&mut DateTime<Utc>> {
+        match self.get_mut(key) {
+            Some(Value::UTCDatetime(v)) => Ok(v),
+            Some(_) => Err(Error::UnexpectedType),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    fn try_get_with<'a, T>(&'a self, key: &str, expected: ElementType, get: impl FnOnce(&'a Value) -> Option<T>) -> result::Result<T, GetError> {
+        let value = self.get(key);
+
+        value.and_then(get).ok_or_else(|| GetError::new(key, expected, value))
+    }
+
+    pub fn try_get_f64(&self, key: &str) -> result::Result<f64, GetError> {
+        self.try_get_with(key, ElementType::Double, |v| v.as_f64())
+    }
+
+    pub fn try_get_i32(&self, key: &str) -> result::Result<i32, GetError> {
+        self.try_get_with(key, ElementType::Int32, |v| v.as_i32())
+    }
+
+    pub fn try_get_i64(&self, key: &str) -> result::Result<i64, GetError> {
+        self.try_get_with(key, ElementType::Int64, |v| v.as_i64())
+    }
+
+    pub fn try_get_str(&self, key: &str) -> result::Result<&str, GetError> {
+        self.try_get_with(key, ElementType::Utf8String, |v| v.as_str())
+    }
+
+    pub fn try_get_array(&self, key: &str) -> result::Result<&Array, GetError> {
+        self.try_get_with(key, ElementType::Array, |v| v.as_array())
+    }
+
+    pub fn try_get_document(&self, key: &str) -> result::Result<&Document, GetError> {
+        self.try_get_with(key, ElementType::Document, |v| v.as_document())
+    }
+
+    pub fn try_get_bool(&self, key: &str) -> result::Result<bool, GetError> {
+        self.try_get_with(key, ElementType::Boolean, |v| v.as_bool())
+    }
+
+    pub fn try_get_binary(&self, key: &str) -> result::Result<&[u8], GetError> {
+        self.try_get_with(key, ElementType::Binary, |v| match v.as_binary() {
+            Some((BinarySubtype::Generic, bytes)) => Some(bytes),
+            _ => None,
+        })
+    }
+
+    pub fn try_get_object_id(&self, key: &str) -> result::Result<&ObjectId, GetError> {
+        self.try_get_with(key, ElementType::ObjectId, |v| v.as_object_id())
+    }
+
+    pub fn try_get_decimal128(&self, key: &str) -> result::Result<Decimal128, GetError> {
+        self.try_get_with(key, ElementType::Decimal128, |v| v.as_decimal128().copied())
+    }
+
+    pub fn try_get_time_stamp(&self, key: &str) -> result::Result<TimeStamp, GetError> {
+        self.try_get_with(key, ElementType::TimeStamp, |v| v.as_timestamp())
+    }
+
+    pub fn try_get_utc_datetime(&self, key: &str) -> result::Result<&DateTime<Utc>, GetError> {
+        self.try_get_with(key, ElementType::UTCDatetime, |v| v.as_utc_date_time())
+    }
+
+    /// Look up a dotted path such as `"a.b.0"`, see `path::Path`. Parse the
+    /// path once with `Path::parse` instead when doing many lookups.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        Path::parse(path).ok()?.get(self)
+    }
+
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut Value> {
+        Path::parse(path).ok()?.get_mut(self)
+    }
+
+    /// Every value matching a dotted path that may contain `*` wildcards,
+    /// see `Path::select`. An invalid path matches nothing.
+    pub fn select_path<'a>(&'a self, path: &str) -> impl Iterator<Item = &'a Value> {
+        Path::parse(path).ok().into_iter().flat_map(move |path| path.select(self).collect::<Vec<_>>())
+    }
+
+    pub fn contains_path(&self, path: &str) -> bool {
+        self.get_path(path).is_some()
+    }
+
+    /// Remove the value at a dotted path, see `Path::remove`.
+    pub fn remove_path(&mut self, path: &str) -> Option<Value> {
+        Path::parse(path).ok()?.remove(self)
+    }
+
+    /// Remove the value at a dotted path along with any documents left
+    /// empty by it, see `Path::remove_and_prune`.
+    pub fn remove_path_and_prune(&mut self, path: &str) -> Option<Value> {
+        Path::parse(path).ok()?.remove_and_prune(self)
+    }
+
+    /// Get a copy of the value at `key` converted with `FromValue`.
+    pub fn get_as<T: FromValue>(&self, key: &str) -> Result<T> {
+        match self.get(key) {
+            Some(v) => T::from_value(v.clone()),
+            None => Err(Error::NotPresent),
+        }
+    }
+
+    /// Deserialize a copy of the document into `T`. When the document isn't
+    /// needed afterwards, `decode::from_document` avoids the copy.
+    ///
+    /// ```
+    /// use serde_derive::{Serialize, Deserialize};
+    /// use bsonrs::{doc, Document};
+    ///
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// let doc = Document::from_data(&Point { x: 1, y: 2 }).unwrap();
+    /// assert_eq!(doc, doc!{"x": 1, "y": 2});
+    /// assert_eq!(doc.to_t::<Point>().unwrap(), Point { x: 1, y: 2 });
+    /// ```
+    pub fn to_t<T: DeserializeOwned>(&self) -> DecodeResult<T> {
+        from_document(self.clone())
+    }
+
+    /// Serialize `data` into a document, see `encode::to_document`.
+    pub fn from_data<T: Serialize + ?Sized>(data: &T) -> EncodeResult<Document> {
+        to_document(data)
+    }
+
     pub fn encode(&self, writer: &mut impl Write) -> EncodeResult<()> {
         encode_document(writer, self)
     }
@@ -261,26 +701,105 @@ impl Document {
         decode_document(reader)
     }
 
+    /// Size in bytes of the encoded document, computed without encoding it.
+    /// Fields skipped by `EncodeOptions::omit_null` are still counted.
+    pub fn encoded_len(&self) -> usize {
+        4 + self.iter().map(|(k, v)| v.encoded_len(k)).sum::<usize>() + 1
+    }
+
     pub fn to_vec(&self) -> EncodeResult<Vec<u8>> {
-        let mut buf = Vec::with_capacity(64);
-        write_i32(&mut buf, 0)?;
+        self.to_vec_with_options(EncodeOptions::default())
+    }
 
-        for (key, val) in self {
-            encode_bson(&mut buf, key.as_ref(), val)?;
-        }
+    pub fn to_vec_with_options(&self, options: EncodeOptions) -> EncodeResult<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        encode_document_into(&mut buf, self, options)?;
+        Ok(buf)
+    }
 
-        buf.write_u8(0)?;
+    /// Append the encoded document to `buf`, so one buffer can be reused
+    /// across many documents instead of allocating for each. On error `buf`
+    /// is left as it was.
+    pub fn to_vec_into(&self, buf: &mut Vec<u8>) -> EncodeResult<()> {
+        self.to_vec_into_with_options(buf, EncodeOptions::default())
+    }
 
-        let len_bytes = (buf.len() as i32).to_le_bytes();
+    pub fn to_vec_into_with_options(&self, buf: &mut Vec<u8>, options: EncodeOptions) -> EncodeResult<()> {
+        let start = buf.len();
+        buf.reserve(self.encoded_len());
 
-        buf[..4].clone_from_slice(&len_bytes);
+        let result = encode_document_into(buf, self, options);
 
-        Ok(buf)
+        if result.is_err() {
+            buf.truncate(start);
+        }
+
+        result
     }
 
+    /// Decode a buffer holding exactly one document. Trailing bytes are an
+    /// error.
     pub fn from_slice(slice: &[u8]) -> DecodeResult<Document> {
-        let mut reader = Cursor::new(slice);
-        decode_document(&mut reader)
+        decode_document_slice(slice, DecodeOptions::default())
+    }
+
+    pub fn from_slice_with_options(slice: &[u8], options: DecodeOptions) -> DecodeResult<Document> {
+        decode_document_slice(slice, options)
+    }
+
+    /// Parse Extended JSON v2 text, canonical or relaxed. See `extjson`.
+    #[cfg(feature = "json")]
+    pub fn from_extjson_str(s: &str) -> DecodeResult<Document> {
+        crate::extjson::from_str(s)
+    }
+
+    /// Display the document over multiple lines, one field per line and
+    /// nested documents and arrays indented by four spaces, which is far
+    /// easier to read than `to_string` once payloads get large.
+    ///
+    /// ```
+    /// use bsonrs::doc;
+    ///
+    /// let doc = doc!{"a": 1, "b": {"c": [1, 2]}};
+    /// assert_eq!(doc.to_string_pretty(), "{\n    \"a\": 1,\n    \"b\": {\n        \"c\": [\n            1,\n            2\n        ]\n    }\n}");
+    /// ```
+    pub fn to_string_pretty(&self) -> String {
+        format!("{:#}", self)
+    }
+
+    pub(crate) fn fmt_pretty(&self, fmt: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        if self.is_empty() {
+            return fmt.write_str("{}");
+        }
+
+        writeln!(fmt, "{{")?;
+
+        for (i, (k, v)) in self.iter().enumerate() {
+            write!(fmt, "{:1$}", "", (indent + 1) * 4)?;
+            write_json_str(fmt, k)?;
+            fmt.write_str(": ")?;
+            v.fmt_pretty(fmt, indent + 1)?;
+            writeln!(fmt, "{}", if i + 1 < self.len() { "," } else { "" })?;
+        }
+
+        write!(fmt, "{:1$}}}", "", indent * 4)
+    }
+
+    /// The same JSON as `Value::to_json` gives for this document, built
+    /// without cloning it.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!(self)
+    }
+
+    /// Like `to_json`, handling NaN and infinite doubles as `non_finite` says.
+    #[cfg(feature = "json")]
+    pub fn to_json_with(&self, non_finite: NonFiniteFloats) -> result::Result<serde_json::Value, JsonError> {
+        Ok(serde_json::Value::Object(
+            self.iter()
+                .map(|(k, v)| Ok((k.clone(), v.to_json_with(non_finite)?)))
+                .collect::<result::Result<_, JsonError>>()?
+        ))
     }
 
     pub fn extend(&mut self, iter: impl Into<Document>) {
@@ -298,6 +817,91 @@ impl Document {
     pub fn swap_remove_index(&mut self, index: usize) -> Option<(String, Value)> {
         self.inner.swap_remove_index(index)
     }
+
+    /// What changed going from `self` to `other`, see `diff::DocumentDiff`.
+    pub fn diff(&self, other: &Document) -> DocumentDiff {
+        DocumentDiff::new(self, other)
+    }
+
+    /// A copy with only the fields selected by `projection`, see the
+    /// `projection` module.
+    pub fn project(&self, projection: &Projection) -> Document {
+        projection.apply(self)
+    }
+
+    /// A single-level copy with dotted keys, see the `flatten` module.
+    pub fn flatten(&self) -> Document {
+        flatten::flatten(self, FlattenOptions::default())
+    }
+
+    pub fn flatten_with_options(&self, options: FlattenOptions) -> Document {
+        flatten::flatten(self, options)
+    }
+
+    /// Rebuild nested documents from dotted keys, reversing `flatten`.
+    pub fn unflatten(&self) -> Result<Document> {
+        flatten::unflatten(self, FlattenOptions::default())
+    }
+
+    pub fn unflatten_with_options(&self, options: FlattenOptions) -> Result<Document> {
+        flatten::unflatten(self, options)
+    }
+
+    /// Compare with `other` ignoring the order of keys, here and in nested
+    /// documents, while arrays must match element by element. This is just
+    /// `==`, which compares the underlying maps without regard to order; it
+    /// spells that out for code that relies on it.
+    pub fn eq_unordered(&self, other: &Document) -> bool {
+        self == other
+    }
+
+    /// A copy with sorted keys and `Int32` widened to `Int64` throughout,
+    /// see the `normalize` module.
+    pub fn normalize(&self) -> Document {
+        normalize::normalize(self, NormalizeOptions::default())
+    }
+
+    pub fn normalize_with_options(&self, options: NormalizeOptions) -> Document {
+        normalize::normalize(self, options)
+    }
+
+    /// Remove `Null` fields, and with `recursive` those of nested documents
+    /// too, including documents inside arrays. Array elements themselves are
+    /// never removed.
+    pub fn strip_nulls(&mut self, recursive: bool) {
+        sanitize::sanitize(self, SanitizePolicy { recursive, ..SanitizePolicy::default() })
+    }
+
+    /// Remove the fields `policy` selects, see the `sanitize` module.
+    pub fn sanitize(&mut self, policy: SanitizePolicy) {
+        sanitize::sanitize(self, policy)
+    }
+
+    /// Apply `patch` as a JSON Merge Patch (RFC 7386): `Null` fields remove
+    /// the key, documents are merged recursively, and any other value
+    /// replaces what was there. Arrays are replaced as a whole.
+    pub fn apply_merge_patch(&mut self, patch: &Document) {
+        for (key, value) in patch {
+            match value {
+                Value::Null => {
+                    self.remove(key);
+                }
+                Value::Document(patch) => {
+                    match self.get_mut(key) {
+                        Some(Value::Document(target)) => target.apply_merge_patch(patch),
+                        _ => {
+                            let mut target = Document::new();
+                            target.apply_merge_patch(patch);
+                            self.insert_value(key.clone(), Value::Document(target));
+                        }
+                    }
+                }
+                _ => {
+                    self.insert_value(key.clone(), value.clone());
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Debug for Document {
@@ -307,7 +911,12 @@ impl fmt::Debug for Document {
 }
 
 impl fmt::Display for Document {
+    /// `{:#}` gives the indented, multi-line form of `to_string_pretty`.
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if fmt.alternate() {
+            return self.fmt_pretty(fmt, 0);
+        }
+
         write!(fmt, "{{")?;
 
         let mut first = true;
@@ -319,7 +928,8 @@ impl fmt::Display for Document {
                 write!(fmt, ", ")?;
             }
 
-            write!(fmt, "{}: {}", k, v)?;
+            write_json_str(fmt, k)?;
+            write!(fmt, ": {}", v)?;
         }
 
         write!(fmt, "{}}}", if !first { " " } else { "" })?;
@@ -328,6 +938,17 @@ impl fmt::Display for Document {
     }
 }
 
+/// Parses Extended JSON text, so `"{\"a\": 1}".parse::<Document>()` works;
+/// the same as `Document::from_extjson_str`.
+#[cfg(feature = "json")]
+impl FromStr for Document {
+    type Err = crate::decode::DecodeError;
+
+    fn from_str(s: &str) -> DecodeResult<Document> {
+        crate::extjson::from_str(s)
+    }
+}
+
 impl IntoIterator for Document {
     type Item = (String, Value);
     type IntoIter = IntoIter<String, Value>;
@@ -367,15 +988,94 @@ impl FromIterator<(String, Value)> for Document {
     }
 }
 
+/// Missing keys give `Null` rather than panicking, so lookups can be
+/// chained: `&doc["user"]["name"]`.
+impl Index<&str> for Document {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+#[cfg(feature = "json")]
+impl<'a> From<&'a Document> for serde_json::Value {
+    fn from(doc: &'a Document) -> serde_json::Value {
+        doc.to_json()
+    }
+}
+
 impl From<IndexMap<String, Value>> for Document {
     fn from(map: IndexMap<String, Value>) -> Document {
         Document { inner: map }
     }
 }
 
+/// Typed helpers for `Document::entry`.
+///
+/// ```
+/// use bsonrs::doc;
+/// use bsonrs::doc::EntryExt;
+///
+/// let mut doc = doc!{};
+///
+/// doc.entry("tags".to_string()).or_insert_array().unwrap().push("a".into());
+/// doc.entry("count".to_string()).and_modify_i64(|n| *n += 1).or_insert(1i64.into());
+/// doc.entry("count".to_string()).and_modify_i64(|n| *n += 1).or_insert(1i64.into());
+///
+/// assert_eq!(doc, doc!{"tags": ["a"], "count": 2i64});
+/// ```
+pub trait EntryExt<'a> {
+    /// The document at this entry, inserting an empty one if vacant.
+    /// Fails with `UnexpectedType` if it holds something else.
+    fn or_insert_document(self) -> Result<&'a mut Document>;
+
+    /// The array at this entry, inserting an empty one if vacant. Fails
+    /// with `UnexpectedType` if it holds something else.
+    fn or_insert_array(self) -> Result<&'a mut Array>;
+
+    /// Apply `f` if the entry holds an integer. An `Int32` is widened to
+    /// `Int64` first, so `f` can't overflow it. Other values are left alone.
+    fn and_modify_i64<F: FnOnce(&mut i64)>(self, f: F) -> Self;
+}
+
+impl<'a> EntryExt<'a> for Entry<'a, String, Value> {
+    fn or_insert_document(self) -> Result<&'a mut Document> {
+        match self.or_insert_with(|| Value::Document(Document::new())) {
+            Value::Document(doc) => Ok(doc),
+            _ => Err(Error::UnexpectedType),
+        }
+    }
+
+    fn or_insert_array(self) -> Result<&'a mut Array> {
+        match self.or_insert_with(|| Value::Array(Array::new())) {
+            Value::Array(array) => Ok(array),
+            _ => Err(Error::UnexpectedType),
+        }
+    }
+
+    fn and_modify_i64<F: FnOnce(&mut i64)>(self, f: F) -> Self {
+        self.and_modify(|value| {
+            if let Value::Int32(v) = *value {
+                *value = Value::Int64(i64::from(v));
+            }
+
+            if let Value::Int64(v) = value {
+                f(v);
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::Document;
+    use chrono::{TimeZone, Utc};
+
+    use super::{Error, GetError, EntryExt};
+    use crate::{Document, Value, Array, ObjectId};
+    use crate::decimal128::Decimal128;
+    use crate::value::TimeStamp;
+    use crate::spec::{BinarySubtype, ElementType};
     use crate::doc;
 
     #[test]
@@ -388,4 +1088,318 @@ mod test {
 
         assert_eq!(document, document2);
     }
+
+    #[test]
+    fn strip_nulls() {
+        let mut document = doc!{"aa": null, "bb": {"cc": null}, "dd": [null, {"ee": null}]};
+
+        let mut shallow = document.clone();
+        shallow.strip_nulls(false);
+        assert_eq!(shallow, doc!{"bb": {"cc": null}, "dd": [null, {"ee": null}]});
+
+        document.strip_nulls(true);
+
+        assert_eq!(document, doc!{"bb": {}, "dd": [null, {}]});
+    }
+
+    #[test]
+    fn eq_unordered() {
+        let a = doc!{"a": 1, "b": {"c": [{"d": 1, "e": 2}], "f": null}};
+        let b = doc!{"b": {"f": null, "c": [{"e": 2, "d": 1}]}, "a": 1};
+
+        assert_ne!(a.keys().collect::<Vec<_>>(), b.keys().collect::<Vec<_>>());
+        assert_eq!(a, b);
+        assert!(a.eq_unordered(&b));
+        assert!(b.eq_unordered(&a));
+
+        // Arrays stay ordered, and every key has to be on both sides
+        assert!(!doc!{"a": [1, 2]}.eq_unordered(&doc!{"a": [2, 1]}));
+        assert!(!doc!{"a": 1}.eq_unordered(&doc!{"a": 1, "b": null}));
+        assert!(!doc!{"a": 1}.eq_unordered(&doc!{"a": 1_i64}));
+    }
+
+    #[test]
+    fn path_edits() {
+        let mut document = doc!{"a": {"b": {"c": 1}}, "d": 2};
+
+        *document.get_path_mut("a.b.c").unwrap() = 5.into();
+        assert!(document.contains_path("a.b.c"));
+        assert!(!document.contains_path("a.b.x"));
+        assert!(document.get_path_mut("a..b").is_none());
+
+        assert_eq!(document.remove_path("a.b.c"), Some(5.into()));
+        assert_eq!(document, doc!{"a": {"b": {}}, "d": 2});
+
+        document.insert("a", doc!{"b": {"c": 1}});
+        assert_eq!(document.remove_path_and_prune("a.b.c"), Some(1.into()));
+        assert_eq!(document, doc!{"d": 2});
+    }
+
+    #[test]
+    fn merge_patch() {
+        let mut document = doc!{
+            "title": "Goodbye!",
+            "author": {"givenName": "John", "familyName": "Doe"},
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"
+        };
+
+        document.apply_merge_patch(&doc!{
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": {"familyName": null},
+            "tags": ["example"]
+        });
+
+        assert_eq!(document, doc!{
+            "title": "Hello!",
+            "author": {"givenName": "John"},
+            "tags": ["example"],
+            "content": "This will be unchanged",
+            "phoneNumber": "+01-123-456-7890"
+        });
+
+        // Nulls inside a new subdocument are dropped, not stored
+        let mut document = doc!{"a": "b"};
+        document.apply_merge_patch(&doc!{"a": {"b": "c", "d": null}, "e": null});
+        assert_eq!(document, doc!{"a": {"b": "c"}});
+    }
+
+    #[test]
+    fn getters() {
+        let document = doc!{
+            "re": (Value::RegExp("^a".to_string(), "i".to_string())),
+            "code": (Value::JavaScriptCode("f()".to_string())),
+            "scoped": (Value::JavaScriptCodeWithScope("g()".to_string(), Box::new(doc!{"x": 1}))),
+            "sym": (Value::Symbol("s".to_string())),
+            "md5": (BinarySubtype::Md5, vec![1, 2])
+        };
+
+        assert_eq!(document.get_regexp("re"), Ok(("^a", "i")));
+        assert_eq!(document.get_code("code"), Ok("f()"));
+        assert_eq!(document.get_code_with_scope("scoped"), Ok(("g()", &doc!{"x": 1})));
+        assert_eq!(document.get_symbol("sym"), Ok("s"));
+        assert_eq!(document.get_binary_with_subtype("md5", BinarySubtype::Md5), Ok(&vec![1, 2]));
+        assert_eq!(document.get_binary_with_subtype("md5", BinarySubtype::Generic), Err(Error::UnexpectedType));
+        assert_eq!(document.get_code("sym"), Err(Error::UnexpectedType));
+        assert_eq!(document.get_symbol("missing"), Err(Error::NotPresent));
+    }
+
+    #[test]
+    fn mut_getters() {
+        let mut document = doc!{"a": {"b": [1]}, "s": "x", "n": 1};
+
+        document.get_document_mut("a").unwrap().get_array_mut("b").unwrap().push(2.into());
+        document.get_str_mut("s").unwrap().push('y');
+        *document.get_i32_mut("n").unwrap() += 1;
+
+        assert_eq!(document, doc!{"a": {"b": [1, 2]}, "s": "xy", "n": 2});
+        assert_eq!(document.get_i64_mut("n"), Err(Error::UnexpectedType));
+        assert_eq!(document.get_bool_mut("missing"), Err(Error::NotPresent));
+    }
+
+    #[test]
+    fn try_getters() {
+        let document = doc!{"name": "x", "port": 80};
+
+        assert_eq!(document.try_get_str("name"), Ok("x"));
+        assert_eq!(document.try_get_i32("port"), Ok(80));
+
+        let err = document.try_get_str("port").unwrap_err();
+        assert_eq!(err, GetError { key: "port".to_string(), expected: ElementType::Utf8String, found: Some(ElementType::Int32), found_subtype: None });
+        assert_eq!(err.to_string(), "key `port`: expected Utf8String, found Int32");
+        assert_eq!(Error::from(err), Error::UnexpectedType);
+
+        let err = document.try_get_bool("debug").unwrap_err();
+        assert_eq!(err.to_string(), "key `debug` not present, expected Boolean");
+        assert_eq!(Error::from(err), Error::NotPresent);
+
+        let document = doc!{"data": (BinarySubtype::Generic, vec![1]), "id": (BinarySubtype::Uuid, vec![0; 16])};
+        assert_eq!(document.try_get_binary("data"), Ok(&[1][..]));
+
+        let err = document.try_get_binary("id").unwrap_err();
+        assert_eq!(err.found_subtype, Some(BinarySubtype::Uuid));
+        assert_eq!(err.to_string(), "key `id`: expected Binary(Generic), found Binary(Uuid)");
+    }
+
+    #[test]
+    fn lenient_numbers() {
+        let document = doc!{
+            "i32": 5,
+            "i64": 5i64,
+            "whole": 5.0,
+            "frac": 5.5,
+            "huge": 1e19,
+            "exact": (1i64 << 53),
+            "inexact": ((1i64 << 53) + 1),
+            "max": (i64::MAX),
+            "s": "5"
+        };
+
+        for key in &["i32", "i64", "whole"] {
+            assert_eq!(document.get_as_i64(key), Ok(5));
+            assert_eq!(document.get_as_f64(key), Ok(5.0));
+        }
+
+        assert_eq!(document.get_as_i64("frac"), Err(Error::UnexpectedType));
+        assert_eq!(document.get_as_i64("huge"), Err(Error::UnexpectedType));
+        assert_eq!(document.get_as_f64("exact"), Ok(9_007_199_254_740_992.0));
+        assert_eq!(document.get_as_f64("inexact"), Err(Error::UnexpectedType));
+        assert_eq!(document.get_as_f64("max"), Err(Error::UnexpectedType));
+        assert_eq!(document.get_as_i64("s"), Err(Error::UnexpectedType));
+        assert_eq!(document.get_as_f64("missing"), Err(Error::NotPresent));
+    }
+
+    #[test]
+    fn encoded_len() {
+        let long: Vec<i32> = (0..12).collect();
+
+        let document = doc!{
+            "f": 1.5,
+            "s": "str",
+            "a": (Array::from(long)),
+            "d": {"x": null, "y": true},
+            "re": (Value::RegExp("^a".to_string(), "i".to_string())),
+            "code": (Value::JavaScriptCode("f()".to_string())),
+            "scoped": (Value::JavaScriptCodeWithScope("g()".to_string(), Box::new(doc!{"x": 1}))),
+            "i": 1,
+            "l": 1i64,
+            "ts": (Value::TimeStamp(TimeStamp::new(0, 1))),
+            "bin": (BinarySubtype::Generic, vec![1, 2]),
+            "old": (BinarySubtype::BinaryOld, vec![1, 2]),
+            "oid": (ObjectId::with_bytes([0; 12])),
+            "dt": (Utc.timestamp_opt(0, 0).unwrap()),
+            "sym": (Value::Symbol("s".to_string())),
+            "dec": (Decimal128::from_parts(false, 1, 0).unwrap()),
+            "min": (Value::MinKey),
+            "max": (Value::MaxKey),
+            "u": (Value::Undefined)
+        };
+
+        assert_eq!(document.encoded_len(), document.to_vec().unwrap().len());
+        assert_eq!(Document::new().encoded_len(), 5);
+
+        let value = Value::from("abc");
+        let mut buf = Vec::new();
+        crate::encode::encode_bson(&mut buf, "key", &value).unwrap();
+        assert_eq!(value.encoded_len("key"), buf.len());
+    }
+
+    #[test]
+    fn typed_removes() {
+        let mut document = doc!{"a": "x", "b": 1, "c": {"d": 2}};
+
+        assert_eq!(document.remove_i64("b"), Err(Error::UnexpectedType));
+        assert_eq!(document, doc!{"a": "x", "b": 1, "c": {"d": 2}});
+
+        assert_eq!(document.remove_str("a"), Ok("x".to_string()));
+        assert_eq!(document.remove_document("c"), Ok(doc!{"d": 2}));
+        assert_eq!(document.remove_str("a"), Err(Error::NotPresent));
+        assert_eq!(document, doc!{"b": 1});
+    }
+
+    #[test]
+    fn entry_helpers() {
+        let mut document = doc!{"a": 1};
+
+        document.entry("b".to_string()).or_insert_document().unwrap().insert("c", 2);
+        document.entry("b".to_string()).or_insert_document().unwrap().insert("d", 3);
+        assert_eq!(document.entry("a".to_string()).or_insert_array(), Err(Error::UnexpectedType));
+
+        // Only integers are modified, and Int32 becomes Int64
+        document.entry("b".to_string()).and_modify_i64(|n| *n += 1);
+        assert_eq!(document, doc!{"a": 1, "b": {"c": 2, "d": 3}});
+
+        document.insert("n", i32::MAX);
+        document.entry("n".to_string()).and_modify_i64(|n| *n += 1);
+        assert_eq!(document.get("n"), Some(&Value::Int64(i64::from(i32::MAX) + 1)));
+    }
+
+    #[test]
+    fn index() {
+        let document = doc!{"user": {"name": "a"}};
+
+        assert_eq!(document["user"]["name"], Value::from("a"));
+        assert_eq!(document["user"]["missing"], Value::Null);
+        assert_eq!(document["missing"], Value::Null);
+    }
+
+    #[test]
+    fn insert_if_some() {
+        let mut document = doc!{};
+
+        document.insert_if_some("a", Some(1));
+        document.insert_if_some("b", None::<i32>);
+
+        assert_eq!(document, doc!{"a": 1});
+    }
+
+    #[test]
+    fn pretty_display() {
+        assert_eq!(Document::new().to_string_pretty(), "{}");
+        assert_eq!(doc!{"a": []}.to_string_pretty(), "{\n    \"a\": []\n}");
+
+        let value = Value::from(doc!{"a": [{"b": null}]});
+        assert_eq!(format!("{:#}", value), "{\n    \"a\": [\n        {\n            \"b\": null\n        }\n    ]\n}");
+        assert_eq!(format!("{:#}", Value::Int32(1)), "1");
+    }
+
+    #[test]
+    fn display_escapes() {
+        let doc = doc!{"a\"b": "x\"y\\z\n\u{1}", "s": (Value::Symbol("\t".to_string()))};
+        assert_eq!(doc.to_string(), r#"{ "a\"b": "x\"y\\z\n\u0001", "s": { "$symbol": "\t" } }"#);
+
+        let doc = doc!{"a": "é", "b": [1, "\r"]};
+        let text = doc.to_string();
+        assert_eq!(text, r#"{ "a": "é", "b": [1, "\r"] }"#);
+        #[cfg(feature = "json")]
+        assert_eq!(Document::from_extjson_str(&text).unwrap(), doc);
+
+        let doc = doc!{
+            "f": 1.0,
+            "big": 1e300,
+            "nan": (f64::NAN),
+            "inf": (f64::NEG_INFINITY),
+            "i": 5_000_000_000i64,
+            "re": (Value::RegExp("a\"/\\d".to_string(), "i".to_string())),
+            "js": (Value::JavaScriptCode("f(\"x\")".to_string())),
+            "scope": (Value::JavaScriptCodeWithScope("g(\"y\")".to_string(), Box::new(doc!{"y": "\""}))),
+            "ts": (TimeStamp::new(7, 1)),
+            "bin": (BinarySubtype::Uuid, vec![0xff; 16]),
+            "id": (ObjectId::with_string("5932a005b4b4b4ac168cd9e4").unwrap()),
+            "date": (Utc.timestamp_millis_opt(1_500_000_000_123).unwrap()),
+            "old": (Utc.timestamp_millis_opt(-1).unwrap()),
+            "sym": (Value::Symbol("\"".to_string())),
+            "dec": (Value::Decimal128("1.50".parse::<Decimal128>().unwrap())),
+            "min": (Value::MinKey),
+            "max": (Value::MaxKey),
+            "undef": (Value::Undefined)
+        };
+
+        let text = doc.to_string();
+        assert!(text.starts_with(r#"{ "f": 1.0, "big": 1e300, "nan": { "$numberDouble": "NaN" }"#), "{}", text);
+        assert!(text.contains(r#""re": { "$regularExpression": { "pattern": "a\"/\\d", "options": "i" } }"#), "{}", text);
+        assert!(text.contains(r#""date": { "$date": "2017-07-14T02:40:00.123Z" }"#), "{}", text);
+
+        #[cfg(feature = "json")]
+        {
+            let mut parsed: Document = text.parse().unwrap();
+            assert_eq!(parsed.to_string(), text);
+            assert!(parsed.remove("nan").and_then(|v| v.as_f64()).unwrap().is_nan());
+
+            let mut expected = doc.clone();
+            expected.remove("nan");
+            assert_eq!(parsed, expected);
+            assert_eq!(format!("{:#}", doc).parse::<Document>().unwrap().to_string(), text);
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn from_str() {
+        let doc: Document = r#"{"a": 1, "b": {"$numberLong": "2"}, "c": ["x"]}"#.parse().unwrap();
+        assert_eq!(doc, doc!{"a": 1, "b": 2i64, "c": ["x"]});
+        assert!("[1]".parse::<Document>().is_err());
+        assert!("{".parse::<Document>().is_err());
+    }
 }
@@ -2,20 +2,32 @@ use std::io::{self, Write};
 use std::fmt;
 use std::error;
 use std::i64;
+use std::convert::TryFrom;
 
 use byteorder::{LittleEndian, WriteBytesExt};
-use chrono::Timelike;
 use serde::ser::{self, Serialize};
 
 use crate::value::Value;
-use crate::serde_impl::encode::Encoder;
+use crate::doc::Document;
+use crate::spec::BinarySubtype;
+use crate::serde_impl::encode::{Encoder, RawEncoder};
+use crate::spec::ElementType;
+use crate::decode::MAX_BSON_SIZE;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum EncodeError {
     IoError(io::Error),
     InvalidMapKeyType(Value),
     Unknown(String),
-    UnsupportedUnsignedType
+    UnsupportedUnsignedType,
+    UnsignedOverflow(u64),
+    UnsupportedInt128Type,
+    Int128Overflow(String),
+    /// A binary value whose length doesn't fit in the `i32` BSON gives it.
+    BinaryTooLarge(usize),
+    /// A document whose encoded length is over the 16 MiB BSON allows.
+    DocumentTooLarge(usize),
 }
 
 impl From<io::Error> for EncodeError {
@@ -33,20 +45,17 @@ impl fmt::Display for EncodeError {
             }
             EncodeError::Unknown(ref inner) => inner.fmt(fmt),
             EncodeError::UnsupportedUnsignedType => write!(fmt, "bson does not support unsigned type"),
+            EncodeError::UnsignedOverflow(v) => write!(fmt, "unsigned integer {} does not fit in Int64", v),
+            EncodeError::UnsupportedInt128Type => write!(fmt, "bson does not support 128-bit integers"),
+            EncodeError::Int128Overflow(ref v) => write!(fmt, "integer {} does not fit in Decimal128", v),
+            EncodeError::BinaryTooLarge(len) => write!(fmt, "binary of {} bytes is too large for BSON", len),
+            EncodeError::DocumentTooLarge(len) => write!(fmt, "document of {} bytes is too large for BSON", len),
         }
     }
 }
 
 impl error::Error for EncodeError {
-    fn description(&self) -> &str {
-        match *self {
-            EncodeError::IoError(ref inner) => inner.description(),
-            EncodeError::InvalidMapKeyType(_) => "Invalid map key type",
-            EncodeError::Unknown(ref inner) => inner,
-            EncodeError::UnsupportedUnsignedType => "bson does not support unsigned type",
-        }
-    }
-    fn cause(&self) -> Option<&dyn error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             EncodeError::IoError(ref inner) => Some(inner),
             _ => None,
@@ -62,6 +71,55 @@ impl ser::Error for EncodeError {
 
 pub type EncodeResult<T> = Result<T, EncodeError>;
 
+/// Options controlling how values are encoded to BSON.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodeOptions {
+    /// Skip document fields whose value is `Null` instead of writing them.
+    /// Array elements are always written so indexes stay contiguous.
+    pub omit_null: bool,
+    /// How `u64`, which BSON has no type for, is encoded. Smaller unsigned
+    /// integers are widened losslessly: `u8` and `u16` to `Int32`, `u32` to
+    /// `Int64`.
+    pub unsigned: UnsignedPolicy,
+    /// Apply `unsigned` to every unsigned integer rather than widening the
+    /// small ones. With the default policy this rejects them all.
+    pub strict_unsigned: bool,
+    /// How `i128` and `u128` are encoded.
+    pub int128: Int128Policy,
+}
+
+/// Representation chosen for unsigned integers that don't fit a BSON
+/// integer type losslessly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnsignedPolicy {
+    /// Refuse them with `EncodeError::UnsupportedUnsignedType`.
+    #[default]
+    Error,
+    /// `Int32` if the value fits, otherwise `Int64`. Values above
+    /// `i64::MAX` fail with `EncodeError::UnsignedOverflow`.
+    Fit,
+    /// Always `Int64`, values above `i64::MAX` wrapping around to negative.
+    Int64Wrapping,
+    /// A `Decimal128` with exponent 0. Never loses precision.
+    Decimal128,
+    /// The decimal digits as a string.
+    String,
+}
+
+/// Representation chosen for `i128` and `u128`. Both policies that encode
+/// them can be deserialized back into the same type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Int128Policy {
+    /// A `Decimal128` with exponent 0. Values of more than 34 digits fail
+    /// with `EncodeError::Int128Overflow`.
+    #[default]
+    Decimal128,
+    /// The decimal digits as a string.
+    String,
+    /// Refuse them with `EncodeError::UnsupportedInt128Type`.
+    Error,
+}
+
 pub(crate) fn write_string(writer: &mut impl Write, s: &str) -> EncodeResult<()> {
     writer.write_i32::<LittleEndian>(s.len() as i32 + 1)?;
     writer.write_all(s.as_bytes())?;
@@ -95,33 +153,107 @@ pub(crate) fn write_f64(writer: &mut impl Write, val: f64) -> EncodeResult<()> {
     writer.write_f64::<LittleEndian>(val).map_err(From::from)
 }
 
-fn encode_array(writer: &mut impl Write, arr: &[Value]) -> EncodeResult<()> {
-    let mut buf = Vec::with_capacity(64);
-    write_i32(&mut buf, 0)?;
+/// The length prefix for binary `data` with `extra` bytes before it.
+pub(crate) fn binary_len(data: &[u8], extra: usize) -> EncodeResult<i32> {
+    i32::try_from(data.len() + extra).map_err(|_| EncodeError::BinaryTooLarge(data.len()))
+}
 
-    for (key, val) in arr.iter().enumerate() {
-        encode_bson(&mut buf, &key.to_string(), val)?;
-    }
+/// Reserve a length prefix, returning where it is.
+pub(crate) fn begin_document(buf: &mut Vec<u8>) -> usize {
+    let start = buf.len();
+    buf.extend_from_slice(&[0; 4]);
+    start
+}
 
-    buf.write_u8(0)?;
+/// Terminate the document begun at `start` and fill in its length.
+pub(crate) fn end_document(buf: &mut Vec<u8>, start: usize) -> EncodeResult<()> {
+    buf.push(0);
+    patch_len(buf, start)
+}
 
-    let len_bytes = (buf.len() as i32).to_le_bytes();
+/// Fill in the length prefix at `start`, which runs to the end of `buf`.
+fn patch_len(buf: &mut [u8], start: usize) -> EncodeResult<()> {
+    let len = buf.len() - start;
 
-    buf[..4].clone_from_slice(&len_bytes);
+    if len > MAX_BSON_SIZE as usize {
+        return Err(EncodeError::DocumentTooLarge(len));
+    }
 
-    writer.write_all(&buf)?;
+    buf[start..start + 4].copy_from_slice(&(len as i32).to_le_bytes());
     Ok(())
 }
 
+fn encode_array(buf: &mut Vec<u8>, arr: &[Value], options: EncodeOptions) -> EncodeResult<()> {
+    let start = begin_document(buf);
+
+    for (key, val) in arr.iter().enumerate() {
+        buf.push(val.element_type() as u8);
+        write!(buf, "{}", key)?;
+        buf.push(0);
+        encode_value_with_options(buf, val, options)?;
+    }
+
+    end_document(buf, start)
+}
+
 pub fn encode_bson(writer: &mut impl Write, key: &str, val: &Value) -> EncodeResult<()> {
-    writer.write_u8(val.element_type() as u8)?;
-    write_cstring(writer, key)?;
+    encode_bson_with_options(writer, key, val, EncodeOptions::default())
+}
 
+pub fn encode_bson_with_options(
+    writer: &mut impl Write,
+    key: &str,
+    val: &Value,
+    options: EncodeOptions
+) -> EncodeResult<()> {
+    match *val {
+        // Their lengths come first, so they are assembled in memory
+        Value::Array(_) | Value::Document(_) | Value::JavaScriptCodeWithScope(..) => {
+            let mut buf = Vec::with_capacity(key.len() + 2 + val.value_len());
+            encode_element(&mut buf, key, val, options)?;
+            writer.write_all(&buf).map_err(From::from)
+        }
+        _ => {
+            writer.write_u8(val.element_type() as u8)?;
+            write_cstring(writer, key)?;
+            encode_scalar(writer, val)
+        }
+    }
+}
+
+fn encode_element(buf: &mut Vec<u8>, key: &str, val: &Value, options: EncodeOptions) -> EncodeResult<()> {
+    buf.push(val.element_type() as u8);
+    write_cstring(buf, key)?;
+    encode_value_with_options(buf, val, options)
+}
+
+/// Write the encoded form of `val`, without the element header. Nested
+/// documents are written in place, their lengths filled in once they are
+/// done, so nothing is copied however deep they go.
+pub(crate) fn encode_value_with_options(
+    buf: &mut Vec<u8>,
+    val: &Value,
+    options: EncodeOptions
+) -> EncodeResult<()> {
+    match *val {
+        Value::Array(ref v) => encode_array(buf, &v, options),
+        Value::Document(ref v) => encode_document_into(buf, v, options),
+        Value::JavaScriptCodeWithScope(ref code, ref scope) => {
+            // The same length prefix as a document's, ahead of the code
+            let start = begin_document(buf);
+            write_string(buf, code)?;
+            encode_document_into(buf, &**scope, options)?;
+            patch_len(buf, start)
+        }
+        _ => encode_scalar(buf, val),
+    }
+}
+
+/// Write a value that holds no document, so has nothing to fill in later.
+fn encode_scalar(writer: &mut impl Write, val: &Value) -> EncodeResult<()> {
     match *val {
         Value::Double(v) => write_f64(writer, v),
         Value::String(ref v) => write_string(writer, &v),
-        Value::Array(ref v) => encode_array(writer, &v),
-        Value::Document(ref v) => encode_document(writer, v),
         Value::Boolean(v) => writer.write_u8(if v { 0x01 } else { 0x00 }).map_err(From::from),
         Value::RegExp(ref pat, ref opt) => {
             write_cstring(writer, pat)?;
@@ -129,50 +261,88 @@ pub fn encode_bson(writer: &mut impl Write, key: &str, val: &Value) -> EncodeRes
         }
         Value::JavaScriptCode(ref code) => write_string(writer, &code),
         Value::ObjectId(ref id) => writer.write_all(&id.bytes()).map_err(From::from),
-        Value::JavaScriptCodeWithScope(ref code, ref scope) => {
-            let mut buf = Vec::new();
-            write_string(&mut buf, code)?;
-            encode_document(&mut buf, scope)?;
-
-            write_i32(writer, buf.len() as i32 + 4)?;
-            writer.write_all(&buf).map_err(From::from)
-        }
         Value::Int32(v) => write_i32(writer, v),
         Value::Int64(v) => write_i64(writer, v),
-        Value::TimeStamp(v) => write_u64(writer, v),
+        Value::TimeStamp(v) => write_u64(writer, u64::from(v)),
+        Value::Binary(BinarySubtype::BinaryOld, ref data) => {
+            let len = binary_len(data, 4)?;
+            write_i32(writer, len)?;
+            writer.write_u8(From::from(BinarySubtype::BinaryOld))?;
+            write_i32(writer, len - 4)?;
+            writer.write_all(data).map_err(From::from)
+        }
         Value::Binary(subtype, ref data) => {
-            write_i32(writer, data.len() as i32)?;
+            write_i32(writer, binary_len(data, 0)?)?;
             writer.write_u8(From::from(subtype))?;
             writer.write_all(data).map_err(From::from)
         }
-        Value::UTCDatetime(ref v) => {
-            write_i64(
-                writer,
-                v.timestamp() * 1000 + i64::from(v.nanosecond() / 1_000_000)
-            )
+        Value::UTCDatetime(ref v) => write_i64(writer, v.timestamp_millis()),
+        Value::Null | Value::MinKey | Value::MaxKey | Value::Undefined => Ok(()),
+        Value::Symbol(ref v) => write_string(writer, &v),
+        Value::Decimal128(ref v) => writer.write_all(&v.bytes()).map_err(From::from),
+        Value::Array(_) | Value::Document(_) | Value::JavaScriptCodeWithScope(..) => {
+            unreachable!("nested values are encoded by encode_value_with_options")
         }
-        Value::Null => Ok(()),
-        Value::Symbol(ref v) => write_string(writer, &v)
     }
 }
 
 pub fn encode_document<'a, S, D> (writer: &mut impl Write, document: D) -> EncodeResult<()>
     where S: AsRef<str> + 'a, D: IntoIterator<Item = (&'a S, &'a Value)>
+{
+    encode_document_with_options(writer, document, EncodeOptions::default())
+}
+
+/// The document is encoded into memory in a single pass, then written with
+/// one `write_all`.
+pub fn encode_document_with_options<'a, S, D> (
+    writer: &mut impl Write,
+    document: D,
+    options: EncodeOptions
+) -> EncodeResult<()>
+    where S: AsRef<str> + 'a, D: IntoIterator<Item = (&'a S, &'a Value)>
 {
     let mut buf = Vec::with_capacity(64);
-    write_i32(&mut buf, 0)?;
+    encode_document_into(&mut buf, document, options)?;
+
+    writer.write_all(&buf)?;
+    Ok(())
+}
+
+/// Append an encoded document to `buf`.
+pub(crate) fn encode_document_into<'a, S, D> (
+    buf: &mut Vec<u8>,
+    document: D,
+    options: EncodeOptions
+) -> EncodeResult<()>
+    where S: AsRef<str> + 'a, D: IntoIterator<Item = (&'a S, &'a Value)>
+{
+    let start = begin_document(buf);
 
     for (key, val) in document {
-        encode_bson(&mut buf, key.as_ref(), val)?;
+        if options.omit_null && *val == Value::Null {
+            continue;
+        }
+
+        encode_element(buf, key.as_ref(), val, options)?;
     }
 
-    buf.write_u8(0)?;
+    end_document(buf, start)
+}
 
-    let len_bytes = (buf.len() as i32).to_le_bytes();
+/// Write one document to an async writer. The document is encoded into
+/// memory first and then written in a single `write_all`.
+#[cfg(feature = "tokio")]
+pub async fn encode_document_async<'a, W, S, D>(writer: &mut W, document: D) -> EncodeResult<()>
+    where W: tokio::io::AsyncWrite + Unpin + ?Sized,
+          S: AsRef<str> + 'a,
+          D: IntoIterator<Item = (&'a S, &'a Value)>
+{
+    use tokio::io::AsyncWriteExt;
 
-    buf[..4].clone_from_slice(&len_bytes);
+    let mut buf = Vec::with_capacity(64);
+    encode_document(&mut buf, document)?;
+    writer.write_all(&buf).await?;
 
-    writer.write_all(&buf)?;
     Ok(())
 }
 
@@ -183,24 +353,145 @@ pub fn to_bson<T: ?Sized>(value: &T) -> EncodeResult<Value>
     value.serialize(ser)
 }
 
+pub fn to_bson_with_options<T>(value: &T, options: EncodeOptions) -> EncodeResult<Value>
+    where T: ?Sized + Serialize
+{
+    value.serialize(Encoder::with_options(options))
+}
+
+/// Serialize `value` into a `Document`. Like `to_vec`, this fails with
+/// `InvalidMapKeyType` if `value` doesn't serialize as a document.
+///
+/// ```
+/// use serde_derive::Serialize;
+/// use bsonrs::{doc, encode};
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     name: String,
+///     age: i32,
+/// }
+///
+/// let user = User { name: "a".to_string(), age: 3 };
+/// assert_eq!(encode::to_document(&user).unwrap(), doc!{"name": "a", "age": 3});
+/// assert!(encode::to_document(&3).is_err());
+/// ```
+pub fn to_document<T>(value: &T) -> EncodeResult<Document>
+    where T: ?Sized + Serialize
+{
+    to_document_with_options(value, EncodeOptions::default())
+}
+
+pub fn to_document_with_options<T>(value: &T, options: EncodeOptions) -> EncodeResult<Document>
+    where T: ?Sized + Serialize
+{
+    match to_bson_with_options(value, options)? {
+        Value::Document(doc) => Ok(doc),
+        other => Err(EncodeError::InvalidMapKeyType(other)),
+    }
+}
+
 pub fn to_vec<T: ?Sized>(value: &T) -> EncodeResult<Vec<u8>>
     where T: Serialize
 {
-    let bson = to_bson(value)?;
+    to_vec_with_options(value, EncodeOptions::default())
+}
 
-    if let Value::Document(object) = bson {
-        let mut buf: Vec<u8> = Vec::new();
-        encode_document(&mut buf, &object)?;
-        return Ok(buf)
+pub fn to_vec_with_options<T>(value: &T, options: EncodeOptions) -> EncodeResult<Vec<u8>>
+    where T: ?Sized + Serialize
+{
+    let doc = to_document_with_options(value, options)?;
+
+    let mut buf = Vec::with_capacity(doc.encoded_len());
+    encode_document_into(&mut buf, &doc, options)?;
+    Ok(buf)
+}
+
+/// Serialize `value` as a document, appending it to `buf`. Reusing one
+/// buffer across many documents avoids allocating for each of them; clear
+/// it in between unless they are meant to end up back to back. As with
+/// `to_writer`, no `Value` is built. On error `buf` is left as it was.
+///
+/// ```
+/// use serde_derive::Serialize;
+/// use bsonrs::{doc, encode, Document};
+///
+/// #[derive(Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let mut buf = Vec::new();
+///
+/// for i in 0..3 {
+///     buf.clear();
+///     encode::to_vec_into(&Point { x: i, y: -i }, &mut buf).unwrap();
+///     assert_eq!(Document::from_slice(&buf).unwrap(), doc!{"x": i, "y": -i});
+/// }
+/// ```
+pub fn to_vec_into<T>(value: &T, buf: &mut Vec<u8>) -> EncodeResult<()>
+    where T: ?Sized + Serialize
+{
+    to_vec_into_with_options(value, buf, EncodeOptions::default())
+}
+
+pub fn to_vec_into_with_options<T>(value: &T, buf: &mut Vec<u8>, options: EncodeOptions) -> EncodeResult<()>
+    where T: ?Sized + Serialize
+{
+    let start = buf.len();
+    serialize_document(value, buf, options)?;
+
+    // Drop the element type and empty key
+    buf.drain(start..start + 2);
+    Ok(())
+}
+
+/// Serialize `value` at the end of `buf` as an element with an empty key,
+/// failing unless it is a document.
+fn serialize_document<T>(value: &T, buf: &mut Vec<u8>, options: EncodeOptions) -> EncodeResult<()>
+    where T: ?Sized + Serialize
+{
+    let start = buf.len();
+
+    if let Err(err) = value.serialize(RawEncoder::new(buf, "", options)) {
+        buf.truncate(start);
+        return Err(err);
     }
 
-    Err(EncodeError::InvalidMapKeyType(bson))
+    // Only documents can stand on their own
+    if buf[start] != ElementType::Document as u8 {
+        buf.truncate(start);
+        return Err(EncodeError::InvalidMapKeyType(to_bson_with_options(value, options)?));
+    }
+
+    Ok(())
+}
+
+/// Serialize `value` as a document into `writer`. Unlike `to_vec`, no
+/// `Value` is built in between: fields are encoded straight into a single
+/// buffer, which is then written in one go.
+pub fn to_writer<T>(writer: &mut impl Write, value: &T) -> EncodeResult<()>
+    where T: ?Sized + Serialize
+{
+    to_writer_with_options(writer, value, EncodeOptions::default())
+}
+
+pub fn to_writer_with_options<T>(writer: &mut impl Write, value: &T, options: EncodeOptions) -> EncodeResult<()>
+    where T: ?Sized + Serialize
+{
+    let mut buf = Vec::with_capacity(64);
+    serialize_document(value, &mut buf, options)?;
+
+    // Skip the element type and empty key
+    writer.write_all(&buf[2..])?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use std::io::Cursor;
-    use crate::encode::encode_document;
+    use crate::encode::{encode_document, encode_document_with_options, EncodeOptions};
     use crate::decode::decode_document;
     use crate::doc;
 
@@ -218,4 +509,252 @@ mod test {
 
         assert_eq!(document, document2);
     }
+
+    #[test]
+    fn omit_null() {
+        let document = doc!{"aa": null, "bb": {"cc": null, "dd": 1}, "ee": [null]};
+
+        let mut buf: Vec<u8> = Vec::new();
+        encode_document_with_options(&mut buf, &document, EncodeOptions { omit_null: true, ..Default::default() }).unwrap();
+
+        let document2 = decode_document(&mut Cursor::new(buf)).unwrap();
+
+        assert_eq!(document2, doc!{"bb": {"dd": 1}, "ee": [null]});
+    }
+
+    #[test]
+    fn to_writer() {
+        use serde_derive::Serialize;
+        use crate::encode::{to_writer, to_writer_with_options, to_vec_with_options};
+        use crate::object_id::ObjectId;
+        use crate::value::{UTCDateTime, TimeStamp};
+        use crate::{Document, Value};
+
+        #[derive(Serialize)]
+        enum Shape {
+            Unit,
+            Circle(f64),
+            Point(i32, i32),
+            Rect { w: i32, h: Option<i32> },
+        }
+
+        #[derive(Serialize)]
+        struct Inner<'a> {
+            name: &'a str,
+            nothing: Option<i32>,
+        }
+
+        #[derive(Serialize)]
+        struct Outer<'a> {
+            id: Value,
+            when: UTCDateTime,
+            ts: TimeStamp,
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+            inner: Inner<'a>,
+            list: Vec<Inner<'a>>,
+            shapes: Vec<Shape>,
+            empty: Document,
+            doc: Document,
+            c: char,
+        }
+
+        let outer = Outer {
+            id: Value::ObjectId(ObjectId::new()),
+            when: UTCDateTime(chrono::Utc::now()),
+            ts: TimeStamp { timestamp: 1, increment: 2 },
+            data: vec![1, 2, 3],
+            inner: Inner { name: "a", nothing: None },
+            list: vec![Inner { name: "b", nothing: Some(1) }],
+            shapes: vec![Shape::Unit, Shape::Circle(1.5), Shape::Point(1, 2), Shape::Rect { w: 1, h: None }],
+            empty: Document::new(),
+            doc: doc!{"x": [1, {"y": null}]},
+            c: 'c',
+        };
+
+        for &omit_null in &[false, true] {
+            let options = EncodeOptions { omit_null, ..Default::default() };
+
+            let mut buf = Vec::new();
+            to_writer_with_options(&mut buf, &outer, options).unwrap();
+
+            assert_eq!(buf, to_vec_with_options(&outer, options).unwrap());
+        }
+
+        assert!(to_writer(&mut Vec::new(), &1).is_err());
+    }
+
+    #[test]
+    fn unsigned_policy() {
+        use crate::encode::{to_bson, to_bson_with_options, to_writer_with_options, UnsignedPolicy, EncodeError};
+        use crate::decimal128::Decimal128;
+        use crate::Value;
+
+        let options = |unsigned| EncodeOptions { unsigned, ..Default::default() };
+
+        assert!(matches!(to_bson(&1u64), Err(EncodeError::UnsupportedUnsignedType)));
+        assert_eq!(to_bson(&1u8).unwrap(), Value::Int32(1));
+        assert_eq!(to_bson(&u16::MAX).unwrap(), Value::Int32(u16::MAX as i32));
+        assert_eq!(to_bson(&u32::MAX).unwrap(), Value::Int64(u32::MAX as i64));
+        assert_eq!(crate::decode::from_bson::<u32>(to_bson(&u32::MAX).unwrap()).unwrap(), u32::MAX);
+
+        let strict = EncodeOptions { strict_unsigned: true, ..Default::default() };
+        assert!(matches!(to_bson_with_options(&1u8, strict), Err(EncodeError::UnsupportedUnsignedType)));
+        assert!(matches!(to_bson_with_options(&1u32, strict), Err(EncodeError::UnsupportedUnsignedType)));
+
+        let fit = options(UnsignedPolicy::Fit);
+        assert_eq!(to_bson_with_options(&7u64, fit).unwrap(), Value::Int32(7));
+        assert_eq!(to_bson_with_options(&(u32::MAX as u64), fit).unwrap(), Value::Int64(u32::MAX as i64));
+        assert!(matches!(to_bson_with_options(&u64::MAX, fit), Err(EncodeError::UnsignedOverflow(u64::MAX))));
+
+        let wrapping = options(UnsignedPolicy::Int64Wrapping);
+        assert_eq!(to_bson_with_options(&1u64, wrapping).unwrap(), Value::Int64(1));
+        assert_eq!(to_bson_with_options(&u64::MAX, wrapping).unwrap(), Value::Int64(-1));
+
+        let decimal = options(UnsignedPolicy::Decimal128);
+        assert_eq!(
+            to_bson_with_options(&u64::MAX, decimal).unwrap(),
+            Value::Decimal128(u64::MAX.to_string().parse::<Decimal128>().unwrap())
+        );
+
+        let string = EncodeOptions { strict_unsigned: true, ..options(UnsignedPolicy::String) };
+        assert_eq!(to_bson_with_options(&vec![1u32], string).unwrap(), Value::Array(vec![Value::from("1")].into_iter().collect()));
+
+        // Options reach nested values, with or without a `Value` in between
+        let doc = doc!{"a": {"b": [1, (Value::Int64(u32::MAX as i64))]}};
+        let mut nested = std::collections::BTreeMap::new();
+        nested.insert("b", vec![1u64, u32::MAX as u64]);
+        let mut outer = std::collections::BTreeMap::new();
+        outer.insert("a", nested);
+
+        assert_eq!(to_bson_with_options(&outer, fit).unwrap(), Value::Document(doc.clone()));
+
+        let mut buf = Vec::new();
+        to_writer_with_options(&mut buf, &outer, fit).unwrap();
+        assert_eq!(buf, doc.to_vec().unwrap());
+    }
+
+    #[test]
+    fn int128_policy() {
+        use crate::encode::{to_bson, to_bson_with_options, Int128Policy, EncodeError};
+        use crate::decode::{from_bson, from_slice_borrowed};
+        use serde_derive::{Serialize, Deserialize};
+        use crate::decimal128::Decimal128;
+        use crate::Value;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Ids {
+            a: u128,
+            b: i128,
+        }
+
+        let ids = Ids { a: 10u128.pow(33), b: -5 };
+
+        let value = to_bson(&ids).unwrap();
+        assert_eq!(value, Value::Document(doc!{
+            "a": ("1000000000000000000000000000000000".parse::<Decimal128>().unwrap()),
+            "b": ("-5".parse::<Decimal128>().unwrap())
+        }));
+        assert_eq!(from_bson::<Ids>(value).unwrap(), ids);
+
+        let bytes = crate::encode::to_vec(&ids).unwrap();
+        assert_eq!(from_slice_borrowed::<Ids>(&bytes).unwrap(), ids);
+
+        assert!(matches!(to_bson(&u128::MAX), Err(EncodeError::Int128Overflow(_))));
+
+        let string = EncodeOptions { int128: Int128Policy::String, ..Default::default() };
+        let ids = Ids { a: u128::MAX, b: i128::MIN };
+        let value = to_bson_with_options(&ids, string).unwrap();
+        assert_eq!(value, Value::Document(doc!{"a": (u128::MAX.to_string()), "b": (i128::MIN.to_string())}));
+        assert_eq!(from_bson::<Ids>(value).unwrap(), ids);
+
+        let error = EncodeOptions { int128: Int128Policy::Error, ..Default::default() };
+        assert!(matches!(to_bson_with_options(&1i128, error), Err(EncodeError::UnsupportedInt128Type)));
+
+        // Plain integers are fine too
+        assert_eq!(from_bson::<i128>(Value::Int64(-3)).unwrap(), -3);
+        assert!(from_bson::<u128>(Value::Decimal128("1.5".parse().unwrap())).is_err());
+    }
+
+    #[test]
+    fn binary_old() {
+        use crate::spec::BinarySubtype;
+
+        let document = doc!{"aa": (BinarySubtype::BinaryOld, vec![1, 2, 3])};
+
+        let mut buf: Vec<u8> = Vec::new();
+        encode_document(&mut buf, &document).unwrap();
+
+        // total length, subtype, then the inner length of the payload
+        assert_eq!(&buf[8..17], &[7, 0, 0, 0, 2, 3, 0, 0, 0]);
+
+        let document2 = decode_document(&mut Cursor::new(buf)).unwrap();
+
+        assert_eq!(document, document2);
+    }
+
+    #[test]
+    fn nested_in_place() {
+        use crate::{Value, Document};
+
+        let mut document = doc!{"x": 1};
+        for i in 0..50 {
+            document = if i % 2 == 0 {
+                doc!{"d": document, "a": [1, "two"]}
+            } else {
+                doc!{"a": [document], "f": (Value::JavaScriptCodeWithScope("f()".to_string(), Box::new(doc!{"s": {"t": 1}})))}
+            };
+        }
+
+        let buf = document.to_vec().unwrap();
+        assert_eq!(buf.len(), document.encoded_len());
+        assert_eq!(Document::from_slice(&buf).unwrap(), document);
+
+        let mut written = Vec::new();
+        encode_document(&mut written, &document).unwrap();
+        assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn too_large() {
+        use crate::Value;
+        use crate::spec::BinarySubtype;
+        use crate::encode::{EncodeError, encode_bson};
+
+        let data = vec![0; 16 * 1024 * 1024];
+        let document = doc!{"a": {"b": (BinarySubtype::Generic, data)}};
+
+        assert!(matches!(document.to_vec(), Err(EncodeError::DocumentTooLarge(_))));
+        assert!(matches!(encode_bson(&mut Vec::new(), "d", &Value::Document(document.clone())), Err(EncodeError::DocumentTooLarge(_))));
+        assert!(matches!(crate::encode::to_vec(&document), Err(EncodeError::DocumentTooLarge(_))));
+
+        let mut buf = Vec::new();
+        encode_bson(&mut buf, "i", &Value::Int32(1)).unwrap();
+        assert_eq!(buf, [0x10, b'i', 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn reused_buffer() {
+        use std::collections::BTreeMap;
+
+        let a = doc!{"a": 1};
+        let b = doc!{"b": [true], "n": null};
+
+        let mut buf = Vec::new();
+        a.to_vec_into(&mut buf).unwrap();
+        b.to_vec_into_with_options(&mut buf, EncodeOptions { omit_null: true, ..Default::default() }).unwrap();
+
+        let mut expected = a.to_vec().unwrap();
+        expected.extend(doc!{"b": [true]}.to_vec().unwrap());
+        assert_eq!(buf, expected);
+
+        let map: BTreeMap<_, _> = vec![("b", vec![true])].into_iter().collect();
+        buf.truncate(a.to_vec().unwrap().len());
+        super::to_vec_into(&map, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+
+        // Only documents can be encoded, and a failure leaves the buffer alone
+        assert!(super::to_vec_into(&5, &mut buf).is_err());
+        assert_eq!(buf, expected);
+    }
 }
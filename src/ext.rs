@@ -0,0 +1,204 @@
+//! Registry for user-defined binary subtypes
+//!
+//! BSON reserves binary subtypes `0x80`–`0xFF` for application use. An
+//! `ExtRegistry` associates such a subtype with a Rust type and a pair of
+//! closures, so values of that type can be turned into `Value::Binary` and
+//! back without handling the raw bytes at every call site.
+//!
+//! ```
+//! use bsonrs::ext::ExtRegistry;
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct Point(i16, i16);
+//!
+//! let mut registry = ExtRegistry::new();
+//! registry.register(0x80,
+//!     |p: &Point| { let mut buf = p.0.to_le_bytes().to_vec(); buf.extend(&p.1.to_le_bytes()); buf },
+//!     |b: &[u8]| if b.len() == 4 {
+//!         Some(Point(i16::from_le_bytes([b[0], b[1]]), i16::from_le_bytes([b[2], b[3]])))
+//!     } else {
+//!         None
+//!     }
+//! ).unwrap();
+//!
+//! let value = registry.to_value(&Point(1, -1)).unwrap();
+//! assert_eq!(registry.from_value::<Point>(&value).unwrap(), Point(1, -1));
+//! ```
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::{fmt, result, error};
+
+use crate::value::Value;
+use crate::spec::BinarySubtype;
+
+type EncodeFn = Box<dyn Fn(&dyn Any) -> Vec<u8> + Send + Sync>;
+type DecodeFn = Box<dyn Fn(&[u8]) -> Option<Box<dyn Any + Send>> + Send + Sync>;
+
+struct Entry {
+    type_id: TypeId,
+    encode: EncodeFn,
+    decode: DecodeFn,
+}
+
+#[derive(Default)]
+pub struct ExtRegistry {
+    entries: HashMap<u8, Entry>,
+    subtypes: HashMap<TypeId, u8>,
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl ExtRegistry {
+    pub fn new() -> ExtRegistry {
+        ExtRegistry::default()
+    }
+
+    /// Associate `subtype` (which must be in the user-defined range
+    /// `0x80..=0xFF`) with the type `T`. Each subtype and each type can only
+    /// be registered once.
+    pub fn register<T, E, D>(&mut self, subtype: u8, encode: E, decode: D) -> Result<()>
+        where T: Any + Send,
+              E: Fn(&T) -> Vec<u8> + Send + Sync + 'static,
+              D: Fn(&[u8]) -> Option<T> + Send + Sync + 'static
+    {
+        if subtype < 0x80 {
+            return Err(Error::InvalidSubtype(subtype));
+        }
+
+        let type_id = TypeId::of::<T>();
+
+        if self.entries.contains_key(&subtype) || self.subtypes.contains_key(&type_id) {
+            return Err(Error::AlreadyRegistered(subtype));
+        }
+
+        let entry = Entry {
+            type_id,
+            encode: Box::new(move |any| {
+                // The registry only hands this closure values of type `T`
+                encode(any.downcast_ref::<T>().expect("extension type mismatch"))
+            }),
+            decode: Box::new(move |bytes| {
+                decode(bytes).map(|v| Box::new(v) as Box<dyn Any + Send>)
+            }),
+        };
+
+        self.entries.insert(subtype, entry);
+        self.subtypes.insert(type_id, subtype);
+
+        Ok(())
+    }
+
+    /// The subtype registered for `T`, if any.
+    pub fn subtype_of<T: Any>(&self) -> Option<u8> {
+        self.subtypes.get(&TypeId::of::<T>()).cloned()
+    }
+
+    /// Encode `value` as a `Value::Binary` with its registered subtype, or
+    /// `None` if `T` hasn't been registered.
+    pub fn to_value<T: Any>(&self, value: &T) -> Option<Value> {
+        let subtype = self.subtype_of::<T>()?;
+        let entry = &self.entries[&subtype];
+
+        Some(Value::Binary(BinarySubtype::UserDefined(subtype), (entry.encode)(value)))
+    }
+
+    /// Decode a `Value::Binary` into `T`, checking that its subtype is the one
+    /// registered for `T`.
+    pub fn from_value<T: Any>(&self, value: &Value) -> Result<T> {
+        let any = self.decode_any(value)?;
+
+        match any.downcast::<T>() {
+            Ok(v) => Ok(*v),
+            Err(_) => Err(Error::TypeMismatch(self.subtype(value).unwrap_or(0)))
+        }
+    }
+
+    /// Decode a `Value::Binary` into whatever type is registered for its
+    /// subtype.
+    pub fn decode_any(&self, value: &Value) -> Result<Box<dyn Any + Send>> {
+        let (subtype, bytes) = match value {
+            Value::Binary(subtype, bytes) => (u8::from(*subtype), bytes),
+            _ => return Err(Error::NotBinary)
+        };
+
+        let entry = self.entries.get(&subtype).ok_or(Error::NotRegistered(subtype))?;
+
+        (entry.decode)(bytes).ok_or(Error::InvalidData(subtype))
+    }
+
+    /// Whether `value` is a binary whose subtype has a registered type.
+    pub fn is_registered(&self, value: &Value) -> bool {
+        self.subtype(value).map(|t| self.entries.contains_key(&t)).unwrap_or(false)
+    }
+
+    /// The `TypeId` registered for a subtype.
+    pub fn type_id_of(&self, subtype: u8) -> Option<TypeId> {
+        self.entries.get(&subtype).map(|e| e.type_id)
+    }
+
+    fn subtype(&self, value: &Value) -> Option<u8> {
+        match value {
+            Value::Binary(subtype, _) => Some(u8::from(*subtype)),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Debug for ExtRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut subtypes: Vec<_> = self.entries.keys().collect();
+        subtypes.sort();
+
+        f.debug_struct("ExtRegistry").field("subtypes", &subtypes).finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    InvalidSubtype(u8),
+    AlreadyRegistered(u8),
+    NotRegistered(u8),
+    NotBinary,
+    TypeMismatch(u8),
+    InvalidData(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidSubtype(t) => write!(fmt, "subtype {:#04x} is not in the user-defined range", t),
+            Error::AlreadyRegistered(t) => write!(fmt, "subtype {:#04x} or its type is already registered", t),
+            Error::NotRegistered(t) => write!(fmt, "no type registered for subtype {:#04x}", t),
+            Error::NotBinary => write!(fmt, "value is not binary"),
+            Error::TypeMismatch(t) => write!(fmt, "subtype {:#04x} is registered for a different type", t),
+            Error::InvalidData(t) => write!(fmt, "invalid data for subtype {:#04x}", t),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(test)]
+mod test {
+    use super::{ExtRegistry, Error};
+    use crate::Value;
+
+    #[test]
+    fn round_trip() {
+        let mut registry = ExtRegistry::new();
+
+        registry.register(0x90, |v: &u16| v.to_le_bytes().to_vec(), |b: &[u8]| {
+            if b.len() == 2 { Some(u16::from_le_bytes([b[0], b[1]])) } else { None }
+        }).unwrap();
+
+        assert_eq!(registry.register(0x10, |_: &u8| vec![], |_| Some(0u8)), Err(Error::InvalidSubtype(0x10)));
+        assert_eq!(registry.register(0x90, |_: &u8| vec![], |_| Some(0u8)), Err(Error::AlreadyRegistered(0x90)));
+
+        let value = registry.to_value(&513u16).unwrap();
+        assert!(registry.is_registered(&value));
+        assert_eq!(registry.from_value::<u16>(&value), Ok(513));
+        assert_eq!(registry.from_value::<u32>(&value), Err(Error::TypeMismatch(0x90)));
+        assert_eq!(registry.from_value::<u16>(&Value::Null), Err(Error::NotBinary));
+        assert_eq!(registry.to_value(&1u32), None);
+    }
+}
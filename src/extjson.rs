@@ -0,0 +1,390 @@
+//! Extended JSON v2
+//!
+//! Parses MongoDB Extended JSON text, in both its canonical
+//! (`{"$numberInt": "1"}`) and relaxed (`1`) forms, straight into a
+//! `Document`. Key order is preserved, and malformed type wrappers such as
+//! `{"$oid": "xyz"}` are reported as errors rather than panicking.
+//!
+//! ```
+//! use bsonrs::Document;
+//!
+//! let doc = Document::from_extjson_str(r#"{
+//!     "_id": {"$oid": "5932a005b4b4b4ac168cd9e4"},
+//!     "n": {"$numberLong": "42"},
+//!     "ratio": 0.5
+//! }"#).unwrap();
+//!
+//! assert_eq!(doc.get_i64("n"), Ok(42));
+//! assert_eq!(doc.get_f64("ratio"), Ok(0.5));
+//! ```
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::de::{self, Deserialize, Deserializer, Visitor, MapAccess, SeqAccess};
+
+use crate::value::{Value, Array, TimeStamp, JSON_NUMBER_TOKEN, from_json_number, clamped_datetime};
+use crate::doc::Document;
+use crate::decode::{DecodeError, DecodeResult};
+use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
+use crate::spec::BinarySubtype;
+use crate::util::base64;
+use crate::util::hex::FromHex;
+
+/// Parse an Extended JSON object into a `Document`.
+pub fn from_str(s: &str) -> DecodeResult<Document> {
+    let mut de = serde_json::Deserializer::from_str(s);
+
+    let value = ExtJsonValue::deserialize(&mut de).and_then(|v| {
+        de.end()?;
+        Ok(v.0)
+    }).map_err(json_error)?;
+
+    match value {
+        Value::Document(doc) => Ok(doc),
+        other => Err(DecodeError::InvalidType(
+            format!("expected an Extended JSON object, found {}", other)
+        )),
+    }
+}
+
+fn json_error(err: serde_json::Error) -> DecodeError {
+    if err.is_data() {
+        DecodeError::InvalidValue(err.to_string())
+    } else {
+        DecodeError::SyntaxError(err.to_string())
+    }
+}
+
+/// A `Value` deserialized from Extended JSON.
+pub(crate) struct ExtJsonValue(pub(crate) Value);
+
+impl<'de> Deserialize<'de> for ExtJsonValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_any(ExtJsonVisitor).map(ExtJsonValue)
+    }
+}
+
+struct ExtJsonVisitor;
+
+impl<'de> Visitor<'de> for ExtJsonVisitor {
+    type Value = Value;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("an Extended JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        if (i64::from(i32::MIN)..=i64::from(i32::MAX)).contains(&v) {
+            Ok(Value::Int32(v as i32))
+        } else {
+            Ok(Value::Int64(v))
+        }
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        if v <= i64::MAX as u64 {
+            self.visit_i64(v as i64)
+        } else {
+            Ok(from_json_number(&v.to_string()))
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Double(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+        where A: SeqAccess<'de>
+    {
+        let mut arr = Array::new();
+
+        while let Some(ExtJsonValue(v)) = seq.next_element()? {
+            arr.push(v);
+        }
+
+        Ok(Value::Array(arr))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+        where A: MapAccess<'de>
+    {
+        let mut doc = Document::new();
+
+        while let Some((key, ExtJsonValue(v))) = map.next_entry::<String, ExtJsonValue>()? {
+            doc.insert_value(key, v);
+        }
+
+        from_wrapper(doc).map_err(de::Error::custom)
+    }
+}
+
+/// Turn a `{"$type": ...}` wrapper into the value it stands for. Documents
+/// whose first key isn't a known wrapper (including query operators such as
+/// `$gt`) are returned unchanged.
+fn from_wrapper(doc: Document) -> Result<Value, String> {
+    let key = match doc.keys().next() {
+        Some(key) if key.starts_with('$') => key.clone(),
+        _ => return Ok(Value::Document(doc)),
+    };
+
+    let expect_keys = |keys: &[&str]| -> Result<(), String> {
+        if doc.len() == keys.len() && keys.iter().all(|k| doc.contains_key(k)) {
+            Ok(())
+        } else {
+            Err(format!("invalid `{}` wrapper, expected keys {:?}", key, keys))
+        }
+    };
+
+    let string = |k: &str| -> Result<&str, String> {
+        doc.get_str(k).map_err(|_| format!("`{}` must be a string", k))
+    };
+
+    let value = match key.as_str() {
+        "$oid" => {
+            expect_keys(&["$oid"])?;
+            let hex = string("$oid")?;
+            Value::ObjectId(ObjectId::with_string(hex).map_err(|_| format!("invalid ObjectId `{}`", hex))?)
+        }
+        "$symbol" => {
+            expect_keys(&["$symbol"])?;
+            Value::Symbol(string("$symbol")?.to_string())
+        }
+        "$numberInt" => {
+            expect_keys(&["$numberInt"])?;
+            let s = string("$numberInt")?;
+            Value::Int32(s.parse().map_err(|_| format!("invalid $numberInt `{}`", s))?)
+        }
+        "$numberLong" => {
+            expect_keys(&["$numberLong"])?;
+            let s = string("$numberLong")?;
+            Value::Int64(s.parse().map_err(|_| format!("invalid $numberLong `{}`", s))?)
+        }
+        "$numberDouble" => {
+            expect_keys(&["$numberDouble"])?;
+            let s = string("$numberDouble")?;
+            Value::Double(match s {
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                "NaN" => f64::NAN,
+                _ => s.parse().map_err(|_| format!("invalid $numberDouble `{}`", s))?,
+            })
+        }
+        "$numberDecimal" => {
+            expect_keys(&["$numberDecimal"])?;
+            let s = string("$numberDecimal")?;
+            Value::Decimal128(s.parse::<Decimal128>().map_err(|e| e.to_string())?)
+        }
+        // A number `serde_json` kept as text (its `arbitrary_precision` feature)
+        JSON_NUMBER_TOKEN => {
+            expect_keys(&[JSON_NUMBER_TOKEN])?;
+            from_json_number(string(JSON_NUMBER_TOKEN)?)
+        }
+        "$binary" => binary(&doc)?,
+        "$code" if doc.len() == 1 => Value::JavaScriptCode(string("$code")?.to_string()),
+        "$code" => {
+            expect_keys(&["$code", "$scope"])?;
+            let scope = doc.get_document("$scope").map_err(|_| "`$scope` must be a document".to_string())?;
+            Value::JavaScriptCodeWithScope(string("$code")?.to_string(), Box::new(scope.clone()))
+        }
+        "$timestamp" => {
+            expect_keys(&["$timestamp"])?;
+            let inner = doc.get_document("$timestamp").map_err(|_| "`$timestamp` must be a document".to_string())?;
+            let t = u32_field(inner, "t")?;
+            let i = u32_field(inner, "i")?;
+            Value::TimeStamp(TimeStamp::new(t, i))
+        }
+        "$regularExpression" => {
+            expect_keys(&["$regularExpression"])?;
+            let inner = doc.get_document("$regularExpression")
+                .map_err(|_| "`$regularExpression` must be a document".to_string())?;
+            match (inner.get_str("pattern"), inner.get_str("options")) {
+                (Ok(pat), Ok(opt)) if inner.len() == 2 => Value::RegExp(pat.to_string(), opt.to_string()),
+                _ => return Err("`$regularExpression` needs string `pattern` and `options`".to_string()),
+            }
+        }
+        // Legacy form; `{"$regex": {...}}` is a query operator and left alone
+        "$regex" if matches!(doc.get("$regex"), Some(Value::String(_))) => {
+            expect_keys(&["$regex", "$options"])?;
+            Value::RegExp(string("$regex")?.to_string(), string("$options")?.to_string())
+        }
+        "$date" => {
+            expect_keys(&["$date"])?;
+            Value::UTCDatetime(date(doc.get("$date").unwrap())?)
+        }
+        "$minKey" => {
+            expect_keys(&["$minKey"])?;
+            match doc.get("$minKey") {
+                Some(Value::Int32(1)) => Value::MinKey,
+                _ => return Err("`$minKey` must be 1".to_string()),
+            }
+        }
+        "$maxKey" => {
+            expect_keys(&["$maxKey"])?;
+            match doc.get("$maxKey") {
+                Some(Value::Int32(1)) => Value::MaxKey,
+                _ => return Err("`$maxKey` must be 1".to_string()),
+            }
+        }
+        "$undefined" => {
+            expect_keys(&["$undefined"])?;
+            match doc.get("$undefined") {
+                Some(Value::Boolean(true)) => Value::Undefined,
+                _ => return Err("`$undefined` must be true".to_string()),
+            }
+        }
+        "$dbPointer" => return Err("`$dbPointer` is not supported".to_string()),
+        _ => Value::Document(doc),
+    };
+
+    Ok(value)
+}
+
+fn binary(doc: &Document) -> Result<Value, String> {
+    let subtype = |s: &str| -> Result<BinarySubtype, String> {
+        match Vec::<u8>::from_hex(s) {
+            Ok(ref b) if b.len() == 1 => Ok(BinarySubtype::from(b[0])),
+            _ => Err(format!("invalid binary subtype `{}`", s)),
+        }
+    };
+
+    let base64 = |s: &str| -> Result<Vec<u8>, String> {
+        base64::decode(s).ok_or_else(|| "invalid base64 in `$binary`".to_string())
+    };
+
+    match (doc.len(), doc.get("$binary")) {
+        // Canonical: {"$binary": {"base64": ..., "subType": ...}}
+        (1, Some(Value::Document(inner))) => {
+            match (inner.get_str("base64"), inner.get_str("subType")) {
+                (Ok(data), Ok(t)) if inner.len() == 2 => Ok(Value::Binary(subtype(t)?, base64(data)?)),
+                _ => Err("`$binary` needs string `base64` and `subType`".to_string()),
+            }
+        }
+        // Legacy: {"$binary": <base64>, "$type": <hex>}
+        (2, Some(Value::String(data))) if doc.contains_key("$type") => {
+            let t = doc.get_str("$type").map_err(|_| "`$type` must be a string".to_string())?;
+            Ok(Value::Binary(subtype(t)?, base64(data)?))
+        }
+        // As produced by `Value::to_json`: {"$binary": <hex>, "type": <int>}
+        (2, Some(Value::String(data))) if doc.contains_key("type") => {
+            let t = doc.get_i32("type").map_err(|_| "`type` must be an integer".to_string())?;
+            let bytes = Vec::<u8>::from_hex(data).map_err(|e| e.to_string())?;
+            Ok(Value::Binary(BinarySubtype::from(t as u8), bytes))
+        }
+        _ => Err("invalid `$binary` wrapper".to_string()),
+    }
+}
+
+fn date(value: &Value) -> Result<DateTime<Utc>, String> {
+    let millis = match *value {
+        // Canonical `{"$numberLong": ...}` has already been unwrapped
+        Value::Int64(v) => v,
+        Value::Int32(v) => i64::from(v),
+        Value::String(ref s) => {
+            return DateTime::parse_from_rfc3339(s)
+                .map(|d| d.with_timezone(&Utc))
+                .map_err(|_| format!("invalid date `{}`", s));
+        }
+        _ => return Err("`$date` must be a string or an integer".to_string()),
+    };
+
+    Ok(clamped_datetime(millis))
+}
+
+fn u32_field(doc: &Document, key: &str) -> Result<u32, String> {
+    let v = match doc.get(key) {
+        Some(Value::Int32(v)) => i64::from(*v),
+        Some(Value::Int64(v)) => *v,
+        _ => return Err(format!("`{}` must be an integer", key)),
+    };
+
+    if !(0..=i64::from(u32::MAX)).contains(&v) {
+        return Err(format!("`{}` out of range: {}", key, v));
+    }
+
+    Ok(v as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Utc, TimeZone};
+
+    use crate::{doc, Document, Value};
+    use crate::spec::BinarySubtype;
+    use crate::decode::DecodeError;
+    use crate::value::TimeStamp;
+
+    #[test]
+    fn canonical_and_relaxed() {
+        let doc = Document::from_extjson_str(r#"{
+            "z": {"$numberInt": "1"},
+            "a": 2,
+            "big": 3000000000,
+            "d": {"$numberDouble": "-Infinity"},
+            "date": {"$date": {"$numberLong": "1500000000000"}},
+            "relaxed": {"$date": "2017-07-14T02:40:00Z"},
+            "bin": {"$binary": {"base64": "AQID", "subType": "80"}},
+            "ts": {"$timestamp": {"t": 1, "i": 2}},
+            "re": {"$regularExpression": {"pattern": "^a", "options": "i"}},
+            "query": {"$gt": 5},
+            "nested": [{"$numberLong": "7"}, null]
+        }"#).unwrap();
+
+        let date = Utc.timestamp_millis_opt(1_500_000_000_000).unwrap();
+
+        assert_eq!(doc, doc!{
+            "z": 1,
+            "a": 2,
+            "big": 3_000_000_000i64,
+            "d": (f64::NEG_INFINITY),
+            "date": date,
+            "relaxed": date,
+            "bin": (BinarySubtype::UserDefined(0x80), vec![1, 2, 3]),
+            "ts": (Value::TimeStamp(TimeStamp::new(1, 2))),
+            "re": (Value::RegExp("^a".to_string(), "i".to_string())),
+            "query": {"$gt": 5},
+            "nested": [7i64, null]
+        });
+
+        assert_eq!(doc.keys().next().map(String::as_str), Some("z"));
+    }
+
+    #[test]
+    fn malformed() {
+        let invalid = |s: &str| match Document::from_extjson_str(s) {
+            Err(DecodeError::InvalidValue(_)) => true,
+            other => panic!("{:?}", other),
+        };
+
+        assert!(invalid(r#"{"a": {"$oid": "xyz"}}"#));
+        assert!(invalid(r#"{"a": {"$numberInt": "1", "extra": 1}}"#));
+        assert!(invalid(r#"{"a": {"$numberLong": 1}}"#));
+        assert!(invalid(r#"{"a": {"$binary": {"base64": "!!", "subType": "00"}}}"#));
+        assert!(invalid(r#"{"a": {"$timestamp": {"t": -1, "i": 0}}}"#));
+
+        assert!(matches!(Document::from_extjson_str("{"), Err(DecodeError::SyntaxError(_))));
+        assert!(matches!(Document::from_extjson_str("[1]"), Err(DecodeError::InvalidType(_))));
+    }
+}
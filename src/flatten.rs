@@ -0,0 +1,163 @@
+//! Converting nested documents to and from a single level
+//!
+//! Flattening joins the keys leading to each value with a separator, so
+//! `{"a": {"b": 1}}` becomes `{"a.b": 1}`. Arrays are flattened by index
+//! unless `ArrayHandling::Keep` is chosen. Empty documents and arrays are
+//! kept as values so that nothing is lost.
+//!
+//! ```
+//! use bsonrs::doc;
+//!
+//! let doc = doc!{"user": {"name": "a", "emails": ["x", "y"]}};
+//! let flat = doc.flatten();
+//!
+//! assert_eq!(flat, doc!{"user.name": "a", "user.emails.0": "x", "user.emails.1": "y"});
+//! assert_eq!(flat.unflatten().unwrap(), doc);
+//! ```
+//!
+//! Keys that contain the separator can't be told apart from nesting, so
+//! unflattening such a document does not give back the original.
+use indexmap::IndexMap;
+
+use crate::value::{Value, Array};
+use crate::doc::{Document, Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlattenOptions {
+    /// Placed between the keys of each level. Defaults to `.`.
+    pub separator: char,
+    pub arrays: ArrayHandling,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> FlattenOptions {
+        FlattenOptions {
+            separator: '.',
+            arrays: ArrayHandling::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayHandling {
+    /// Flatten arrays like documents, keyed by index. When unflattening,
+    /// a level whose keys are exactly `0`, `1`, ... in order becomes an
+    /// array.
+    #[default]
+    Index,
+    /// Keep arrays as values. Unflattening never creates arrays.
+    Keep,
+}
+
+pub fn flatten(doc: &Document, options: FlattenOptions) -> Document {
+    let mut flat = Document::new();
+
+    for (key, value) in doc {
+        flatten_value(key.clone(), value, options, &mut flat);
+    }
+
+    flat
+}
+
+fn flatten_value(key: String, value: &Value, options: FlattenOptions, flat: &mut Document) {
+    match value {
+        Value::Document(doc) if !doc.is_empty() => {
+            for (k, v) in doc {
+                flatten_value(format!("{}{}{}", key, options.separator, k), v, options, flat);
+            }
+        }
+        Value::Array(array) if !array.is_empty() && options.arrays == ArrayHandling::Index => {
+            for (i, v) in array.iter().enumerate() {
+                flatten_value(format!("{}{}{}", key, options.separator, i), v, options, flat);
+            }
+        }
+        _ => {
+            flat.insert_value(key, value.clone());
+        }
+    }
+}
+
+enum Node {
+    Leaf(Value),
+    Branch(IndexMap<String, Node>),
+}
+
+/// Rebuild nested documents from separated keys. Fails with
+/// `UnexpectedType` if one key is a prefix of another, as with `a` and
+/// `a.b`.
+pub fn unflatten(doc: &Document, options: FlattenOptions) -> Result<Document> {
+    let mut root = IndexMap::new();
+
+    for (key, value) in doc {
+        let mut segments = key.split(options.separator);
+        let last = segments.next_back().unwrap_or_default();
+        let mut level = &mut root;
+
+        for segment in segments {
+            let node = level.entry(segment.to_string()).or_insert_with(|| Node::Branch(IndexMap::new()));
+
+            level = match node {
+                Node::Branch(children) => children,
+                Node::Leaf(_) => return Err(Error::UnexpectedType),
+            };
+        }
+
+        if level.insert(last.to_string(), Node::Leaf(value.clone())).is_some() {
+            return Err(Error::UnexpectedType);
+        }
+    }
+
+    Ok(build_document(root, options))
+}
+
+fn build_document(children: IndexMap<String, Node>, options: FlattenOptions) -> Document {
+    children.into_iter().map(|(k, node)| (k, build_value(node, options))).collect()
+}
+
+fn build_value(node: Node, options: FlattenOptions) -> Value {
+    match node {
+        Node::Leaf(value) => value,
+        Node::Branch(children) => {
+            let indexed = options.arrays == ArrayHandling::Index
+                && children.keys().enumerate().all(|(i, k)| *k == i.to_string());
+
+            if indexed {
+                Value::Array(children.into_iter().map(|(_, node)| build_value(node, options)).collect::<Array>())
+            } else {
+                Value::Document(build_document(children, options))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FlattenOptions, ArrayHandling};
+    use crate::{doc, Array};
+    use crate::doc::Error;
+
+    #[test]
+    fn round_trip() {
+        let doc = doc!{"a": {"b": [1, {"c": 2}], "d": {}}, "e": (Array::new()), "f": 3};
+
+        let flat = doc.flatten();
+        assert_eq!(flat, doc!{"a.b.0": 1, "a.b.1.c": 2, "a.d": {}, "e": (Array::new()), "f": 3});
+        assert_eq!(flat.unflatten().unwrap(), doc);
+
+        let options = FlattenOptions { separator: '/', arrays: ArrayHandling::Keep };
+        let flat = doc.flatten_with_options(options);
+        assert_eq!(flat, doc!{"a/b": [1, {"c": 2}], "a/d": {}, "e": (Array::new()), "f": 3});
+        assert_eq!(flat.unflatten_with_options(options).unwrap(), doc);
+
+        // Without array handling, index keys stay a document
+        let flat = doc!{"a.0": 1};
+        assert_eq!(flat.unflatten_with_options(FlattenOptions { arrays: ArrayHandling::Keep, ..Default::default() }).unwrap(), doc!{"a": {"0": 1}});
+        assert_eq!(doc!{"a.1": 1}.unflatten().unwrap(), doc!{"a": {"1": 1}});
+    }
+
+    #[test]
+    fn conflicts() {
+        assert_eq!(doc!{"a": 1, "a.b": 2}.unflatten(), Err(Error::UnexpectedType));
+        assert_eq!(doc!{"a.b": 2, "a": 1}.unflatten(), Err(Error::UnexpectedType));
+    }
+}
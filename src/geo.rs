@@ -0,0 +1,275 @@
+//! GeoJSON shaped documents
+//!
+//! Location data is conventionally stored as GeoJSON objects such as
+//! `{"type": "Point", "coordinates": [lng, lat]}`. `Geometry` builds and
+//! validates these documents and parses them back into coordinates.
+//!
+//! ```
+//! use bsonrs::doc;
+//! use bsonrs::geo::Geometry;
+//!
+//! let point = Geometry::point(13.4, 52.5).unwrap();
+//! let doc = doc!{"location": point.clone()};
+//!
+//! assert_eq!(Geometry::from_value(doc.get("location").unwrap()).unwrap(), point);
+//! ```
+use std::{fmt, error, result};
+
+use crate::value::{Value, Array};
+use crate::doc::Document;
+use crate::doc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub lng: f64,
+    pub lat: f64,
+}
+
+impl Position {
+    pub fn new(lng: f64, lat: f64) -> Result<Position> {
+        let position = Position { lng, lat };
+        position.validate()?;
+        Ok(position)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(-180.0..=180.0).contains(&self.lng) || !(-90.0..=90.0).contains(&self.lat) {
+            return Err(Error::InvalidPosition(self.lng, self.lat));
+        }
+
+        Ok(())
+    }
+
+    fn to_value(self) -> Value {
+        Value::Array(Array::from_vec(vec![Value::Double(self.lng), Value::Double(self.lat)]))
+    }
+
+    fn from_value(value: &Value) -> Result<Position> {
+        let arr = value.as_array().ok_or(Error::Malformed("position must be an array"))?;
+
+        // A third element (altitude) is allowed by GeoJSON and ignored here
+        if arr.len() < 2 || arr.len() > 3 {
+            return Err(Error::Malformed("position must have two or three elements"));
+        }
+
+        Position::new(number(&arr[0])?, number(&arr[1])?)
+    }
+}
+
+fn number(value: &Value) -> Result<f64> {
+    match *value {
+        Value::Double(v) => Ok(v),
+        Value::Int32(v) => Ok(f64::from(v)),
+        Value::Int64(v) => Ok(v as f64),
+        _ => Err(Error::Malformed("coordinate must be a number"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    Point(Position),
+    LineString(Vec<Position>),
+    /// An outer ring followed by any holes. Every ring is closed, i.e. its
+    /// first and last positions are equal.
+    Polygon(Vec<Vec<Position>>),
+}
+
+impl Geometry {
+    pub fn point(lng: f64, lat: f64) -> Result<Geometry> {
+        Ok(Geometry::Point(Position::new(lng, lat)?))
+    }
+
+    pub fn line_string(positions: Vec<Position>) -> Result<Geometry> {
+        let geometry = Geometry::LineString(positions);
+        geometry.validate()?;
+        Ok(geometry)
+    }
+
+    pub fn polygon(rings: Vec<Vec<Position>>) -> Result<Geometry> {
+        let geometry = Geometry::Polygon(rings);
+        geometry.validate()?;
+        Ok(geometry)
+    }
+
+    /// The GeoJSON `type` name.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Geometry::Point(..) => "Point",
+            Geometry::LineString(..) => "LineString",
+            Geometry::Polygon(..) => "Polygon",
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Geometry::Point(p) => p.validate(),
+            Geometry::LineString(positions) => {
+                if positions.len() < 2 {
+                    return Err(Error::TooFewPositions(positions.len()));
+                }
+
+                positions.iter().try_for_each(Position::validate)
+            }
+            Geometry::Polygon(rings) => {
+                if rings.is_empty() {
+                    return Err(Error::TooFewPositions(0));
+                }
+
+                for ring in rings {
+                    if ring.len() < 4 {
+                        return Err(Error::TooFewPositions(ring.len()));
+                    }
+
+                    if ring.first() != ring.last() {
+                        return Err(Error::UnclosedRing);
+                    }
+
+                    ring.iter().try_for_each(Position::validate)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    pub fn to_document(&self) -> Document {
+        let coordinates = match self {
+            Geometry::Point(p) => p.to_value(),
+            Geometry::LineString(positions) => positions_to_value(positions),
+            Geometry::Polygon(rings) => {
+                Value::Array(rings.iter().map(|r| positions_to_value(r)).collect())
+            }
+        };
+
+        doc!{
+            "type": self.type_name(),
+            "coordinates": coordinates
+        }
+    }
+
+    /// Wrap the geometry as `{"$geometry": ...}`, the form taken by geospatial
+    /// query operators.
+    pub fn to_query(&self) -> Document {
+        doc!{"$geometry": self.to_document()}
+    }
+
+    /// Parse and validate a GeoJSON document. A `{"$geometry": ...}` wrapper
+    /// is accepted as well.
+    pub fn from_document(doc: &Document) -> Result<Geometry> {
+        if let Ok(inner) = doc.get_document("$geometry") {
+            return Geometry::from_document(inner);
+        }
+
+        let coordinates = doc.get("coordinates").ok_or(Error::Malformed("missing coordinates"))?;
+
+        let geometry = match doc.get_str("type") {
+            Ok("Point") => Geometry::Point(Position::from_value(coordinates)?),
+            Ok("LineString") => Geometry::LineString(positions_from_value(coordinates)?),
+            Ok("Polygon") => {
+                let rings = coordinates.as_array().ok_or(Error::Malformed("rings must be an array"))?;
+                Geometry::Polygon(rings.iter().map(positions_from_value).collect::<Result<_>>()?)
+            }
+            Ok(other) => return Err(Error::UnsupportedType(other.to_string())),
+            Err(_) => return Err(Error::Malformed("missing type")),
+        };
+
+        geometry.validate()?;
+        Ok(geometry)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Geometry> {
+        match value {
+            Value::Document(doc) => Geometry::from_document(doc),
+            _ => Err(Error::Malformed("geometry must be a document"))
+        }
+    }
+}
+
+fn positions_to_value(positions: &[Position]) -> Value {
+    Value::Array(positions.iter().map(|p| p.to_value()).collect())
+}
+
+fn positions_from_value(value: &Value) -> Result<Vec<Position>> {
+    let arr = value.as_array().ok_or(Error::Malformed("positions must be an array"))?;
+    arr.iter().map(Position::from_value).collect()
+}
+
+impl From<Geometry> for Value {
+    fn from(g: Geometry) -> Value {
+        Value::Document(g.to_document())
+    }
+}
+
+impl From<Geometry> for Document {
+    fn from(g: Geometry) -> Document {
+        g.to_document()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    InvalidPosition(f64, f64),
+    TooFewPositions(usize),
+    UnclosedRing,
+    UnsupportedType(String),
+    Malformed(&'static str),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidPosition(lng, lat) => write!(fmt, "invalid position [{}, {}]", lng, lat),
+            Error::TooFewPositions(n) => write!(fmt, "too few positions: {}", n),
+            Error::UnclosedRing => write!(fmt, "polygon ring is not closed"),
+            Error::UnsupportedType(ref t) => write!(fmt, "unsupported geometry type `{}`", t),
+            Error::Malformed(desc) => write!(fmt, "malformed geometry: {}", desc),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(test)]
+mod test {
+    use super::{Geometry, Position, Error};
+    use crate::doc;
+
+    #[test]
+    fn polygon() {
+        let ring = vec![
+            Position::new(0.0, 0.0).unwrap(),
+            Position::new(1.0, 0.0).unwrap(),
+            Position::new(1.0, 1.0).unwrap(),
+            Position::new(0.0, 0.0).unwrap(),
+        ];
+
+        let polygon = Geometry::polygon(vec![ring.clone()]).unwrap();
+        let doc = polygon.to_document();
+
+        assert_eq!(doc, doc!{
+            "type": "Polygon",
+            "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]]
+        });
+        assert_eq!(Geometry::from_document(&polygon.to_query()), Ok(polygon));
+
+        assert_eq!(Geometry::polygon(vec![ring[..3].to_vec()]), Err(Error::TooFewPositions(3)));
+        assert_eq!(Geometry::polygon(vec![vec![ring[0], ring[1], ring[2], ring[1]]]), Err(Error::UnclosedRing));
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(Geometry::point(181.0, 0.0), Err(Error::InvalidPosition(181.0, 0.0)));
+        assert_eq!(Geometry::line_string(vec![Position::new(0.0, 0.0).unwrap()]), Err(Error::TooFewPositions(1)));
+
+        let point = doc!{"type": "Point", "coordinates": [10, 20]};
+        assert_eq!(Geometry::from_document(&point), Geometry::point(10.0, 20.0));
+
+        let bad = doc!{"type": "Point", "coordinates": [10, 100]};
+        assert_eq!(Geometry::from_document(&bad), Err(Error::InvalidPosition(10.0, 100.0)));
+
+        let circle = doc!{"type": "Circle", "coordinates": [0, 0]};
+        assert_eq!(Geometry::from_document(&circle), Err(Error::UnsupportedType("Circle".to_string())));
+    }
+}
@@ -1,6 +1,14 @@
 pub use value::{Value, Array};
 pub use doc::Document;
 pub use object_id::ObjectId;
+pub use spec::{ElementType, BinarySubtype};
+pub use convert::{IntoValue, FromValue};
+
+#[cfg(feature = "derive")]
+pub use bsonrs_derive::{IntoValue, FromValue};
+
+// Lets the derive macros refer to `::bsonrs` from inside this crate too.
+extern crate self as bsonrs;
 
 mod macros;
 pub mod value;
@@ -11,6 +19,25 @@ pub mod serde_impl;
 mod spec;
 mod util;
 pub mod object_id;
+pub mod decimal128;
+pub mod ext;
+pub mod convert;
+pub mod log;
+pub mod path;
+pub mod diff;
+pub mod patch;
+pub mod flatten;
+pub mod normalize;
+pub mod sanitize;
+pub mod projection;
+pub mod serde_helpers;
+pub mod geo;
+pub mod raw;
+pub mod stream;
+#[cfg(feature = "json")]
+pub mod extjson;
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl;
 
 #[cfg(test)]
 mod test {
@@ -58,6 +85,22 @@ mod test {
 		assert_eq!(foo, foo2);
 	}
 
+	#[test]
+	fn to_and_from_document() {
+		use crate::encode::to_document;
+		use crate::decode::{from_document, DecodeError};
+
+		let bar = Bar { b: 1, c: 2.5, d: "x".to_string() };
+
+		let doc = to_document(&bar).unwrap();
+		assert_eq!(doc, doc!{"b": 1i64, "c": 2.5, "d": "x"});
+		assert_eq!(from_document::<Bar>(doc).unwrap(), bar);
+
+		assert!(to_document(&vec![1, 2]).is_err());
+		assert!(matches!(from_document::<Bar>(doc!{"b": 1i64}), Err(DecodeError::ExpectedField(_))));
+	}
+
+	#[cfg(feature = "json")]
 	#[test]
 	fn into_and_from_json() {
 		let foo = Bar {
@@ -88,4 +131,170 @@ mod test {
 
 		assert_eq!(doc, doc2);
 	}
+
+	#[test]
+	fn decimal128() {
+		use crate::decimal128::Decimal128;
+
+		#[derive(Serialize, Deserialize, Debug, PartialEq)]
+		struct Price {
+			amount: Decimal128
+		}
+
+		let price = Price { amount: "19.99".parse().unwrap() };
+		let doc = doc!{"amount": price.amount};
+
+		assert_eq!(to_bson(&price).unwrap(), Value::Document(doc.clone()));
+		assert_eq!(from_bson::<Price>(Value::Document(doc.clone())).unwrap(), price);
+
+		let bytes = doc.to_vec().unwrap();
+		assert_eq!(bytes[4], 0x13);
+		assert_eq!(crate::Document::from_slice(&bytes).unwrap(), doc);
+
+		#[cfg(feature = "json")]
+		{
+			let json = Value::Document(doc.clone()).to_json();
+			assert_eq!(json, serde_json::json!({"amount": {"$numberDecimal": "19.99"}}));
+			assert_eq!(Value::from_json(json), Value::Document(doc));
+		}
+	}
+
+	#[test]
+	fn binary_subtype() {
+		use crate::spec::BinarySubtype;
+		use crate::value::Binary;
+		use crate::decode::from_slice_borrowed;
+
+		#[derive(Serialize, Deserialize, Debug, PartialEq)]
+		struct Blobs {
+			hash: Binary,
+			custom: Binary,
+			plain: Binary,
+		}
+
+		let blobs = Blobs {
+			hash: Binary { subtype: BinarySubtype::Md5, bytes: vec![1; 16] },
+			custom: Binary { subtype: BinarySubtype::UserDefined(0x80), bytes: vec![2, 3] },
+			plain: Binary { subtype: BinarySubtype::Generic, bytes: vec![] },
+		};
+
+		let doc = doc!{
+			"hash": (BinarySubtype::Md5, vec![1; 16]),
+			"custom": (BinarySubtype::UserDefined(0x80), vec![2, 3]),
+			"plain": (BinarySubtype::Generic, vec![])
+		};
+
+		assert_eq!(to_bson(&blobs).unwrap(), Value::Document(doc.clone()));
+		assert_eq!(from_bson::<Blobs>(Value::Document(doc.clone())).unwrap(), blobs);
+
+		let bytes = crate::encode::to_vec(&blobs).unwrap();
+		assert_eq!(bytes, doc.to_vec().unwrap());
+		assert_eq!(from_slice_borrowed::<Blobs>(&bytes).unwrap(), blobs);
+
+		assert!(from_bson::<Binary>(Value::from("not binary")).is_err());
+	}
+
+	#[test]
+	fn chrono_datetime() {
+		use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+		use crate::value::UTCDateTime;
+		use crate::decode::from_slice_borrowed;
+
+		#[derive(Deserialize, Debug, PartialEq)]
+		struct Event {
+			at: DateTime<Utc>,
+			local: DateTime<FixedOffset>,
+			maybe: Option<DateTime<Utc>>,
+			wrapped: UTCDateTime,
+		}
+
+		let at = Utc.timestamp_millis_opt(1_500_000_000_123).unwrap();
+		let doc = doc!{"at": at, "local": at, "maybe": at, "wrapped": at};
+		let event = Event { at, local: at.into(), maybe: Some(at), wrapped: UTCDateTime(at) };
+
+		assert_eq!(from_bson::<Event>(Value::Document(doc.clone())).unwrap(), event);
+		assert_eq!(from_slice_borrowed::<Event>(&doc.to_vec().unwrap()).unwrap(), event);
+
+		// Strings holding a datetime still work for chrono fields
+		let doc = doc!{"at": (at.to_rfc3339()), "local": at, "maybe": null, "wrapped": at};
+		assert_eq!(from_bson::<Event>(Value::Document(doc)).unwrap(), Event { maybe: None, ..event });
+	}
+
+	#[cfg(feature = "subtle")]
+	#[test]
+	fn constant_time_eq() {
+		use crate::spec::BinarySubtype;
+
+		let token = Value::Binary(BinarySubtype::Sensitive, vec![1, 2, 3, 4]);
+
+		assert!(token.ct_eq(&Value::Binary(BinarySubtype::Sensitive, vec![1, 2, 3, 4])));
+		assert!(!token.ct_eq(&Value::Binary(BinarySubtype::Sensitive, vec![1, 2, 3, 5])));
+		assert!(!token.ct_eq(&Value::Binary(BinarySubtype::Generic, vec![1, 2, 3, 4])));
+		assert!(!token.ct_eq(&Value::Binary(BinarySubtype::Sensitive, vec![1, 2, 3])));
+		assert!(Value::from("secret").ct_eq(&Value::from("secret")));
+		assert!(!Value::from("secret").ct_eq(&Value::from("secreT")));
+	}
+
+	#[test]
+	fn value_macros() {
+		use crate::{bson, array, Array};
+
+		assert_eq!(bson!(null), Value::Null);
+		assert_eq!(bson!(5), Value::Int32(5));
+		assert_eq!(bson!("a"), Value::from("a"));
+		assert_eq!(bson!([]), Value::Array(Array::new()));
+		assert_eq!(bson!({}), Value::Document(doc!{}));
+
+		let value = bson!({"a": [1, 2, {"b": null}]});
+		assert_eq!(value, Value::Document(doc!{"a": [1, 2, {"b": null}]}));
+
+		assert_eq!(array![], Array::new());
+		assert_eq!(array![1, "x", [], {"b": null}], Array::from(vec![
+			Value::Int32(1),
+			Value::from("x"),
+			Value::Array(Array::new()),
+			Value::Document(doc!{"b": null}),
+		]));
+	}
+
+	#[test]
+	fn doc_macro_syntax() {
+		let key = "k";
+		let owned = String::from("o");
+		let n = 2;
+		let v = [1, 2];
+
+		let document = doc!{
+			key: 1,
+			&owned: n + 1,
+			format!("f{}", n): [n, n * 2, {"x": v.len() as i64},],
+			"nested": {"a": [1, 2,], "b": {"c": -1,},},
+			"m": v.iter().map(|x| x + 1).sum::<i32>(),
+			"t": if n > 1 { "big" } else { "small" },
+			(v.len().to_string()): null,
+			std::f64::consts::PI.to_string(): [],
+		};
+
+		let mut expected = crate::Document::new();
+		expected.insert("k", 1);
+		expected.insert("o", 3);
+		expected.insert("f2", crate::Array::from(vec![Value::Int32(2), Value::Int32(4), Value::Document(doc!{"x": 2i64})]));
+		expected.insert("nested", doc!{"a": [1, 2], "b": {"c": -1}});
+		expected.insert("m", 5);
+		expected.insert("t", "big");
+		expected.insert_value("2".to_string(), Value::Null);
+		expected.insert("3.141592653589793", crate::Array::new());
+
+		assert_eq!(document, expected);
+	}
+
+	#[test]
+	fn doc_macro_optional() {
+		let missing: Option<i32> = None;
+		let name = Some("x");
+
+		let document = doc!{"a" ?: missing, "name" ?: name, "b": {"c" ?: Some(1)}, "d" ?: missing};
+
+		assert_eq!(document, doc!{"name": "x", "b": {"c": 1}});
+	}
 }
@@ -0,0 +1,315 @@
+//! Append-only BSON log files
+//!
+//! A log is a file of back-to-back BSON documents, each one carrying its own
+//! length prefix. `BsonLogWriter` appends documents with a configurable fsync
+//! policy and rotates the file once it grows past a size limit;
+//! `BsonLogReader` reads complete records and stops quietly at a torn final
+//! record, so it can tail a log that is still being written.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::doc::Document;
+use crate::encode::EncodeResult;
+use crate::decode::DecodeResult;
+use crate::stream::frame_len;
+
+/// When `BsonLogWriter` calls `fsync` on the log file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Leave it to the operating system.
+    Never,
+    /// After every appended document.
+    Always,
+    /// After every `n` appended documents.
+    EveryN(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogOptions {
+    pub sync: SyncPolicy,
+    /// Rotate the file before an append would make it larger than this many
+    /// bytes. A single document larger than the limit still gets its own file.
+    pub max_file_size: Option<u64>,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            sync: SyncPolicy::Never,
+            max_file_size: None,
+        }
+    }
+}
+
+pub struct BsonLogWriter {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    unsynced: usize,
+    options: LogOptions,
+}
+
+impl BsonLogWriter {
+    /// Open `path` for appending, creating it if needed. Rotated files are
+    /// renamed to `path.1`, `path.2`, … in the order they were closed.
+    pub fn open(path: impl AsRef<Path>, options: LogOptions) -> io::Result<BsonLogWriter> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(BsonLogWriter {
+            path,
+            file,
+            size,
+            unsynced: 0,
+            options,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size in bytes of the active file.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn append(&mut self, doc: &Document) -> EncodeResult<()> {
+        let buf = doc.to_vec()?;
+
+        if let Some(max) = self.options.max_file_size {
+            if self.size > 0 && self.size + buf.len() as u64 > max {
+                self.rotate()?;
+            }
+        }
+
+        self.file.write_all(&buf)?;
+        self.size += buf.len() as u64;
+        self.unsynced += 1;
+
+        match self.options.sync {
+            SyncPolicy::Always => self.sync()?,
+            SyncPolicy::EveryN(n) if self.unsynced >= n => self.sync()?,
+            _ => ()
+        }
+
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Close the active file, move it aside and start a new one.
+    pub fn rotate(&mut self) -> io::Result<PathBuf> {
+        self.sync()?;
+
+        let rotated = self.next_rotated_path();
+        fs::rename(&self.path, &rotated)?;
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+
+        Ok(rotated)
+    }
+
+    /// Rotated files, oldest first.
+    pub fn rotated_files(&self) -> Vec<PathBuf> {
+        (1..).map(|n| self.rotated_path(n)).take_while(|p| p.exists()).collect()
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn next_rotated_path(&self) -> PathBuf {
+        (1..).map(|n| self.rotated_path(n)).find(|p| !p.exists()).unwrap()
+    }
+}
+
+impl Drop for BsonLogWriter {
+    fn drop(&mut self) {
+        if self.unsynced > 0 && self.options.sync != SyncPolicy::Never {
+            let _ = self.file.sync_data();
+        }
+    }
+}
+
+pub struct BsonLogReader {
+    file: File,
+    position: u64,
+    // Set once iteration hits a record that can't be read
+    failed: bool,
+}
+
+impl BsonLogReader {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<BsonLogReader> {
+        Ok(BsonLogReader {
+            file: File::open(path)?,
+            position: 0,
+            failed: false,
+        })
+    }
+
+    /// Offset of the first byte after the last complete record read.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Read the next complete record. Returns `Ok(None)` at the end of the
+    /// file or when the last record is only partially written; calling again
+    /// later picks up from the same place once more data has been appended.
+    pub fn read_next(&mut self) -> DecodeResult<Option<Document>> {
+        self.file.seek(SeekFrom::Start(self.position))?;
+
+        let mut len_bytes = [0u8; 4];
+        if read_full(&mut self.file, &mut len_bytes)? < 4 {
+            return Ok(None);
+        }
+
+        // Checked before allocating, so a corrupt prefix can't ask for
+        // gigabytes
+        let len = frame_len(len_bytes)?;
+
+        let mut buf = vec![0u8; len];
+        buf[..4].copy_from_slice(&len_bytes);
+
+        if read_full(&mut self.file, &mut buf[4..])? < buf.len() - 4 {
+            return Ok(None);
+        }
+
+        let doc = Document::from_slice(&buf)?;
+        self.position += buf.len() as u64;
+
+        Ok(Some(doc))
+    }
+}
+
+impl Iterator for BsonLogReader {
+    type Item = DecodeResult<Document>;
+
+    /// Ends after the first error, since a record that can't be read would
+    /// otherwise be returned again forever. `read_next` can still be used
+    /// to retry it.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let next = self.read_next();
+        self.failed = next.is_err();
+
+        next.transpose()
+    }
+}
+
+/// Like `read_exact`, but reports how much was read instead of failing at EOF.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(read)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use super::{BsonLogWriter, BsonLogReader, LogOptions, SyncPolicy};
+    use crate::doc;
+    use crate::decode::DecodeError;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bsonrs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn torn_tail() {
+        let dir = temp_dir("torn");
+        let path = dir.join("log");
+
+        let mut writer = BsonLogWriter::open(&path, LogOptions { sync: SyncPolicy::Always, ..Default::default() }).unwrap();
+        writer.append(&doc!{"a": 1}).unwrap();
+        writer.append(&doc!{"a": 2}).unwrap();
+
+        let partial = doc!{"a": 3}.to_vec().unwrap();
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&partial[..7]).unwrap();
+
+        let mut reader = BsonLogReader::open(&path).unwrap();
+        let docs: Vec<_> = reader.by_ref().map(|d| d.unwrap()).collect();
+        assert_eq!(docs, vec![doc!{"a": 1}, doc!{"a": 2}]);
+
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&partial[7..]).unwrap();
+        assert_eq!(reader.read_next().unwrap(), Some(doc!{"a": 3}));
+        assert_eq!(reader.read_next().unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("log");
+        let record = doc!{"a": 1}.to_vec().unwrap().len() as u64;
+
+        let mut writer = BsonLogWriter::open(&path, LogOptions { max_file_size: Some(record * 2), ..Default::default() }).unwrap();
+        for i in 0..5 {
+            writer.append(&doc!{"a": i}).unwrap();
+        }
+
+        let rotated = writer.rotated_files();
+        assert_eq!(rotated.len(), 2);
+        assert_eq!(BsonLogReader::open(&rotated[0]).unwrap().count(), 2);
+        assert_eq!(BsonLogReader::open(&rotated[1]).unwrap().count(), 2);
+        assert_eq!(BsonLogReader::open(&path).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_record() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join("log");
+
+        let mut writer = BsonLogWriter::open(&path, LogOptions::default()).unwrap();
+        writer.append(&doc!{"a": 1}).unwrap();
+
+        // An unknown element type
+        let mut bad = doc!{"a": 2}.to_vec().unwrap();
+        bad[4] = 0x20;
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&bad).unwrap();
+
+        let results: Vec<_> = BsonLogReader::open(&path).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), &doc!{"a": 1});
+        assert!(results[1].is_err());
+
+        // A length prefix past the size limit fails without allocating it
+        fs::write(&path, i32::MAX.to_le_bytes()).unwrap();
+        let mut reader = BsonLogReader::open(&path).unwrap();
+        assert!(matches!(reader.read_next(), Err(DecodeError::InvalidLength(..))));
+        assert!(reader.next().is_some());
+        assert!(reader.next().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
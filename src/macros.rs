@@ -1,6 +1,7 @@
 // bson macro based on the serde_json json! implementation.
 
-/// Construct a value::Value value from a literal.
+/// Construct a value::Value value from a literal. Anything that isn't a
+/// document, array or `null` literal is converted with `Value::from`.
 ///
 /// ```rust
 /// # #[macro_use]
@@ -172,6 +173,21 @@ macro_rules! bson {
         unimplemented!($comma);
     };
 
+    // Optional value followed by comma, inserted only if it is `Some`.
+    (@object $object:ident ($($key:tt)+) (? : $value:expr , $($rest:tt)*) $copy:tt) => {
+        if let Some(value) = $value {
+            $object.insert_value(($($key)+).into(), $crate::value::Value::from(value));
+        }
+        $crate::bson!(@object $object () ($($rest)*) ($($rest)*));
+    };
+
+    // Last value is optional, with no trailing comma.
+    (@object $object:ident ($($key:tt)+) (? : $value:expr) $copy:tt) => {
+        if let Some(value) = $value {
+            $object.insert_value(($($key)+).into(), $crate::value::Value::from(value));
+        }
+    };
+
     // Key is fully parenthesized. This avoids clippy double_parens false
     // positives because the parenthesization may be necessary here.
     (@object $object:ident () (($key:expr) => $($rest:tt)*) $copy:tt) => {
@@ -198,7 +214,7 @@ macro_rules! bson {
     };
 
     ([]) => {
-        $crate::value::Value::Array(vec![].into())
+        $crate::value::Value::Array($crate::value::Array::new())
     };
 
     ([ $($tt:tt)+ ]) => {
@@ -210,13 +226,35 @@ macro_rules! bson {
     };
 
     ({$($tt:tt)+}) => {
-        $crate::value::Value::Document($crate::doc!{$($tt)+});
+        $crate::value::Value::Document($crate::doc!{$($tt)+})
     };
 
     // Any Serialize type: numbers, strings, struct literals, variables etc.
     // Must be below every other rule.
     ($other:expr) => {
-        std::convert::From::from($other)
+        $crate::value::Value::from($other)
+    };
+}
+
+/// Construct a value::Array from a literal, with the same element syntax as
+/// `bson!`.
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate bsonrs;
+/// #
+/// # fn main() {
+/// let value = array![1, "two", [3.0], {"four": null}];
+/// assert_eq!(value.len(), 4);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! array {
+    () => {
+        $crate::value::Array::new()
+    };
+    ( $($tt:tt)+ ) => {
+        $crate::bson!(@array [] $($tt)+)
     };
 }
 
@@ -239,6 +277,38 @@ macro_rules! bson {
 /// };
 /// # }
 /// ```
+///
+/// Keys may be any expression convertible into a `String`, and values any
+/// expression convertible into a `Value`, at any depth. Trailing commas are
+/// allowed everywhere. A key that itself contains `:` or `=>` must be put in
+/// parentheses.
+///
+/// A field written `key ?: value` takes an `Option` and is left out when
+/// it is `None`, instead of being stored as `null`.
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate bsonrs;
+/// #
+/// # fn main() {
+/// let field = "name";
+/// let tags = vec!["a", "b"];
+///
+/// let value = doc! {
+///     field: "x",
+///     format!("{}_len", field): 1,
+///     "tags": [tags[0], tags[1], { "count": tags.len() as i64 },],
+///     (std::i32::MAX.to_string()): null,
+///     "nickname" ?: None::<&str>,
+///     "age" ?: Some(30),
+/// };
+///
+/// assert_eq!(value.get_str("name"), Ok("x"));
+/// assert_eq!(value.get_i32("name_len"), Ok(1));
+/// assert!(!value.contains_key("nickname"));
+/// assert_eq!(value.get_i32("age"), Ok(30));
+/// # }
+/// ```
 #[macro_export]
 macro_rules! doc {
     () => {{ $crate::doc::Document::with_capacity(8) }};
@@ -0,0 +1,112 @@
+//! Bringing documents to a canonical shape
+//!
+//! Two documents holding the same data can still differ in key order or in
+//! which integer type a number was stored as. Normalizing sorts the keys of
+//! every nested document and converts numbers according to a
+//! `NumberPolicy`, so the results can be compared, hashed or used as cache
+//! keys. Arrays keep their order.
+//!
+//! ```
+//! use bsonrs::doc;
+//!
+//! let a = doc!{"b": 1, "a": {"d": [2_i64], "c": 3_i64}};
+//! let b = doc!{"a": {"c": 3, "d": [2]}, "b": 1_i64};
+//!
+//! assert_ne!(a, b);
+//! assert_eq!(a.normalize(), b.normalize());
+//! ```
+use crate::value::Value;
+use crate::doc::Document;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NormalizeOptions {
+    pub numbers: NumberPolicy,
+    /// Remove fields whose value is an empty document, including ones that
+    /// only became empty through this. Documents inside arrays are kept so
+    /// that positions don't shift. Defaults to `false`.
+    pub strip_empty_documents: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberPolicy {
+    /// Leave numeric types as they are.
+    Keep,
+    /// Store every `Int32` as an `Int64`.
+    #[default]
+    Int64,
+    /// Store `Int32` and `Int64` as `Double`, like JSON numbers. An `Int64`
+    /// a double can't hold exactly stays an `Int64`.
+    Double,
+}
+
+pub fn normalize(doc: &Document, options: NormalizeOptions) -> Document {
+    let mut doc = doc.clone();
+    normalize_document(&mut doc, options);
+    doc
+}
+
+fn normalize_document(doc: &mut Document, options: NormalizeOptions) {
+    for (_, value) in doc.iter_mut() {
+        normalize_value(value, options);
+    }
+
+    if options.strip_empty_documents {
+        doc.retain(|_, value| !matches!(value, Value::Document(inner) if inner.is_empty()));
+    }
+
+    doc.sort_keys();
+}
+
+fn normalize_value(value: &mut Value, options: NormalizeOptions) {
+    match value {
+        Value::Document(doc) => normalize_document(doc, options),
+        Value::Array(array) => {
+            for item in array.iter_mut() {
+                normalize_value(item, options);
+            }
+        }
+        Value::JavaScriptCodeWithScope(_, scope) => normalize_document(scope, options),
+        Value::Int32(i) => match options.numbers {
+            NumberPolicy::Keep => (),
+            NumberPolicy::Int64 => *value = Value::Int64(i64::from(*i)),
+            NumberPolicy::Double => *value = Value::Double(f64::from(*i)),
+        },
+        Value::Int64(i) if options.numbers == NumberPolicy::Double => {
+            let f = *i as f64;
+
+            // 2^63 rounds up out of range, so check it before casting back
+            if f < 9_223_372_036_854_775_808.0 && f as i64 == *i {
+                *value = Value::Double(f);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NormalizeOptions, NumberPolicy};
+    use crate::{doc, Value};
+
+    #[test]
+    fn normalize() {
+        let doc = doc!{"z": {"y": 1, "x": [{"b": 2, "a": 3_i64}]}, "a": {}, "m": {"n": {}}};
+
+        assert_eq!(
+            doc.normalize(),
+            doc!{"a": {}, "m": {"n": {}}, "z": {"x": [{"a": 3_i64, "b": 2_i64}], "y": 1_i64}}
+        );
+
+        let options = NormalizeOptions { numbers: NumberPolicy::Keep, strip_empty_documents: true };
+        let normal = doc.normalize_with_options(options);
+        assert_eq!(normal, doc!{"z": {"x": [{"a": 3_i64, "b": 2}], "y": 1}});
+        assert_eq!(normal.keys().collect::<Vec<_>>(), ["z"]);
+
+        let options = NormalizeOptions { numbers: NumberPolicy::Double, ..Default::default() };
+        let normal = doc!{"a": 1, "b": 2_i64, "c": (i64::MAX), "d": ((1_i64 << 53) + 1)}.normalize_with_options(options);
+        assert_eq!(normal.get("a"), Some(&Value::Double(1.0)));
+        assert_eq!(normal.get("b"), Some(&Value::Double(2.0)));
+        assert_eq!(normal.get("c"), Some(&Value::Int64(i64::MAX)));
+        assert_eq!(normal.get("d"), Some(&Value::Int64((1 << 53) + 1)));
+    }
+}
@@ -1,19 +1,80 @@
 //! ObjectId
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
-use std::ffi::CStr;
 use std::{io, fmt, result, error};
+use std::str::FromStr;
+use std::convert::TryFrom;
 
 use byteorder::{ByteOrder, BigEndian, LittleEndian};
-use libc;
+use chrono::{DateTime, TimeZone, Utc};
 use rand::{self, Rng};
 use rand::rngs::OsRng;
 
-use crate::util::md5;
 use crate::util::hex::{ToHex, FromHex, FromHexError};
 
-static mut MACHINE_BYTES: Option<[u8; 3]> = None;
-static OID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static PROCESS_UNIQUE: OnceLock<[u8; 5]> = OnceLock::new();
+static OID_COUNTER: OnceLock<AtomicU32> = OnceLock::new();
+static GENERATOR: RwLock<Option<Arc<dyn ObjectIdGenerator>>> = RwLock::new(None);
+
+/// Source of the ids returned by `ObjectId::new`, e.g. a deterministic
+/// sequence for tests or ids handed out by a coordination service. Install
+/// one with `set_generator`.
+pub trait ObjectIdGenerator: Send + Sync {
+    fn generate(&self) -> ObjectId;
+}
+
+/// The generator used unless another is installed: a 4 byte timestamp, 5
+/// random bytes chosen once per process and a 3 byte counter starting at a
+/// random value.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultGenerator;
+
+impl ObjectIdGenerator for DefaultGenerator {
+    fn generate(&self) -> ObjectId {
+        let mut buf: [u8; 12] = [0; 12];
+
+        buf[..4].copy_from_slice(&timestamp());
+        buf[4..9].copy_from_slice(&process_unique());
+        buf[9..].copy_from_slice(&gen_count());
+
+        ObjectId {
+            bytes: buf
+        }
+    }
+}
+
+/// Make `ObjectId::new` delegate to `generator` from now on, process-wide.
+///
+/// ```
+/// use std::sync::atomic::{AtomicU8, Ordering};
+/// use bsonrs::object_id::{self, ObjectId, ObjectIdGenerator};
+///
+/// struct Sequential(AtomicU8);
+///
+/// impl ObjectIdGenerator for Sequential {
+///     fn generate(&self) -> ObjectId {
+///         let mut bytes = [0; 12];
+///         bytes[11] = self.0.fetch_add(1, Ordering::SeqCst);
+///         ObjectId::with_bytes(bytes)
+///     }
+/// }
+///
+/// object_id::set_generator(Sequential(AtomicU8::new(1)));
+/// assert_eq!(ObjectId::new().to_hex(), "000000000000000000000001");
+/// assert_eq!(ObjectId::new().to_hex(), "000000000000000000000002");
+///
+/// object_id::reset_generator();
+/// assert_ne!(ObjectId::new().timestamp_millis(), 0);
+/// ```
+pub fn set_generator<G: ObjectIdGenerator + 'static>(generator: G) {
+    *GENERATOR.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(generator));
+}
+
+/// Go back to `DefaultGenerator`.
+pub fn reset_generator() {
+    *GENERATOR.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
 
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct ObjectId {
@@ -23,8 +84,9 @@ pub struct ObjectId {
 pub type Result<T> = result::Result<T, Error>;
 
 impl ObjectId {
-    /// Generate a new ObjectId
-    /// 
+    /// Generate a new ObjectId with the installed generator, by default
+    /// `DefaultGenerator`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -35,31 +97,12 @@ impl ObjectId {
     /// println!("{:?}", id);
     /// ```
     pub fn new() -> ObjectId {
-        let timestamp = timestamp();
-        let machine_id = machine_id();
-        let process_id = process_id();
-        let counter = gen_count();
-
-        let mut buf: [u8; 12] = [0; 12];
-
-        buf[0] = timestamp[0];
-        buf[1] = timestamp[1];
-        buf[2] = timestamp[2];
-        buf[3] = timestamp[3];
-
-        buf[4] = machine_id[0];
-        buf[5] = machine_id[1];
-        buf[6] = machine_id[2];
+        // Cloned out rather than holding the lock while a generator runs
+        let generator = GENERATOR.read().unwrap_or_else(|e| e.into_inner()).clone();
 
-        buf[7] = process_id[0];
-        buf[8] = process_id[1];
-
-        buf[9] = counter[0];
-        buf[10] = counter[1];
-        buf[11] = counter[2];
-
-        ObjectId {
-            bytes: buf
+        match generator {
+            Some(generator) => generator.generate(),
+            None => DefaultGenerator.generate(),
         }
     }
 
@@ -104,17 +147,76 @@ impl ObjectId {
         })
     }
 
+    /// The smallest ObjectId created at `time`, for querying `_id` by
+    /// creation time: `{"_id": {"$gte": ObjectId::min_for_time(start)}}`.
+    /// Times are truncated to the second and clamped to the range a 4 byte
+    /// timestamp can hold.
+    pub fn min_for_time(time: DateTime<Utc>) -> ObjectId {
+        ObjectId::for_time(time, 0x00)
+    }
+
+    /// The largest ObjectId created at `time`, see `min_for_time`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use bsonrs::object_id::ObjectId;
+    ///
+    /// let time = Utc.with_ymd_and_hms(2017, 6, 3, 11, 39, 49).unwrap();
+    /// let id = ObjectId::with_string("5932a005b4b4b4ac168cd9e4").unwrap();
+    ///
+    /// assert!(ObjectId::min_for_time(time) <= id && id <= ObjectId::max_for_time(time));
+    /// assert_eq!(ObjectId::max_for_time(time).to_hex(), "5932a005ffffffffffffffff");
+    /// ```
+    pub fn max_for_time(time: DateTime<Utc>) -> ObjectId {
+        ObjectId::for_time(time, 0xFF)
+    }
+
+    fn for_time(time: DateTime<Utc>, fill: u8) -> ObjectId {
+        let secs = time.timestamp().clamp(0, i64::from(u32::MAX)) as u32;
+
+        let mut bytes = [fill; 12];
+        bytes[..4].copy_from_slice(&secs.to_be_bytes());
+
+        ObjectId { bytes }
+    }
+
     /// 12-byte binary representation of this ObjectId.
     pub fn bytes(&self) -> [u8; 12] {
         self.bytes
     }
 
-    /// Timstamp of this ObjectId
-    pub fn timestamp(&self) -> u32 {
+    /// When this ObjectId was created, to the second, taken from its
+    /// leading 4 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bsonrs::object_id::ObjectId;
+    ///
+    /// let id = ObjectId::with_string("5932a005b4b4b4ac168cd9e4").unwrap();
+    ///
+    /// assert_eq!(id.timestamp().to_rfc3339(), "2017-06-03T11:39:49+00:00");
+    /// assert_eq!(id.timestamp_secs(), 1_496_489_989);
+    /// assert_eq!(id.timestamp_millis(), 1_496_489_989_000);
+    /// ```
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(i64::from(self.timestamp_secs()), 0).unwrap()
+    }
+
+    /// Creation time as milliseconds since the Unix epoch.
+    pub fn timestamp_millis(&self) -> i64 {
+        i64::from(self.timestamp_secs()) * 1000
+    }
+
+    /// Creation time as seconds since the Unix epoch.
+    pub fn timestamp_secs(&self) -> u32 {
         BigEndian::read_u32(&self.bytes)
     }
 
     /// Machine ID of this ObjectId
+    #[deprecated(note = "ObjectIds no longer embed a machine id; these bytes are now random")]
     pub fn machine_id(&self) -> u32 {
         let mut buf: [u8; 4] = [0; 4];
         buf[..3].clone_from_slice(&self.bytes[4..7]);
@@ -122,6 +224,7 @@ impl ObjectId {
     }
 
     /// Process ID of this ObjectId
+    #[deprecated(note = "ObjectIds no longer embed a process id; these bytes are now random")]
     pub fn process_id(&self) -> u16 {
         LittleEndian::read_u16(&self.bytes[7..9])
     }
@@ -138,6 +241,23 @@ impl Default for ObjectId {
     }
 }
 
+impl FromStr for ObjectId {
+    type Err = Error;
+
+    /// Parse a 24 character hex string, as `ObjectId::with_string`.
+    fn from_str(s: &str) -> Result<ObjectId> {
+        ObjectId::with_string(s)
+    }
+}
+
+impl TryFrom<&str> for ObjectId {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<ObjectId> {
+        ObjectId::with_string(s)
+    }
+}
+
 impl fmt::Display for ObjectId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str(&self.to_hex())
@@ -162,74 +282,22 @@ fn timestamp() -> [u8; 4] {
     buf
 }
 
+/// Random per process, so ids from different processes don't collide
+/// within the same second.
 #[inline]
-fn hosename() -> Option<String> {
-    let mut buf = [0u8; 255];
-    let ptr = buf.as_mut_ptr() as *mut libc::c_char;
-
-    unsafe {
-        if libc::gethostname(ptr, buf.len() as libc::size_t) != 0 {
-            return None;
-        }
-
-        Some(CStr::from_ptr(ptr).to_string_lossy().to_string())
-    }
-}
-
-#[inline]
-fn machine_id() -> [u8; 3] {
-    unsafe {
-        if let Some(bytes) = MACHINE_BYTES.as_ref() {
-            return *bytes;
-        }
-    }
-
-    let hostname = hosename().expect("Can't get hostname!");
-
-    let bytes = format!("{:x}", md5::compute(hostname.as_bytes()));
-    let bytes = bytes.as_bytes();
-
-    let mut buf = [0u8; 3];
-    buf[0] = bytes[0];
-    buf[1] = bytes[1];
-    buf[2] = bytes[2];
-
-    unsafe {
-        MACHINE_BYTES = Some(buf);
-    }
-
-    buf
-}
-
-#[inline]
-fn process_id() -> [u8; 2] {
-    let pid = unsafe {
-        libc::getpid() as u16
-    };
-    let mut buf: [u8; 2] = [0; 2];
-    LittleEndian::write_u16(&mut buf, pid);
-    buf
+fn process_unique() -> [u8; 5] {
+    *PROCESS_UNIQUE.get_or_init(|| OsRng.gen())
 }
 
 #[inline]
 fn gen_count() -> [u8; 3] {
+    const MAX_U24: u32 = 0x00FF_FFFF;
 
-    const MAX_U24: usize = 0x00FF_FFFF;
-
-    if OID_COUNTER.load(Ordering::SeqCst) == 0 {
-        let mut rng = OsRng;
-        let start = rng.gen_range(0, MAX_U24 + 1);
-        OID_COUNTER.store(start, Ordering::SeqCst); 
-    }
-
-    let count = OID_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let counter = OID_COUNTER.get_or_init(|| AtomicU32::new(OsRng.gen_range(0, MAX_U24 + 1)));
+    let count = counter.fetch_add(1, Ordering::SeqCst) & MAX_U24;
 
-    let u = count % MAX_U24;
-
-    let mut buf: [u8; 8] = [0; 8];
-    BigEndian::write_u64(&mut buf, u as u64);
-
-    [buf[5], buf[6], buf[7]]
+    let buf = count.to_be_bytes();
+    [buf[1], buf[2], buf[3]]
 }
 
 #[derive(Debug)]
@@ -288,3 +356,72 @@ impl error::Error for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::ObjectId;
+
+    #[test]
+    fn generation() {
+        let a = ObjectId::new().bytes();
+        let b = ObjectId::new().bytes();
+
+        // Same process, so the same random middle
+        assert_eq!(a[4..9], b[4..9]);
+
+        // Other tests may take ids in between
+        let count = |id: [u8; 12]| u32::from_be_bytes([0, id[9], id[10], id[11]]);
+        let step = count(b).wrapping_sub(count(a)) & 0x00FF_FFFF;
+        assert!((1..1000).contains(&step));
+    }
+
+    #[test]
+    fn parse_order_hash() {
+        use std::collections::HashSet;
+        use std::convert::TryFrom;
+
+        let id: ObjectId = "5932a005b4b4b4ac168cd9e4".parse().unwrap();
+        assert_eq!(id, ObjectId::try_from("5932a005b4b4b4ac168cd9e4").unwrap());
+
+        assert!("5932a005b4b4b4ac168cd9e".parse::<ObjectId>().is_err());
+        assert!("5932a005b4b4b4ac168cd9".parse::<ObjectId>().is_err());
+        assert!("zz32a005b4b4b4ac168cd9e4".parse::<ObjectId>().is_err());
+
+        // Byte order puts older ids first
+        let older = ObjectId::with_bytes([0x59, 0x32, 0xa0, 0x04, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        let mut ids = [id.clone(), ObjectId::new(), older.clone()];
+        ids.sort();
+        assert_eq!(ids[..2], [older, id.clone()]);
+
+        let set: HashSet<_> = vec![id.clone(), id].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn range_for_time() {
+        use chrono::{TimeZone, Utc};
+
+        let id = ObjectId::new();
+        let time = id.timestamp();
+
+        assert!(ObjectId::min_for_time(time) <= id);
+        assert!(id <= ObjectId::max_for_time(time));
+        assert!(ObjectId::max_for_time(time - chrono::Duration::seconds(1)) < id);
+        assert_eq!(ObjectId::min_for_time(time).timestamp(), time);
+
+        let early = Utc.timestamp_opt(-5, 0).unwrap();
+        assert_eq!(ObjectId::min_for_time(early).bytes(), [0; 12]);
+    }
+
+    #[test]
+    fn timestamp() {
+        let before = chrono::Utc::now().timestamp();
+        let id = ObjectId::new();
+
+        assert!(id.timestamp().timestamp() >= before);
+        assert_eq!(id.timestamp().timestamp_millis(), id.timestamp_millis());
+
+        let max = ObjectId::with_bytes([0xFF; 12]);
+        assert_eq!(max.timestamp_millis(), 4_294_967_295_000);
+    }
+}
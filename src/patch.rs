@@ -0,0 +1,448 @@
+//! JSON Patch (RFC 6902) over documents
+//!
+//! A patch is a list of `PatchOp`s, each addressing its target with a JSON
+//! Pointer (RFC 6901) such as `"/user/emails/0"`. Operations convert to and
+//! from documents of the form `{"op": "add", "path": "/a", "value": 1}`, so a
+//! patch can be stored or sent alongside the documents it changes.
+//!
+//! ```
+//! use bsonrs::doc;
+//! use bsonrs::patch::{self, PatchOp};
+//!
+//! let mut doc = doc!{"name": "a", "tags": ["x"]};
+//!
+//! let ops = vec![
+//!     PatchOp::Replace { path: "/name".to_string(), value: "b".into() },
+//!     PatchOp::Add { path: "/tags/-".to_string(), value: "y".into() },
+//! ];
+//!
+//! patch::apply(&mut doc, &ops).unwrap();
+//! assert_eq!(doc, doc!{"name": "b", "tags": ["x", "y"]});
+//!
+//! let op = PatchOp::from_document(&doc!{"op": "remove", "path": "/tags/0"}).unwrap();
+//! op.apply(&mut doc).unwrap();
+//! assert_eq!(doc, doc!{"name": "b", "tags": ["y"]});
+//! ```
+use std::{fmt, error, result};
+
+use crate::value::{Value, Array};
+use crate::doc::Document;
+use crate::diff::{DocumentDiff, Change};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /// An operation document is missing a field or names an unknown `op`.
+    InvalidOperation(String),
+    InvalidPointer(String),
+    /// The target, or for `add` its parent, does not exist.
+    NotFound(String),
+    /// A `move` whose `path` is inside `from`.
+    MoveIntoSelf(String),
+    TestFailed(String),
+}
+
+pub type PatchResult<T> = result::Result<T, PatchError>;
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::InvalidOperation(ref inner) => write!(fmt, "invalid patch operation: {}", inner),
+            PatchError::InvalidPointer(ref path) => write!(fmt, "invalid pointer `{}`", path),
+            PatchError::NotFound(ref path) => write!(fmt, "no value at `{}`", path),
+            PatchError::MoveIntoSelf(ref path) => write!(fmt, "cannot move `{}` into itself", path),
+            PatchError::TestFailed(ref path) => write!(fmt, "test failed at `{}`", path),
+        }
+    }
+}
+
+impl error::Error for PatchError {}
+
+impl PatchOp {
+    pub fn op(&self) -> &'static str {
+        match self {
+            PatchOp::Add { .. } => "add",
+            PatchOp::Remove { .. } => "remove",
+            PatchOp::Replace { .. } => "replace",
+            PatchOp::Move { .. } => "move",
+            PatchOp::Copy { .. } => "copy",
+            PatchOp::Test { .. } => "test",
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            PatchOp::Add { path, .. } | PatchOp::Remove { path } | PatchOp::Replace { path, .. } |
+            PatchOp::Move { path, .. } | PatchOp::Copy { path, .. } | PatchOp::Test { path, .. } => path,
+        }
+    }
+
+    pub fn from_document(doc: &Document) -> PatchResult<PatchOp> {
+        let string = |key: &str| {
+            doc.get_str(key)
+                .map(str::to_string)
+                .map_err(|_| PatchError::InvalidOperation(format!("`{}` must be a string", key)))
+        };
+        let value = || {
+            doc.get("value")
+                .cloned()
+                .ok_or_else(|| PatchError::InvalidOperation("missing `value`".to_string()))
+        };
+
+        let op = match string("op")?.as_str() {
+            "add" => PatchOp::Add { path: string("path")?, value: value()? },
+            "remove" => PatchOp::Remove { path: string("path")? },
+            "replace" => PatchOp::Replace { path: string("path")?, value: value()? },
+            "move" => PatchOp::Move { from: string("from")?, path: string("path")? },
+            "copy" => PatchOp::Copy { from: string("from")?, path: string("path")? },
+            "test" => PatchOp::Test { path: string("path")?, value: value()? },
+            other => return Err(PatchError::InvalidOperation(format!("unknown op `{}`", other))),
+        };
+
+        Ok(op)
+    }
+
+    pub fn to_document(&self) -> Document {
+        let mut doc = Document::new();
+        doc.insert("op", self.op());
+
+        if let PatchOp::Move { from, .. } | PatchOp::Copy { from, .. } = self {
+            doc.insert("from", from.clone());
+        }
+
+        doc.insert("path", self.path());
+
+        if let PatchOp::Add { value, .. } | PatchOp::Replace { value, .. } | PatchOp::Test { value, .. } = self {
+            doc.insert_value("value".to_string(), value.clone());
+        }
+
+        doc
+    }
+
+    /// Apply this single operation. On error `doc` is left unchanged.
+    pub fn apply(&self, doc: &mut Document) -> PatchResult<()> {
+        match self {
+            PatchOp::Add { path, value } => add(doc, path, value.clone()),
+            PatchOp::Remove { path } => remove(doc, path).map(|_| ()),
+            PatchOp::Replace { path, value } => {
+                let tokens = parse_pointer(path)?;
+
+                if tokens.is_empty() {
+                    *doc = root_document(path, value.clone())?;
+                    return Ok(());
+                }
+
+                match get_mut(doc, &tokens) {
+                    Some(target) => {
+                        *target = value.clone();
+                        Ok(())
+                    }
+                    None => Err(PatchError::NotFound(path.clone())),
+                }
+            }
+            PatchOp::Move { from, path } => {
+                if from == path {
+                    let tokens = parse_pointer(from)?;
+
+                    if tokens.is_empty() || get(doc, &tokens).is_some() {
+                        return Ok(());
+                    }
+
+                    return Err(PatchError::NotFound(from.clone()));
+                }
+
+                if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                    return Err(PatchError::MoveIntoSelf(from.clone()));
+                }
+
+                let mut moved = doc.clone();
+                let value = remove(&mut moved, from)?;
+                add(&mut moved, path, value)?;
+                *doc = moved;
+
+                Ok(())
+            }
+            PatchOp::Copy { from, path } => {
+                let tokens = parse_pointer(from)?;
+
+                let value = if tokens.is_empty() {
+                    Value::Document(doc.clone())
+                } else {
+                    get(doc, &tokens).cloned().ok_or_else(|| PatchError::NotFound(from.clone()))?
+                };
+
+                add(doc, path, value)
+            }
+            PatchOp::Test { path, value } => {
+                let tokens = parse_pointer(path)?;
+
+                let equal = if tokens.is_empty() {
+                    matches!(value, Value::Document(d) if d == doc)
+                } else {
+                    get(doc, &tokens) == Some(value)
+                };
+
+                if equal {
+                    Ok(())
+                } else {
+                    Err(PatchError::TestFailed(path.clone()))
+                }
+            }
+        }
+    }
+}
+
+impl From<&PatchOp> for Document {
+    fn from(op: &PatchOp) -> Document {
+        op.to_document()
+    }
+}
+
+/// Apply `ops` in order. The patch is atomic: if any operation fails, `doc`
+/// is left as it was.
+pub fn apply(doc: &mut Document, ops: &[PatchOp]) -> PatchResult<()> {
+    let mut patched = doc.clone();
+
+    for op in ops {
+        op.apply(&mut patched)?;
+    }
+
+    *doc = patched;
+
+    Ok(())
+}
+
+/// Operations that turn `old` into `new`. Subdocuments present on both
+/// sides are compared field by field; any other differing value, arrays
+/// included, is replaced as a whole.
+pub fn diff(old: &Document, new: &Document) -> Vec<PatchOp> {
+    DocumentDiff::new(old, new).into_keyed_changes().map(|(keys, change)| {
+        let path: String = keys.iter().map(|key| format!("/{}", escape(key))).collect();
+
+        match change {
+            Change::Added { value, .. } => PatchOp::Add { path, value },
+            Change::Removed { .. } => PatchOp::Remove { path },
+            Change::Changed { new, .. } => PatchOp::Replace { path, value: new },
+        }
+    }).collect()
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn parse_pointer(pointer: &str) -> PatchResult<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if !pointer.starts_with('/') {
+        return Err(PatchError::InvalidPointer(pointer.to_string()));
+    }
+
+    pointer[1..].split('/').map(|token| {
+        // `~` may only start the escapes `~0` and `~1`
+        if token.split('~').skip(1).any(|rest| !rest.starts_with('0') && !rest.starts_with('1')) {
+            return Err(PatchError::InvalidPointer(pointer.to_string()));
+        }
+
+        Ok(token.replace("~1", "/").replace("~0", "~"))
+    }).collect()
+}
+
+/// Array indexes are decimal without leading zeros.
+fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    token.parse().ok()
+}
+
+fn root_document(path: &str, value: Value) -> PatchResult<Document> {
+    match value {
+        Value::Document(doc) => Ok(doc),
+        _ => Err(PatchError::InvalidOperation(format!("`{}` can only be replaced by a document", path))),
+    }
+}
+
+fn get<'a>(doc: &'a Document, tokens: &[String]) -> Option<&'a Value> {
+    let (first, rest) = tokens.split_first()?;
+    let mut current = doc.get(first)?;
+
+    for token in rest {
+        current = match current {
+            Value::Document(doc) => doc.get(token)?,
+            Value::Array(array) => array.get(array_index(token)?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+fn get_mut<'a>(doc: &'a mut Document, tokens: &[String]) -> Option<&'a mut Value> {
+    let (first, rest) = tokens.split_first()?;
+    let mut current = doc.get_mut(first)?;
+
+    for token in rest {
+        current = match current {
+            Value::Document(doc) => doc.get_mut(token)?,
+            Value::Array(array) => array.get_mut(array_index(token)?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+enum Parent<'a> {
+    Document(&'a mut Document),
+    Array(&'a mut Array),
+}
+
+/// The container holding the last token of `tokens`, which must not be
+/// empty.
+fn parent_mut<'a>(doc: &'a mut Document, tokens: &[String]) -> Option<Parent<'a>> {
+    if tokens.len() == 1 {
+        return Some(Parent::Document(doc));
+    }
+
+    match get_mut(doc, &tokens[..tokens.len() - 1]) {
+        Some(Value::Document(doc)) => Some(Parent::Document(doc)),
+        Some(Value::Array(array)) => Some(Parent::Array(array)),
+        _ => None,
+    }
+}
+
+fn add(doc: &mut Document, path: &str, value: Value) -> PatchResult<()> {
+    let tokens = parse_pointer(path)?;
+
+    if tokens.is_empty() {
+        *doc = root_document(path, value)?;
+        return Ok(());
+    }
+
+    let last = &tokens[tokens.len() - 1];
+
+    match parent_mut(doc, &tokens) {
+        Some(Parent::Document(parent)) => {
+            parent.insert_value(last.clone(), value);
+        }
+        Some(Parent::Array(array)) => {
+            let index = if last == "-" {
+                array.len()
+            } else {
+                array_index(last).filter(|&i| i <= array.len()).ok_or_else(|| PatchError::NotFound(path.to_string()))?
+            };
+
+            array.insert(index, value);
+        }
+        None => return Err(PatchError::NotFound(path.to_string())),
+    }
+
+    Ok(())
+}
+
+fn remove(doc: &mut Document, path: &str) -> PatchResult<Value> {
+    let tokens = parse_pointer(path)?;
+
+    if tokens.is_empty() {
+        return Err(PatchError::InvalidOperation("cannot remove the root".to_string()));
+    }
+
+    let last = &tokens[tokens.len() - 1];
+
+    let removed = match parent_mut(doc, &tokens) {
+        Some(Parent::Document(parent)) => parent.remove(last),
+        Some(Parent::Array(array)) => {
+            array_index(last).filter(|&i| i < array.len()).map(|i| array.remove(i))
+        }
+        None => None,
+    };
+
+    removed.ok_or_else(|| PatchError::NotFound(path.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PatchOp, PatchError, apply, diff};
+    use crate::doc;
+
+    fn ops(docs: &[crate::Document]) -> Vec<PatchOp> {
+        docs.iter().map(|d| PatchOp::from_document(d).unwrap()).collect()
+    }
+
+    #[test]
+    fn operations() {
+        let mut document = doc!{"a": {"b": [1, 2]}, "c~/d": 1};
+
+        apply(&mut document, &ops(&[
+            doc!{"op": "add", "path": "/a/b/1", "value": 5},
+            doc!{"op": "add", "path": "/a/b/-", "value": 6},
+            doc!{"op": "remove", "path": "/a/b/0"},
+            doc!{"op": "replace", "path": "/c~0~1d", "value": "x"},
+            doc!{"op": "copy", "from": "/a/b", "path": "/e"},
+            doc!{"op": "move", "from": "/e/0", "path": "/f"},
+            doc!{"op": "test", "path": "/f", "value": 5},
+        ])).unwrap();
+
+        assert_eq!(document, doc!{"a": {"b": [5, 2, 6]}, "c~/d": "x", "e": [2, 6], "f": 5});
+    }
+
+    #[test]
+    fn errors_leave_document_unchanged() {
+        let original = doc!{"a": {"b": 1}, "c": [1]};
+        let mut document = original.clone();
+
+        let cases = vec![
+            (PatchOp::Remove { path: "/x".to_string() }, PatchError::NotFound("/x".to_string())),
+            (PatchOp::Add { path: "/x/y".to_string(), value: 1.into() }, PatchError::NotFound("/x/y".to_string())),
+            (PatchOp::Add { path: "/c/2".to_string(), value: 1.into() }, PatchError::NotFound("/c/2".to_string())),
+            (PatchOp::Remove { path: "/c/01".to_string() }, PatchError::NotFound("/c/01".to_string())),
+            (PatchOp::Move { from: "/a".to_string(), path: "/a/b".to_string() }, PatchError::MoveIntoSelf("/a".to_string())),
+            (PatchOp::Test { path: "/a/b".to_string(), value: 2.into() }, PatchError::TestFailed("/a/b".to_string())),
+            (PatchOp::Remove { path: "a".to_string() }, PatchError::InvalidPointer("a".to_string())),
+            (PatchOp::Remove { path: "/a~2".to_string() }, PatchError::InvalidPointer("/a~2".to_string())),
+        ];
+
+        for (op, err) in cases {
+            let ops = vec![PatchOp::Add { path: "/z".to_string(), value: 1.into() }, op];
+            assert_eq!(apply(&mut document, &ops), Err(err));
+            assert_eq!(document, original);
+        }
+
+        assert!(PatchOp::from_document(&doc!{"op": "frobnicate", "path": "/a"}).is_err());
+        assert!(PatchOp::from_document(&doc!{"op": "add", "path": "/a"}).is_err());
+    }
+
+    #[test]
+    fn diff_round_trip() {
+        let old = doc!{"a": 1, "b": {"c": 2, "d": 3}, "e/f": [1], "g": {"h": 1}};
+        let new = doc!{"a": 1, "b": {"c": 4}, "e/f": [1, 2], "g": 5, "i": null};
+
+        let ops = diff(&old, &new);
+
+        assert_eq!(ops.iter().map(PatchOp::to_document).collect::<Vec<_>>(), vec![
+            doc!{"op": "replace", "path": "/b/c", "value": 4},
+            doc!{"op": "remove", "path": "/b/d"},
+            doc!{"op": "replace", "path": "/e~1f", "value": [1, 2]},
+            doc!{"op": "replace", "path": "/g", "value": 5},
+            doc!{"op": "add", "path": "/i", "value": null},
+        ]);
+
+        let mut patched = old.clone();
+        apply(&mut patched, &ops).unwrap();
+        assert_eq!(patched, new);
+        assert!(diff(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,357 @@
+//! Dotted paths into nested documents
+//!
+//! A `Path` is parsed once from a string like `"a.b.3.c"` and can then be
+//! applied to any number of documents. Each segment is looked up as a key in
+//! documents, and, when it is a number, as an index in arrays.
+//!
+//! ```
+//! use bsonrs::doc;
+//! use bsonrs::path::Path;
+//!
+//! let path = Path::parse("user.emails.0").unwrap();
+//! let mut doc = doc!{"user": {"emails": ["a@example.com"]}};
+//!
+//! assert_eq!(path.get(&doc).and_then(|v| v.as_str()), Some("a@example.com"));
+//!
+//! path.set(&mut doc, "b@example.com").unwrap();
+//! assert_eq!(doc, doc!{"user": {"emails": ["b@example.com"]}});
+//! ```
+//!
+//! With `select`, a `*` segment matches every element of an array or every
+//! value of a document:
+//!
+//! ```
+//! use bsonrs::{doc, Value};
+//!
+//! let doc = doc!{"orders": [{"total": 5}, {"total": 7}, {"note": "free"}]};
+//! let totals: Vec<_> = doc.select_path("orders.*.total").collect();
+//!
+//! assert_eq!(totals, vec![&Value::Int32(5), &Value::Int32(7)]);
+//! ```
+use std::{fmt, error, result};
+use std::str::FromStr;
+
+use crate::value::Value;
+use crate::doc::{Document, Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    key: String,
+    index: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn parse(path: &str) -> result::Result<Path, ParseError> {
+        if path.is_empty() {
+            return Err(ParseError { path: path.to_string() });
+        }
+
+        let segments = path.split('.').map(|key| {
+            if key.is_empty() {
+                return Err(ParseError { path: path.to_string() });
+            }
+
+            Ok(Segment {
+                key: key.to_string(),
+                index: key.parse().ok(),
+            })
+        }).collect::<result::Result<_, _>>()?;
+
+        Ok(Path { segments })
+    }
+
+    /// Number of segments in the path.
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn get<'a>(&self, doc: &'a Document) -> Option<&'a Value> {
+        let (first, rest) = self.segments.split_first()?;
+        let mut current = doc.get(&first.key)?;
+
+        for segment in rest {
+            current = segment.get(current)?;
+        }
+
+        Some(current)
+    }
+
+    pub fn get_mut<'a>(&self, doc: &'a mut Document) -> Option<&'a mut Value> {
+        let (first, rest) = self.segments.split_first()?;
+        let mut current = doc.get_mut(&first.key)?;
+
+        for segment in rest {
+            current = segment.get_mut(current)?;
+        }
+
+        Some(current)
+    }
+
+    /// Every value matching this path, where a `*` segment matches all
+    /// elements of an array or values of a document. Elsewhere, as in `get`,
+    /// `*` is an ordinary key.
+    pub fn select<'a>(&self, doc: &'a Document) -> impl Iterator<Item = &'a Value> {
+        let mut current: Vec<&'a Value> = match self.segments.split_first() {
+            Some((first, _)) if first.is_wildcard() => doc.iter().map(|(_, v)| v).collect(),
+            Some((first, _)) => doc.get(&first.key).into_iter().collect(),
+            None => Vec::new(),
+        };
+
+        for segment in self.segments.iter().skip(1) {
+            current = current.into_iter().flat_map(|value| segment.select(value)).collect();
+        }
+
+        current.into_iter()
+    }
+
+    pub fn contains(&self, doc: &Document) -> bool {
+        self.get(doc).is_some()
+    }
+
+    /// Remove the value at this path and return it. Array elements are
+    /// removed too, shifting the ones after them down.
+    pub fn remove(&self, doc: &mut Document) -> Option<Value> {
+        remove_in_document(doc, &self.segments, false)
+    }
+
+    /// Like `remove`, but also drop the documents on the way to it that
+    /// this leaves empty. `doc` itself is kept even if it ends up empty.
+    pub fn remove_and_prune(&self, doc: &mut Document) -> Option<Value> {
+        remove_in_document(doc, &self.segments, true)
+    }
+
+    /// Store `value` at this path, creating missing intermediate documents,
+    /// and return the value it replaced. Fails with `Error::UnexpectedType`
+    /// if the path runs through something other than a document or array, and
+    /// with `Error::NotPresent` for an array index past the end.
+    pub fn set(&self, doc: &mut Document, value: impl Into<Value>) -> Result<Option<Value>> {
+        if self.segments.is_empty() {
+            return Err(Error::NotPresent);
+        }
+
+        set_in_document(doc, &self.segments, value.into())
+    }
+}
+
+fn set_in_document(doc: &mut Document, segments: &[Segment], value: Value) -> Result<Option<Value>> {
+    let (first, rest) = segments.split_first().ok_or(Error::NotPresent)?;
+
+    if rest.is_empty() {
+        return Ok(doc.insert_value(first.key.clone(), value));
+    }
+
+    let next = doc.entry(first.key.clone())
+        .or_insert_with(|| Value::Document(Document::new()));
+
+    set_in_value(next, rest, value)
+}
+
+fn set_in_value(target: &mut Value, segments: &[Segment], value: Value) -> Result<Option<Value>> {
+    match target {
+        Value::Document(doc) => set_in_document(doc, segments, value),
+        Value::Array(arr) => {
+            let (first, rest) = segments.split_first().ok_or(Error::NotPresent)?;
+            let slot = first.index_mut(arr)?;
+
+            if rest.is_empty() {
+                Ok(Some(std::mem::replace(slot, value)))
+            } else {
+                set_in_value(slot, rest, value)
+            }
+        }
+        _ => Err(Error::UnexpectedType),
+    }
+}
+
+fn remove_in_document(doc: &mut Document, segments: &[Segment], prune: bool) -> Option<Value> {
+    let (first, rest) = segments.split_first()?;
+
+    if rest.is_empty() {
+        return doc.remove(&first.key);
+    }
+
+    let next = doc.get_mut(&first.key)?;
+    let removed = remove_in_value(next, rest, prune)?;
+
+    if prune && matches!(next, Value::Document(d) if d.is_empty()) {
+        doc.remove(&first.key);
+    }
+
+    Some(removed)
+}
+
+fn remove_in_value(target: &mut Value, segments: &[Segment], prune: bool) -> Option<Value> {
+    match target {
+        Value::Document(doc) => remove_in_document(doc, segments, prune),
+        Value::Array(arr) => {
+            let (first, rest) = segments.split_first()?;
+            let index = first.index?;
+
+            if rest.is_empty() {
+                if index < arr.len() { Some(arr.remove(index)) } else { None }
+            } else {
+                remove_in_value(arr.get_mut(index)?, rest, prune)
+            }
+        }
+        _ => None,
+    }
+}
+
+impl Segment {
+    fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        match value {
+            Value::Document(doc) => doc.get(&self.key),
+            Value::Array(arr) => arr.get(self.index?),
+            _ => None,
+        }
+    }
+
+    fn is_wildcard(&self) -> bool {
+        self.key == "*"
+    }
+
+    fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        match value {
+            Value::Document(doc) if self.is_wildcard() => doc.iter().map(|(_, v)| v).collect(),
+            Value::Array(arr) if self.is_wildcard() => arr.iter().collect(),
+            _ => self.get(value).into_iter().collect(),
+        }
+    }
+
+    fn get_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        match value {
+            Value::Document(doc) => doc.get_mut(&self.key),
+            Value::Array(arr) => arr.get_mut(self.index?),
+            _ => None,
+        }
+    }
+
+    fn index_mut<'a>(&self, arr: &'a mut [Value]) -> Result<&'a mut Value> {
+        let index = self.index.ok_or(Error::UnexpectedType)?;
+        arr.get_mut(index).ok_or(Error::NotPresent)
+    }
+}
+
+impl FromStr for Path {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> result::Result<Path, ParseError> {
+        Path::parse(s)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                fmt.write_str(".")?;
+            }
+
+            fmt.write_str(&segment.key)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub path: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid path `{}`", self.path)
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[cfg(test)]
+mod test {
+    use super::Path;
+    use crate::doc;
+    use crate::doc::Error;
+    use crate::Value;
+
+    #[test]
+    fn get_and_set() {
+        let path = Path::parse("a.b.1.c").unwrap();
+        let mut doc = doc!{"a": {"b": [{"c": 1}, {"c": 2}]}};
+
+        assert_eq!(path.get(&doc), Some(&Value::Int32(2)));
+        assert_eq!(path.set(&mut doc, 3), Ok(Some(Value::Int32(2))));
+        assert_eq!(doc, doc!{"a": {"b": [{"c": 1}, {"c": 3}]}});
+
+        assert_eq!(Path::parse("x.y").unwrap().set(&mut doc, 1), Ok(None));
+        assert_eq!(doc.get_document("x").unwrap(), &doc!{"y": 1});
+
+        assert_eq!(Path::parse("a.b.5.c").unwrap().set(&mut doc, 1), Err(Error::NotPresent));
+        assert_eq!(Path::parse("x.y.z").unwrap().set(&mut doc, 1), Err(Error::UnexpectedType));
+        assert_eq!(Path::parse("a.b.0").unwrap().get(&doc), Some(&Value::Document(doc!{"c": 1})));
+        assert_eq!(Path::parse("a.b.c").unwrap().get(&doc), None);
+
+        assert!(Path::parse("a.b.1").unwrap().contains(&doc));
+        assert!(!Path::parse("a.b.2").unwrap().contains(&doc));
+
+        assert!(Path::parse("").is_err());
+        assert!(Path::parse("a..b").is_err());
+        assert_eq!(Path::parse("a.b.1.c").unwrap().to_string(), "a.b.1.c");
+    }
+
+    #[test]
+    fn remove() {
+        let mut doc = doc!{"a": {"b": {"c": 1}}, "d": [1, {"e": 2}, 3]};
+
+        assert_eq!(Path::parse("a.b.c").unwrap().remove(&mut doc), Some(Value::Int32(1)));
+        assert_eq!(doc.get("a"), Some(&Value::Document(doc!{"b": {}})));
+        assert_eq!(Path::parse("a.b.c").unwrap().remove(&mut doc), None);
+
+        assert_eq!(Path::parse("d.0").unwrap().remove(&mut doc), Some(Value::Int32(1)));
+        assert_eq!(Path::parse("d.0.e").unwrap().remove(&mut doc), Some(Value::Int32(2)));
+        assert_eq!(Path::parse("d.5").unwrap().remove(&mut doc), None);
+        assert_eq!(doc.get("d"), doc!{"d": [{}, 3]}.get("d"));
+
+        let mut doc = doc!{"a": {"b": {"c": 1}, "x": {"y": {"z": 1}, "w": 2}}};
+        assert_eq!(Path::parse("a.x.y.z").unwrap().remove_and_prune(&mut doc), Some(Value::Int32(1)));
+        assert_eq!(doc, doc!{"a": {"b": {"c": 1}, "x": {"w": 2}}});
+        assert_eq!(Path::parse("a.b.c").unwrap().remove_and_prune(&mut doc), Some(Value::Int32(1)));
+        assert_eq!(doc, doc!{"a": {"x": {"w": 2}}});
+        assert_eq!(Path::parse("a.x.w").unwrap().remove_and_prune(&mut doc), Some(Value::Int32(2)));
+        assert_eq!(doc, doc!{});
+    }
+
+    #[test]
+    fn select() {
+        let doc = doc!{
+            "orders": [
+                {"total": 5, "items": [{"sku": "a"}, {"sku": "b"}]},
+                {"total": 7, "items": [{"sku": "c"}]},
+                "not a document"
+            ],
+            "by_region": {"eu": {"total": 1}, "us": {"total": 2}},
+            "*": 3
+        };
+
+        let select = |path: &str| Path::parse(path).unwrap().select(&doc).cloned().collect::<Vec<_>>();
+
+        assert_eq!(select("orders.*.total"), vec![Value::Int32(5), Value::Int32(7)]);
+        assert_eq!(select("orders.*.items.*.sku"), vec![Value::from("a"), Value::from("b"), Value::from("c")]);
+        assert_eq!(select("by_region.*.total"), vec![Value::Int32(1), Value::Int32(2)]);
+        assert_eq!(select("orders.1.total"), vec![Value::Int32(7)]);
+        assert_eq!(select("orders.*.missing"), vec![]);
+        assert_eq!(select("*").len(), 3);
+
+        // Only `select` expands wildcards
+        assert_eq!(Path::parse("*").unwrap().get(&doc), Some(&Value::Int32(3)));
+    }
+}
@@ -0,0 +1,252 @@
+//! MongoDB-style projections
+//!
+//! A projection is parsed from a spec document mapping dotted paths to
+//! `1`/`true` to include them or `0`/`false` to exclude them. A spec either
+//! includes or excludes; only `_id` may go against the others. Inclusion
+//! keeps `_id` unless it is explicitly excluded.
+//!
+//! ```
+//! use bsonrs::doc;
+//! use bsonrs::projection::Projection;
+//!
+//! let doc = doc!{"_id": 1, "name": "a", "address": {"city": "x", "zip": 2}, "tags": ["t"]};
+//!
+//! let projection = Projection::parse(&doc!{"name": 1, "address.city": 1}).unwrap();
+//! assert_eq!(doc.project(&projection), doc!{"_id": 1, "name": "a", "address": {"city": "x"}});
+//!
+//! let projection = Projection::parse(&doc!{"_id": 0, "address": 0}).unwrap();
+//! assert_eq!(doc.project(&projection), doc!{"name": "a", "tags": ["t"]});
+//! ```
+//!
+//! As in MongoDB, a path leading into an array applies to each document in
+//! it: `{"a.b": 1}` turns `{"a": [{"b": 1, "c": 2}, 3]}` into
+//! `{"a": [{"b": 1}]}`.
+use std::{fmt, error, result};
+
+use indexmap::IndexMap;
+
+use crate::value::Value;
+use crate::doc::Document;
+
+const ID: &str = "_id";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+    include: bool,
+    fields: Node,
+}
+
+/// Fields to project below one level. A node without children stands for
+/// the whole field.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Node {
+    children: IndexMap<String, Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectionError {
+    /// A value that is neither a boolean nor a number.
+    InvalidValue(String),
+    InvalidPath(String),
+    /// A spec both including and excluding fields other than `_id`.
+    Mixed(String),
+    /// Two paths where one is a prefix of the other, such as `a` and `a.b`.
+    Collision(String),
+}
+
+impl fmt::Display for ProjectionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProjectionError::InvalidValue(ref path) => write!(fmt, "projection of `{}` must be a boolean or number", path),
+            ProjectionError::InvalidPath(ref path) => write!(fmt, "invalid projection path `{}`", path),
+            ProjectionError::Mixed(ref path) => write!(fmt, "cannot mix inclusion and exclusion at `{}`", path),
+            ProjectionError::Collision(ref path) => write!(fmt, "projection path collision at `{}`", path),
+        }
+    }
+}
+
+impl error::Error for ProjectionError {}
+
+impl Projection {
+    pub fn parse(spec: &Document) -> result::Result<Projection, ProjectionError> {
+        let mut include = None;
+        let mut id = None;
+        let mut fields = Node::default();
+
+        for (path, value) in spec {
+            let flag = match *value {
+                Value::Boolean(b) => b,
+                Value::Int32(v) => v != 0,
+                Value::Int64(v) => v != 0,
+                Value::Double(v) => v != 0.0,
+                _ => return Err(ProjectionError::InvalidValue(path.clone())),
+            };
+
+            if path == ID {
+                id = Some(flag);
+                continue;
+            }
+
+            if *include.get_or_insert(flag) != flag {
+                return Err(ProjectionError::Mixed(path.clone()));
+            }
+
+            fields.insert(path)?;
+        }
+
+        // A spec of only `_id` includes or excludes just that
+        let include = include.or(id).unwrap_or(false);
+
+        if id == Some(include) || (include && id.is_none()) {
+            fields.children.entry(ID.to_string()).or_default();
+        }
+
+        Ok(Projection { include, fields })
+    }
+
+    pub fn is_inclusion(&self) -> bool {
+        self.include
+    }
+
+    pub fn apply(&self, doc: &Document) -> Document {
+        if self.include {
+            include_document(doc, &self.fields)
+        } else {
+            exclude_document(doc, &self.fields)
+        }
+    }
+}
+
+impl Node {
+    fn insert(&mut self, path: &str) -> result::Result<(), ProjectionError> {
+        let segments: Vec<_> = path.split('.').collect();
+
+        if segments.iter().any(|s| s.is_empty()) {
+            return Err(ProjectionError::InvalidPath(path.to_string()));
+        }
+
+        let mut node = self;
+
+        for (i, segment) in segments.iter().enumerate() {
+            match node.children.get(*segment) {
+                // A shorter path already covers this one
+                Some(existing) if existing.children.is_empty() => {
+                    return Err(ProjectionError::Collision(path.to_string()));
+                }
+                // A longer one is already inside this one
+                Some(_) if i == segments.len() - 1 => {
+                    return Err(ProjectionError::Collision(path.to_string()));
+                }
+                _ => (),
+            }
+
+            node = node.children.entry(segment.to_string()).or_default();
+        }
+
+        Ok(())
+    }
+}
+
+fn include_document(doc: &Document, node: &Node) -> Document {
+    let mut projected = Document::new();
+
+    for (key, value) in doc {
+        if let Some(child) = node.children.get(key) {
+            if child.children.is_empty() {
+                projected.insert_value(key.clone(), value.clone());
+            } else if let Some(value) = include_value(value, child) {
+                projected.insert_value(key.clone(), value);
+            }
+        }
+    }
+
+    projected
+}
+
+fn include_value(value: &Value, node: &Node) -> Option<Value> {
+    match value {
+        Value::Document(doc) => Some(Value::Document(include_document(doc, node))),
+        Value::Array(array) => {
+            Some(Value::Array(array.iter().filter_map(|v| match v {
+                Value::Document(_) | Value::Array(_) => include_value(v, node),
+                _ => None,
+            }).collect()))
+        }
+        _ => None,
+    }
+}
+
+fn exclude_document(doc: &Document, node: &Node) -> Document {
+    let mut projected = Document::new();
+
+    for (key, value) in doc {
+        match node.children.get(key) {
+            None => {
+                projected.insert_value(key.clone(), value.clone());
+            }
+            Some(child) if !child.children.is_empty() => {
+                projected.insert_value(key.clone(), exclude_value(value, child));
+            }
+            Some(_) => (),
+        }
+    }
+
+    projected
+}
+
+fn exclude_value(value: &Value, node: &Node) -> Value {
+    match value {
+        Value::Document(doc) => Value::Document(exclude_document(doc, node)),
+        Value::Array(array) => Value::Array(array.iter().map(|v| exclude_value(v, node)).collect()),
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Projection, ProjectionError};
+    use crate::doc;
+
+    #[test]
+    fn include() {
+        let doc = doc!{"a": 1, "_id": 2, "b": {"c": 3, "d": 4}, "e": [{"c": 5, "d": 6}, 7, [{"c": 8}]], "f": 9};
+
+        let projection = Projection::parse(&doc!{"b.c": 1, "e.c": true, "f": 1.0, "x.y": 1}).unwrap();
+        assert!(projection.is_inclusion());
+        assert_eq!(doc.project(&projection), doc!{"_id": 2, "b": {"c": 3}, "e": [{"c": 5}, [{"c": 8}]], "f": 9});
+
+        let projection = Projection::parse(&doc!{"a": 1, "_id": false}).unwrap();
+        assert_eq!(doc.project(&projection), doc!{"a": 1});
+
+        let projection = Projection::parse(&doc!{"_id": 1}).unwrap();
+        assert_eq!(doc.project(&projection), doc!{"_id": 2});
+
+        // Scalars where a subdocument was expected are dropped
+        let projection = Projection::parse(&doc!{"a.b": 1}).unwrap();
+        assert_eq!(doc.project(&projection), doc!{"_id": 2});
+    }
+
+    #[test]
+    fn exclude() {
+        let doc = doc!{"_id": 1, "a": 2, "b": {"c": 3, "d": 4}, "e": [{"c": 5, "d": 6}, 7]};
+
+        let projection = Projection::parse(&doc!{"a": 0, "b.c": false, "e.d": 0}).unwrap();
+        assert!(!projection.is_inclusion());
+        assert_eq!(doc.project(&projection), doc!{"_id": 1, "b": {"d": 4}, "e": [{"c": 5}, 7]});
+
+        let projection = Projection::parse(&doc!{"_id": 0}).unwrap();
+        assert_eq!(doc.project(&projection), doc!{"a": 2, "b": {"c": 3, "d": 4}, "e": [{"c": 5, "d": 6}, 7]});
+
+        assert_eq!(doc.project(&Projection::parse(&doc!{}).unwrap()), doc);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Projection::parse(&doc!{"a": 1, "b": 0}), Err(ProjectionError::Mixed("b".to_string())));
+        assert_eq!(Projection::parse(&doc!{"a": "yes"}), Err(ProjectionError::InvalidValue("a".to_string())));
+        assert_eq!(Projection::parse(&doc!{"a..b": 1}), Err(ProjectionError::InvalidPath("a..b".to_string())));
+        assert_eq!(Projection::parse(&doc!{"a": 1, "a.b": 1}), Err(ProjectionError::Collision("a.b".to_string())));
+        assert_eq!(Projection::parse(&doc!{"a.b": 1, "a": 1}), Err(ProjectionError::Collision("a".to_string())));
+        assert!(Projection::parse(&doc!{"a.b": 1, "a.c": 1}).is_ok());
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::value::{Array, TimeStamp};
+use crate::spec::BinarySubtype;
+use crate::decode::{DecodeError, DecodeResult, DecodeOptions, decode_array_slice_at};
+use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
+
+use super::{RawValue, RawDocument, RawIter, check_document};
+
+/// An array borrowed from an encoded buffer. Arrays are encoded as documents
+/// keyed `"0"`, `"1"`, ...; elements are looked up by position rather than
+/// by those keys.
+#[derive(Clone, Copy, PartialEq)]
+pub struct RawArray<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RawArray<'a> {
+    pub fn new(data: &'a [u8]) -> DecodeResult<RawArray<'a>> {
+        check_document(data)?;
+        Ok(RawArray { data })
+    }
+
+    /// The encoded array, including its length prefix.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn iter(&self) -> ArrayIter<'a> {
+        ArrayIter {
+            inner: RawIter::new(self.data),
+        }
+    }
+
+    /// The element at `index`, decoding only that element. Elements before
+    /// it are skipped over without being parsed.
+    pub fn get(&self, index: usize) -> DecodeResult<Option<RawValue<'a>>> {
+        match RawIter::new(self.data).nth(index) {
+            Some(element) => {
+                let (_, element_type, bytes) = element?;
+                RawValue::from_bytes(element_type, bytes).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    raw_getters! {
+        usize;
+        get_f64 -> f64, as_f64;
+        get_str -> &'a str, as_str;
+        get_array -> RawArray<'a>, as_array;
+        get_document -> RawDocument<'a>, as_document;
+        get_bool -> bool, as_bool;
+        get_i32 -> i32, as_i32;
+        get_i64 -> i64, as_i64;
+        get_time_stamp -> TimeStamp, as_timestamp;
+        get_binary -> (BinarySubtype, &'a [u8]), as_binary;
+        get_object_id -> ObjectId, as_object_id;
+        get_utc_datetime -> DateTime<Utc>, as_utc_date_time;
+        get_decimal128 -> Decimal128, as_decimal128;
+    }
+
+    /// Decode every element.
+    pub fn to_array(&self) -> DecodeResult<Array> {
+        self.to_array_with_options(DecodeOptions::default())
+    }
+
+    /// Decode every element as `RawDocument::to_document_with_options`
+    /// would, counting the array as the outermost document.
+    pub fn to_array_with_options(&self, options: DecodeOptions) -> DecodeResult<Array> {
+        self.to_array_at(options, 1)
+    }
+
+    /// `depth` is the one this array is nested at.
+    pub(crate) fn to_array_at(self, options: DecodeOptions, depth: usize) -> DecodeResult<Array> {
+        decode_array_slice_at(self.data, options, depth)
+    }
+}
+
+impl<'a> fmt::Debug for RawArray<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "RawArray({} bytes)", self.data.len())
+    }
+}
+
+impl<'a> IntoIterator for RawArray<'a> {
+    type Item = DecodeResult<RawValue<'a>>;
+    type IntoIter = ArrayIter<'a>;
+
+    fn into_iter(self) -> ArrayIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the elements of a `RawArray`. Stops after the first error.
+pub struct ArrayIter<'a> {
+    inner: RawIter<'a>,
+}
+
+impl<'a> Iterator for ArrayIter<'a> {
+    type Item = DecodeResult<RawValue<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|r| {
+            let (_, element_type, bytes) = r?;
+            RawValue::from_bytes(element_type, bytes)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::raw::{RawDocument, RawValue};
+    use crate::decode::DecodeError;
+    use crate::{doc, Value};
+
+    #[test]
+    fn get_and_iter() {
+        let doc = doc!{"a": [1, "two", [3.0], {"b": 4}]};
+        let bytes = doc.to_vec().unwrap();
+        let raw = RawDocument::new(&bytes).unwrap();
+        let arr = raw.get_array("a").unwrap().unwrap();
+
+        assert_eq!(arr.get(0).unwrap(), Some(RawValue::Int32(1)));
+        assert_eq!(arr.get_str(1).unwrap(), Some("two"));
+        assert_eq!(arr.get_array(2).unwrap().unwrap().get_f64(0).unwrap(), Some(3.0));
+        assert_eq!(arr.get_document(3).unwrap().unwrap().get_i32("b").unwrap(), Some(4));
+        assert_eq!(arr.get(4).unwrap(), None);
+        assert!(matches!(arr.get_i32(1), Err(DecodeError::InvalidType(_))));
+
+        assert_eq!(arr.iter().count(), 4);
+        assert_eq!(Value::Array(arr.to_array().unwrap()), *doc.get("a").unwrap());
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::doc::Document;
+use crate::value::TimeStamp;
+use crate::spec::BinarySubtype;
+use crate::decode::{DecodeError, DecodeResult, DecodeOptions, decode_document_slice_at};
+use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
+
+use super::{RawValue, RawArray, RawIter, check_document};
+
+/// A document borrowed from an encoded buffer. Construction only checks the
+/// length prefix; elements are parsed as they are looked up or iterated, so
+/// errors in the body surface from those calls.
+#[derive(Clone, Copy, PartialEq)]
+pub struct RawDocument<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RawDocument<'a> {
+    pub fn new(data: &'a [u8]) -> DecodeResult<RawDocument<'a>> {
+        check_document(data)?;
+        Ok(RawDocument { data })
+    }
+
+    /// For buffers whose framing is already known to be valid.
+    pub(crate) fn from_bytes_unchecked(data: &'a [u8]) -> RawDocument<'a> {
+        RawDocument { data }
+    }
+
+    /// The encoded document, including its length prefix.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn iter(&self) -> Iter<'a> {
+        Iter {
+            inner: RawIter::new(self.data),
+        }
+    }
+
+    /// Find `key`, decoding only its value. Returns `Ok(None)` if it isn't
+    /// present.
+    pub fn get(&self, key: &str) -> DecodeResult<Option<RawValue<'a>>> {
+        for element in RawIter::new(self.data) {
+            let (k, element_type, bytes) = element?;
+
+            if k == key {
+                return RawValue::from_bytes(element_type, bytes).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    raw_getters! {
+        &str;
+        get_f64 -> f64, as_f64;
+        get_str -> &'a str, as_str;
+        get_array -> RawArray<'a>, as_array;
+        get_document -> RawDocument<'a>, as_document;
+        get_bool -> bool, as_bool;
+        get_i32 -> i32, as_i32;
+        get_i64 -> i64, as_i64;
+        get_time_stamp -> TimeStamp, as_timestamp;
+        get_binary -> (BinarySubtype, &'a [u8]), as_binary;
+        get_object_id -> ObjectId, as_object_id;
+        get_utc_datetime -> DateTime<Utc>, as_utc_date_time;
+        get_decimal128 -> Decimal128, as_decimal128;
+    }
+
+    /// Decode the whole document.
+    pub fn to_document(&self) -> DecodeResult<Document> {
+        self.to_document_with_options(DecodeOptions::default())
+    }
+
+    /// Decode the whole document as `Document::from_slice_with_options`
+    /// would, nesting limit included.
+    pub fn to_document_with_options(&self, options: DecodeOptions) -> DecodeResult<Document> {
+        self.to_document_at(options, 1)
+    }
+
+    /// `depth` is the one this document is nested at.
+    pub(crate) fn to_document_at(self, options: DecodeOptions, depth: usize) -> DecodeResult<Document> {
+        decode_document_slice_at(self.data, options, depth)
+    }
+}
+
+impl<'a> fmt::Debug for RawDocument<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "RawDocument({} bytes)", self.data.len())
+    }
+}
+
+impl<'a> IntoIterator for RawDocument<'a> {
+    type Item = DecodeResult<(&'a str, RawValue<'a>)>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the elements of a `RawDocument`. Stops after the first
+/// error.
+pub struct Iter<'a> {
+    inner: RawIter<'a>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = DecodeResult<(&'a str, RawValue<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|r| {
+            let (key, element_type, bytes) = r?;
+            Ok((key, RawValue::from_bytes(element_type, bytes)?))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RawDocument;
+    use crate::raw::RawValue;
+    use crate::decode::DecodeError;
+    use crate::spec::BinarySubtype;
+    use crate::{doc, Value};
+
+    #[test]
+    fn get_and_iter() {
+        let doc = doc!{
+            "a": 1,
+            "b": "two",
+            "c": {"d": 3.5},
+            "e": [1, 2],
+            "f": (BinarySubtype::Generic, vec![1, 2, 3]),
+            "g": null,
+            "h": (Value::RegExp("^x".to_string(), "i".to_string()))
+        };
+        let bytes = doc.to_vec().unwrap();
+        let raw = RawDocument::new(&bytes).unwrap();
+
+        assert_eq!(raw.get_i32("a").unwrap(), Some(1));
+        assert_eq!(raw.get_str("b").unwrap(), Some("two"));
+        assert_eq!(raw.get_document("c").unwrap().unwrap().get_f64("d").unwrap(), Some(3.5));
+        assert_eq!(raw.get_binary("f").unwrap(), Some((BinarySubtype::Generic, &[1u8, 2, 3][..])));
+        assert_eq!(raw.get("g").unwrap(), Some(RawValue::Null));
+        assert_eq!(raw.get("h").unwrap(), Some(RawValue::RegExp("^x", "i")));
+        assert_eq!(raw.get("z").unwrap(), None);
+        assert!(matches!(raw.get_str("a"), Err(DecodeError::InvalidType(_))));
+
+        let keys: Vec<_> = raw.iter().map(|r| r.unwrap().0).collect();
+        assert_eq!(keys, vec!["a", "b", "c", "e", "f", "g", "h"]);
+        assert_eq!(raw.to_document().unwrap(), doc);
+    }
+
+    #[test]
+    fn malformed() {
+        let bytes = doc!{"a": "hello", "b": 1}.to_vec().unwrap();
+
+        assert!(RawDocument::new(&bytes[..bytes.len() - 1]).is_err());
+
+        // Claim a string longer than the document
+        let mut bad = bytes.clone();
+        bad[7] = 0x7F;
+        let raw = RawDocument::new(&bad).unwrap();
+        assert!(raw.get("b").is_err());
+        assert_eq!(raw.iter().count(), 1);
+    }
+}
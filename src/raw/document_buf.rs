@@ -0,0 +1,327 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::doc::Document;
+use crate::spec::BinarySubtype;
+use crate::encode::EncodeResult;
+use crate::spec::ElementType;
+use crate::decode::{DecodeError, DecodeResult};
+
+use super::{RawValue, RawDocument, RawIter, Iter, check_document};
+
+/// An owned, encoded document. Elements are appended straight onto the end
+/// of the buffer, so a document can be built without going through a
+/// `Document` first.
+#[derive(Clone, PartialEq)]
+pub struct RawDocumentBuf {
+    data: Vec<u8>,
+}
+
+impl RawDocumentBuf {
+    /// An empty document.
+    pub fn new() -> RawDocumentBuf {
+        RawDocumentBuf {
+            data: vec![5, 0, 0, 0, 0],
+        }
+    }
+
+    /// Take ownership of an encoded document. Like `RawDocument::new`, only
+    /// the length prefix and trailing null are checked.
+    pub fn from_bytes(data: Vec<u8>) -> DecodeResult<RawDocumentBuf> {
+        check_document(&data)?;
+        Ok(RawDocumentBuf { data })
+    }
+
+    pub fn from_document(doc: &Document) -> EncodeResult<RawDocumentBuf> {
+        Ok(RawDocumentBuf { data: doc.to_vec()? })
+    }
+
+    pub fn as_raw_document(&self) -> RawDocument<'_> {
+        RawDocument::from_bytes_unchecked(&self.data)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.as_raw_document().iter()
+    }
+
+    pub fn get(&self, key: &str) -> DecodeResult<Option<RawValue<'_>>> {
+        self.as_raw_document().get(key)
+    }
+
+    pub fn to_document(&self) -> DecodeResult<Document> {
+        self.as_raw_document().to_document()
+    }
+
+    /// Append an element to the end of the document. Keys are not checked
+    /// for duplicates.
+    ///
+    /// ```
+    /// use bsonrs::doc;
+    /// use bsonrs::raw::RawDocumentBuf;
+    ///
+    /// let mut raw = RawDocumentBuf::new();
+    /// raw.append("a", 1);
+    /// raw.append("b", "two");
+    ///
+    /// assert_eq!(raw.as_bytes(), &doc!{"a": 1, "b": "two"}.to_vec().unwrap()[..]);
+    /// ```
+    pub fn append<'v>(&mut self, key: &str, value: impl Into<RawValue<'v>>) {
+        let value = value.into();
+
+        // Drop the trailing null, write the element, then put it back
+        self.data.pop();
+        self.data.push(value.element_type() as u8);
+        write_cstr(&mut self.data, key);
+        write_value(&mut self.data, &value);
+        self.data.push(0);
+
+        self.fix_len();
+    }
+
+    /// Overwrite the value of `key` without moving any other bytes. Only
+    /// fixed-width values (Double, Int32, Int64, ObjectId, Boolean,
+    /// UTCDatetime, TimeStamp and Decimal128) can be replaced this way, and
+    /// only by a value of the same type. Returns `Ok(false)` if `key` isn't
+    /// present.
+    ///
+    /// ```
+    /// use bsonrs::doc;
+    /// use bsonrs::raw::RawDocumentBuf;
+    ///
+    /// let mut raw = RawDocumentBuf::from_document(&doc!{"n": 1, "s": "x"}).unwrap();
+    ///
+    /// assert!(raw.replace("n", 2).unwrap());
+    /// assert!(raw.replace("s", "y").is_err());
+    /// assert_eq!(raw.to_document().unwrap(), doc!{"n": 2, "s": "x"});
+    /// ```
+    pub fn replace<'v>(&mut self, key: &str, value: impl Into<RawValue<'v>>) -> DecodeResult<bool> {
+        let value = value.into();
+
+        let (_, element_type, range) = match self.find(key)? {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+
+        if !is_fixed_width(element_type) {
+            return Err(DecodeError::InvalidType(format!(
+                "`{}` is {:?}, which can't be replaced in place", key, element_type
+            )));
+        }
+
+        if value.element_type() != element_type {
+            return Err(DecodeError::InvalidType(format!(
+                "expected `{}` to be replaced by {:?}, found {:?}", key, element_type, value.element_type()
+            )));
+        }
+
+        let mut buf = Vec::with_capacity(range.len());
+        write_value(&mut buf, &value);
+        self.data[range].copy_from_slice(&buf);
+
+        Ok(true)
+    }
+
+    /// Remove the first element named `key`, shifting the rest of the
+    /// document down. Returns `Ok(false)` if `key` isn't present.
+    pub fn remove(&mut self, key: &str) -> DecodeResult<bool> {
+        match self.find(key)? {
+            Some((start, _, value)) => {
+                self.data.drain(start..value.end);
+                self.fix_len();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The offset of the element named `key`, its type, and where its value
+    /// sits in the buffer.
+    fn find(&self, key: &str) -> DecodeResult<Option<(usize, ElementType, Range<usize>)>> {
+        let base = self.data.as_ptr() as usize;
+
+        for element in RawIter::new(&self.data) {
+            let (k, element_type, bytes) = element?;
+
+            if k == key {
+                // The type byte sits just before the key
+                let start = k.as_ptr() as usize - base - 1;
+                let value = bytes.as_ptr() as usize - base;
+                return Ok(Some((start, element_type, value..value + bytes.len())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn fix_len(&mut self) {
+        let len = (self.data.len() as i32).to_le_bytes();
+        self.data[..4].copy_from_slice(&len);
+    }
+}
+
+fn is_fixed_width(element_type: ElementType) -> bool {
+    matches!(
+        element_type,
+        ElementType::Double | ElementType::Int32 | ElementType::Int64 | ElementType::ObjectId |
+        ElementType::Boolean | ElementType::UTCDatetime | ElementType::TimeStamp | ElementType::Decimal128
+    )
+}
+
+impl Default for RawDocumentBuf {
+    fn default() -> RawDocumentBuf {
+        RawDocumentBuf::new()
+    }
+}
+
+impl fmt::Debug for RawDocumentBuf {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "RawDocumentBuf({} bytes)", self.data.len())
+    }
+}
+
+impl<'a> IntoIterator for &'a RawDocumentBuf {
+    type Item = DecodeResult<(&'a str, RawValue<'a>)>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> From<&'a RawDocumentBuf> for RawValue<'a> {
+    fn from(doc: &'a RawDocumentBuf) -> RawValue<'a> {
+        RawValue::Document(doc.as_raw_document())
+    }
+}
+
+fn write_cstr(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as i32 + 1).to_le_bytes());
+    write_cstr(buf, s);
+}
+
+/// Write the encoded form of `value`, without the element header.
+fn write_value(buf: &mut Vec<u8>, value: &RawValue) {
+    match *value {
+        RawValue::Double(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        RawValue::String(v) | RawValue::JavaScriptCode(v) | RawValue::Symbol(v) => write_str(buf, v),
+        RawValue::Array(v) => buf.extend_from_slice(v.as_bytes()),
+        RawValue::Document(v) => buf.extend_from_slice(v.as_bytes()),
+        RawValue::Boolean(v) => buf.push(v as u8),
+        RawValue::RegExp(pat, opt) => {
+            write_cstr(buf, pat);
+            write_cstr(buf, opt);
+        }
+        RawValue::JavaScriptCodeWithScope(code, scope) => {
+            let len = 4 + 4 + code.len() + 1 + scope.as_bytes().len();
+            buf.extend_from_slice(&(len as i32).to_le_bytes());
+            write_str(buf, code);
+            buf.extend_from_slice(scope.as_bytes());
+        }
+        RawValue::Int32(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        RawValue::Int64(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        RawValue::TimeStamp(v) => buf.extend_from_slice(&u64::from(v).to_le_bytes()),
+        RawValue::Binary(BinarySubtype::BinaryOld, data) => {
+            buf.extend_from_slice(&(data.len() as i32 + 4).to_le_bytes());
+            buf.push(From::from(BinarySubtype::BinaryOld));
+            buf.extend_from_slice(&(data.len() as i32).to_le_bytes());
+            buf.extend_from_slice(data);
+        }
+        RawValue::Binary(subtype, data) => {
+            buf.extend_from_slice(&(data.len() as i32).to_le_bytes());
+            buf.push(From::from(subtype));
+            buf.extend_from_slice(data);
+        }
+        RawValue::ObjectId(ref id) => buf.extend_from_slice(&id.bytes()),
+        RawValue::UTCDatetime(v) => {
+            let millis = v.timestamp_millis();
+            buf.extend_from_slice(&millis.to_le_bytes());
+        }
+        RawValue::Decimal128(v) => buf.extend_from_slice(&v.bytes()),
+        RawValue::Null | RawValue::MinKey | RawValue::MaxKey | RawValue::Undefined => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RawDocumentBuf;
+    use crate::raw::{RawDocument, RawValue};
+    use crate::spec::BinarySubtype;
+    use crate::object_id::ObjectId;
+    use crate::doc;
+
+    #[test]
+    fn append() {
+        let id = ObjectId::new();
+        let inner = doc!{"x": 1}.to_vec().unwrap();
+
+        let mut raw = RawDocumentBuf::new();
+        assert_eq!(raw.as_bytes(), &doc!{}.to_vec().unwrap()[..]);
+
+        raw.append("a", 1.5);
+        raw.append("b", "two");
+        raw.append("c", RawDocument::new(&inner).unwrap());
+        raw.append("d", RawValue::Binary(BinarySubtype::BinaryOld, &[1, 2, 3]));
+        raw.append("e", RawValue::JavaScriptCodeWithScope("x", RawDocument::new(&inner).unwrap()));
+        raw.append("f", id.clone());
+        raw.append("g", RawValue::Null);
+        raw.append("h", i64::MAX);
+
+        let expected = doc!{
+            "a": 1.5,
+            "b": "two",
+            "c": {"x": 1},
+            "d": (BinarySubtype::BinaryOld, vec![1, 2, 3]),
+            "e": (crate::Value::JavaScriptCodeWithScope("x".to_string(), Box::new(doc!{"x": 1}))),
+            "f": id,
+            "g": null,
+            "h": (i64::MAX)
+        };
+
+        assert_eq!(raw.as_bytes(), &expected.to_vec().unwrap()[..]);
+        assert_eq!(raw.to_document().unwrap(), expected);
+
+        let copy = RawDocumentBuf::from_bytes(raw.clone().into_bytes()).unwrap();
+        assert_eq!(copy, raw);
+        assert!(RawDocumentBuf::from_bytes(vec![5, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn replace_and_remove() {
+        let id = ObjectId::new();
+        let mut raw = RawDocumentBuf::from_document(&doc!{"a": 1, "b": "x", "c": 2.5, "d": true}).unwrap();
+
+        assert!(raw.replace("a", 5).unwrap());
+        assert!(raw.replace("c", -1.0).unwrap());
+        assert!(raw.replace("d", false).unwrap());
+        assert!(!raw.replace("z", 1).unwrap());
+        assert!(raw.replace("a", 5i64).is_err());
+        assert!(raw.replace("b", "y").is_err());
+        assert_eq!(raw.to_document().unwrap(), doc!{"a": 5, "b": "x", "c": -1.0, "d": false});
+
+        assert!(raw.remove("b").unwrap());
+        assert!(!raw.remove("b").unwrap());
+        raw.append("e", id.clone());
+        assert!(raw.remove("a").unwrap());
+
+        let expected = doc!{"c": -1.0, "d": false, "e": id};
+        assert_eq!(raw.as_bytes(), &expected.to_vec().unwrap()[..]);
+
+        assert!(raw.remove("c").unwrap());
+        assert!(raw.remove("d").unwrap());
+        assert!(raw.remove("e").unwrap());
+        assert_eq!(raw, RawDocumentBuf::new());
+    }
+}
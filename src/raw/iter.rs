@@ -0,0 +1,96 @@
+use crate::spec::ElementType;
+use crate::decode::{DecodeError, DecodeResult};
+
+use super::{read_cstr, value_len, check_document};
+
+/// Walks the elements of an encoded document, yielding each key, element
+/// type and the bytes of its value. Nothing is decoded beyond what is needed
+/// to find where each element ends, and nothing is allocated.
+///
+/// The value bytes can be parsed with `RawValue::from_bytes`, copied into
+/// another buffer, or skipped entirely. Iteration stops after the first
+/// error.
+///
+/// ```
+/// use bsonrs::{doc, ElementType};
+/// use bsonrs::raw::RawIter;
+///
+/// let bytes = doc!{"a": 1, "b": "two", "c": 3}.to_vec().unwrap();
+///
+/// let ints = RawIter::new(&bytes)
+///     .filter_map(Result::ok)
+///     .filter(|&(_, element_type, _)| element_type == ElementType::Int32)
+///     .map(|(key, _, _)| key)
+///     .collect::<Vec<_>>();
+///
+/// assert_eq!(ints, vec!["a", "c"]);
+/// ```
+pub struct RawIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> RawIter<'a> {
+    /// Nothing is checked until the first call to `next`.
+    pub fn new(data: &'a [u8]) -> RawIter<'a> {
+        RawIter {
+            data,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    fn next_element(&mut self) -> DecodeResult<Option<(&'a str, ElementType, &'a [u8])>> {
+        if self.offset == 0 {
+            check_document(self.data)?;
+            self.offset = 4;
+        }
+
+        let tag = *self.data.get(self.offset).ok_or(DecodeError::EndOfStream)?;
+
+        if tag == 0 {
+            if self.offset + 1 != self.data.len() {
+                return Err(DecodeError::InvalidLength(
+                    self.data.len(),
+                    format!("document ends at byte {}", self.offset + 1)
+                ));
+            }
+
+            return Ok(None);
+        }
+
+        let element_type = ElementType::from(tag).ok_or(DecodeError::UnrecognizedElementType(tag))?;
+        let (key, key_len) = read_cstr(&self.data[self.offset + 1..])?;
+
+        let start = self.offset + 1 + key_len;
+        let len = value_len(element_type, &self.data[start..])?;
+
+        // The value must leave room for the document's trailing null
+        if start + len >= self.data.len() {
+            return Err(DecodeError::EndOfStream);
+        }
+
+        self.offset = start + len;
+
+        Ok(Some((key, element_type, &self.data[start..start + len])))
+    }
+}
+
+impl<'a> Iterator for RawIter<'a> {
+    type Item = DecodeResult<(&'a str, ElementType, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let next = self.next_element();
+
+        if !matches!(next, Ok(Some(_))) {
+            self.done = true;
+        }
+
+        next.transpose()
+    }
+}
@@ -0,0 +1,475 @@
+//! Borrowed views over encoded BSON
+//!
+//! `RawDocument` reads fields straight out of a `&[u8]` buffer, walking the
+//! element headers on demand instead of building a `Document`. Only the
+//! values actually asked for are decoded, which makes it cheap to pull one or
+//! two fields out of a large document.
+//!
+//! ```
+//! use bsonrs::doc;
+//! use bsonrs::raw::RawDocument;
+//!
+//! let bytes = doc!{"_id": 1, "name": "bson", "tags": ["a", "b"]}.to_vec().unwrap();
+//! let raw = RawDocument::new(&bytes).unwrap();
+//!
+//! assert_eq!(raw.get_str("name").unwrap(), Some("bson"));
+//! assert_eq!(raw.get_i32("missing").unwrap(), None);
+//! ```
+//!
+//! Arrays come back as `RawArray`, which is read the same way by index.
+//! `RawDocumentBuf` is the owned counterpart, and can also be built up one
+//! element at a time with `append` and edited with `replace` and `remove`.
+//! For anything these don't cover, `RawIter` walks the raw elements of a
+//! document directly.
+use std::str;
+
+use chrono::{DateTime, Utc};
+
+use crate::value::{Value, TimeStamp, clamped_datetime};
+use crate::spec::{ElementType, BinarySubtype};
+use crate::decode::{DecodeError, DecodeResult, DecodeOptions};
+use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
+
+// Typed lookups on `RawDocument` and `RawArray`: `Ok(None)` if the element
+// is missing, `InvalidType` if it holds something else.
+macro_rules! raw_getters {
+    ($key:ty; $($(#[$attr:meta])* $name:ident -> $ty:ty, $as:ident;)+) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(&self, key: $key) -> DecodeResult<Option<$ty>> {
+                match self.get(key)? {
+                    Some(value) => value.$as().map(Some).ok_or_else(|| {
+                        DecodeError::InvalidType(format!(
+                            "expected `{}` to be {}, found {:?}",
+                            key, stringify!($ty), value.element_type()
+                        ))
+                    }),
+                    None => Ok(None),
+                }
+            }
+        )+
+    };
+}
+
+mod array;
+mod document;
+mod document_buf;
+mod iter;
+
+pub use self::array::{RawArray, ArrayIter};
+pub use self::document::{RawDocument, Iter};
+pub use self::document_buf::RawDocumentBuf;
+pub use self::iter::RawIter;
+
+/// A single borrowed value inside a `RawDocument`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawValue<'a> {
+    Double(f64),
+    String(&'a str),
+    Array(RawArray<'a>),
+    Document(RawDocument<'a>),
+    Boolean(bool),
+    Null,
+    RegExp(&'a str, &'a str),
+    JavaScriptCode(&'a str),
+    JavaScriptCodeWithScope(&'a str, RawDocument<'a>),
+    Int32(i32),
+    Int64(i64),
+    TimeStamp(TimeStamp),
+    Binary(BinarySubtype, &'a [u8]),
+    ObjectId(ObjectId),
+    UTCDatetime(DateTime<Utc>),
+    Symbol(&'a str),
+    Decimal128(Decimal128),
+    MinKey,
+    MaxKey,
+    Undefined,
+}
+
+impl<'a> RawValue<'a> {
+    /// Parse a value of the given type from exactly the bytes it occupies.
+    pub fn from_bytes(element_type: ElementType, bytes: &'a [u8]) -> DecodeResult<RawValue<'a>> {
+        let value = match element_type {
+            ElementType::Double => RawValue::Double(f64::from_bits(read_i64(bytes, 0)? as u64)),
+            ElementType::Utf8String => RawValue::String(read_str(bytes)?),
+            ElementType::Document => RawValue::Document(RawDocument::new(bytes)?),
+            ElementType::Array => RawValue::Array(RawArray::new(bytes)?),
+            ElementType::Binary => {
+                let len = value_len(ElementType::Binary, bytes)? - 5;
+                let subtype = BinarySubtype::from(*bytes.get(4).ok_or(DecodeError::EndOfStream)?);
+                let mut data = bytes.get(5..5 + len).ok_or(DecodeError::EndOfStream)?;
+
+                // The old binary subtype repeats the payload length inside the data
+                if subtype == BinarySubtype::BinaryOld {
+                    if len < 4 {
+                        return Err(DecodeError::InvalidLength(len, format!("invalid length {} for old binary", len)));
+                    }
+
+                    let inner = read_i32(data, 0)?;
+                    if inner as usize != len - 4 {
+                        return Err(DecodeError::InvalidLength(
+                            inner as usize,
+                            format!("invalid inner length {} for old binary of length {}", inner, len)
+                        ));
+                    }
+
+                    data = &data[4..];
+                }
+
+                RawValue::Binary(subtype, data)
+            }
+            ElementType::Undefiend => RawValue::Undefined,
+            ElementType::ObjectId => {
+                let mut id = [0; 12];
+                id.copy_from_slice(bytes.get(..12).ok_or(DecodeError::EndOfStream)?);
+                RawValue::ObjectId(ObjectId::with_bytes(id))
+            }
+            ElementType::Boolean => RawValue::Boolean(*bytes.first().ok_or(DecodeError::EndOfStream)? != 0),
+            ElementType::UTCDatetime => {
+                RawValue::UTCDatetime(clamped_datetime(read_i64(bytes, 0)?))
+            }
+            ElementType::NullValue => RawValue::Null,
+            ElementType::RegularExpression => {
+                let (pat, len) = read_cstr(bytes)?;
+                let (opt, _) = read_cstr(&bytes[len..])?;
+                RawValue::RegExp(pat, opt)
+            }
+            ElementType::JavaScriptCode => RawValue::JavaScriptCode(read_str(bytes)?),
+            ElementType::Symbol => RawValue::Symbol(read_str(bytes)?),
+            ElementType::JavaScriptCodeWithScope => {
+                let code = read_str(bytes.get(4..).ok_or(DecodeError::EndOfStream)?)?;
+                let scope = bytes.get(8 + code.len() + 1..).ok_or(DecodeError::EndOfStream)?;
+                RawValue::JavaScriptCodeWithScope(code, RawDocument::new(scope)?)
+            }
+            ElementType::Int32 => RawValue::Int32(read_i32(bytes, 0)?),
+            ElementType::TimeStamp => RawValue::TimeStamp(TimeStamp::from(read_i64(bytes, 0)? as u64)),
+            ElementType::Int64 => RawValue::Int64(read_i64(bytes, 0)?),
+            ElementType::Decimal128 => {
+                let mut d = [0; 16];
+                d.copy_from_slice(bytes.get(..16).ok_or(DecodeError::EndOfStream)?);
+                RawValue::Decimal128(Decimal128::from_bytes(d))
+            }
+            ElementType::MinKey => RawValue::MinKey,
+            ElementType::MaxKey => RawValue::MaxKey,
+            ElementType::DBPointer => return Err(DecodeError::UnrecognizedElementType(element_type as u8)),
+        };
+
+        Ok(value)
+    }
+
+    pub fn element_type(&self) -> ElementType {
+        match self {
+            RawValue::Double(..) => ElementType::Double,
+            RawValue::String(..) => ElementType::Utf8String,
+            RawValue::Array(..) => ElementType::Array,
+            RawValue::Document(..) => ElementType::Document,
+            RawValue::Boolean(..) => ElementType::Boolean,
+            RawValue::Null => ElementType::NullValue,
+            RawValue::RegExp(..) => ElementType::RegularExpression,
+            RawValue::JavaScriptCode(..) => ElementType::JavaScriptCode,
+            RawValue::JavaScriptCodeWithScope(..) => ElementType::JavaScriptCodeWithScope,
+            RawValue::Int32(..) => ElementType::Int32,
+            RawValue::Int64(..) => ElementType::Int64,
+            RawValue::TimeStamp(..) => ElementType::TimeStamp,
+            RawValue::Binary(..) => ElementType::Binary,
+            RawValue::ObjectId(..) => ElementType::ObjectId,
+            RawValue::UTCDatetime(..) => ElementType::UTCDatetime,
+            RawValue::Symbol(..) => ElementType::Symbol,
+            RawValue::Decimal128(..) => ElementType::Decimal128,
+            RawValue::MinKey => ElementType::MinKey,
+            RawValue::MaxKey => ElementType::MaxKey,
+            RawValue::Undefined => ElementType::Undefiend,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            RawValue::Double(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            RawValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<RawArray<'a>> {
+        match *self {
+            RawValue::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_document(&self) -> Option<RawDocument<'a>> {
+        match *self {
+            RawValue::Document(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            RawValue::Boolean(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            RawValue::Int32(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            RawValue::Int64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_timestamp(&self) -> Option<TimeStamp> {
+        match *self {
+            RawValue::TimeStamp(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_binary(&self) -> Option<(BinarySubtype, &'a [u8])> {
+        match *self {
+            RawValue::Binary(t, v) => Some((t, v)),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_id(&self) -> Option<ObjectId> {
+        match *self {
+            RawValue::ObjectId(ref v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn as_utc_date_time(&self) -> Option<DateTime<Utc>> {
+        match *self {
+            RawValue::UTCDatetime(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_decimal128(&self) -> Option<Decimal128> {
+        match *self {
+            RawValue::Decimal128(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Decode into an owned `Value`, including everything nested inside it.
+    pub fn to_value(&self) -> DecodeResult<Value> {
+        self.to_value_with_options(DecodeOptions::default())
+    }
+
+    /// Documents and arrays are decoded as by
+    /// `RawDocument::to_document_with_options`, counting this value as the
+    /// outermost document.
+    pub fn to_value_with_options(&self, options: DecodeOptions) -> DecodeResult<Value> {
+        self.to_value_at(options, 1)
+    }
+
+    /// `depth` is the one the value is nested at if it is a document or
+    /// array, or the scope of code with scope.
+    pub(crate) fn to_value_at(&self, options: DecodeOptions, depth: usize) -> DecodeResult<Value> {
+        let value = match *self {
+            RawValue::Double(v) => Value::Double(v),
+            RawValue::String(v) => Value::String(v.to_string()),
+            RawValue::Array(v) => Value::Array(v.to_array_at(options, depth)?),
+            RawValue::Document(v) => Value::Document(v.to_document_at(options, depth)?),
+            RawValue::Boolean(v) => Value::Boolean(v),
+            RawValue::Null => Value::Null,
+            RawValue::RegExp(pat, opt) => Value::RegExp(pat.to_string(), opt.to_string()),
+            RawValue::JavaScriptCode(v) => Value::JavaScriptCode(v.to_string()),
+            RawValue::JavaScriptCodeWithScope(code, scope) => {
+                Value::JavaScriptCodeWithScope(code.to_string(), Box::new(scope.to_document_at(options, depth)?))
+            }
+            RawValue::Int32(v) => Value::Int32(v),
+            RawValue::Int64(v) => Value::Int64(v),
+            RawValue::TimeStamp(v) => Value::TimeStamp(v),
+            RawValue::Binary(t, v) => Value::Binary(t, v.to_vec()),
+            RawValue::ObjectId(ref v) => Value::ObjectId(v.clone()),
+            RawValue::UTCDatetime(v) => Value::UTCDatetime(v),
+            RawValue::Symbol(v) => Value::Symbol(v.to_string()),
+            RawValue::Decimal128(v) => Value::Decimal128(v),
+            RawValue::MinKey => Value::MinKey,
+            RawValue::MaxKey => Value::MaxKey,
+            RawValue::Undefined => Value::Undefined,
+        };
+
+        Ok(value)
+    }
+}
+
+macro_rules! raw_value_from_impls {
+    ($($t:ty => $variant:ident,)+) => {
+        $(
+            impl<'a> From<$t> for RawValue<'a> {
+                fn from(v: $t) -> RawValue<'a> {
+                    RawValue::$variant(v)
+                }
+            }
+        )+
+    };
+}
+
+raw_value_from_impls! {
+    f64 => Double,
+    &'a str => String,
+    RawArray<'a> => Array,
+    RawDocument<'a> => Document,
+    bool => Boolean,
+    i32 => Int32,
+    i64 => Int64,
+    ObjectId => ObjectId,
+    DateTime<Utc> => UTCDatetime,
+    Decimal128 => Decimal128,
+}
+
+impl<'a> From<&'a String> for RawValue<'a> {
+    fn from(s: &'a String) -> RawValue<'a> {
+        RawValue::String(s)
+    }
+}
+
+impl<'a> From<(BinarySubtype, &'a [u8])> for RawValue<'a> {
+    fn from((subtype, data): (BinarySubtype, &'a [u8])) -> RawValue<'a> {
+        RawValue::Binary(subtype, data)
+    }
+}
+
+/// Check the length prefix and trailing null of an encoded document.
+pub(crate) fn check_document(bytes: &[u8]) -> DecodeResult<()> {
+    let len = document_len(bytes)?;
+
+    if len != bytes.len() {
+        return Err(DecodeError::InvalidLength(
+            len,
+            format!("document declares {} bytes but {} are available", len, bytes.len())
+        ));
+    }
+
+    if bytes[len - 1] != 0 {
+        return Err(DecodeError::InvalidValue("document is not null terminated".to_string()));
+    }
+
+    Ok(())
+}
+
+fn document_len(bytes: &[u8]) -> DecodeResult<usize> {
+    let len = read_i32(bytes, 0)?;
+
+    if len < 5 {
+        return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for document", len)));
+    }
+
+    Ok(len as usize)
+}
+
+/// Number of bytes taken by a value of the given type at the start of
+/// `bytes`.
+pub(crate) fn value_len(element_type: ElementType, bytes: &[u8]) -> DecodeResult<usize> {
+    let len = match element_type {
+        ElementType::Double | ElementType::UTCDatetime | ElementType::TimeStamp | ElementType::Int64 => 8,
+        ElementType::Int32 => 4,
+        ElementType::Boolean => 1,
+        ElementType::ObjectId => 12,
+        ElementType::Decimal128 => 16,
+        ElementType::NullValue | ElementType::Undefiend | ElementType::MinKey | ElementType::MaxKey => 0,
+        ElementType::Utf8String | ElementType::JavaScriptCode | ElementType::Symbol => 4 + string_len(bytes)?,
+        ElementType::Document | ElementType::Array => document_len(bytes)?,
+        ElementType::JavaScriptCodeWithScope => {
+            let len = read_i32(bytes, 0)?;
+            if len < 14 {
+                return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for code with scope", len)));
+            }
+            len as usize
+        }
+        ElementType::Binary => {
+            let len = read_i32(bytes, 0)?;
+            if len < 0 {
+                return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for binary", len)));
+            }
+            5 + len as usize
+        }
+        ElementType::RegularExpression => {
+            let (_, pat) = read_cstr(bytes)?;
+            let (_, opt) = read_cstr(&bytes[pat..])?;
+            pat + opt
+        }
+        ElementType::DBPointer => 4 + string_len(bytes)? + 12,
+    };
+
+    if len > bytes.len() {
+        return Err(DecodeError::EndOfStream);
+    }
+
+    Ok(len)
+}
+
+fn string_len(bytes: &[u8]) -> DecodeResult<usize> {
+    let len = read_i32(bytes, 0)?;
+
+    if len < 1 {
+        return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for UTF-8 string", len)));
+    }
+
+    Ok(len as usize)
+}
+
+pub(crate) fn read_i32(bytes: &[u8], at: usize) -> DecodeResult<i32> {
+    match bytes.get(at..at + 4) {
+        Some(b) => Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(DecodeError::EndOfStream),
+    }
+}
+
+pub(crate) fn read_i64(bytes: &[u8], at: usize) -> DecodeResult<i64> {
+    match bytes.get(at..at + 8) {
+        Some(b) => Ok(i64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])),
+        None => Err(DecodeError::EndOfStream),
+    }
+}
+
+/// A length prefixed, null terminated string.
+fn read_str(bytes: &[u8]) -> DecodeResult<&str> {
+    utf8(read_str_bytes(bytes)?)
+}
+
+/// The contents of a length prefixed, null terminated string, without
+/// checking that they are UTF-8.
+pub(crate) fn read_str_bytes(bytes: &[u8]) -> DecodeResult<&[u8]> {
+    let len = string_len(bytes)?;
+    let data = bytes.get(4..4 + len).ok_or(DecodeError::EndOfStream)?;
+
+    if data[len - 1] != 0 {
+        return Err(DecodeError::InvalidValue("string is not null terminated".to_string()));
+    }
+
+    Ok(&data[..len - 1])
+}
+
+/// A null terminated string, and the number of bytes it took including the
+/// terminator.
+pub(crate) fn read_cstr(bytes: &[u8]) -> DecodeResult<(&str, usize)> {
+    let end = memchr::memchr(0, bytes).ok_or(DecodeError::EndOfStream)?;
+    Ok((utf8(&bytes[..end])?, end + 1))
+}
+
+/// Fails with the `FromUtf8Error` decoding the same bytes would give.
+pub(crate) fn utf8(bytes: &[u8]) -> DecodeResult<&str> {
+    match str::from_utf8(bytes) {
+        Ok(s) => Ok(s),
+        Err(_) => Err(String::from_utf8(bytes.to_vec()).unwrap_err().into()),
+    }
+}
@@ -0,0 +1,104 @@
+//! Dropping fields that carry no data
+//!
+//! Documents built from optional inputs, such as the `$set` part of an
+//! update, tend to collect `Null` fields and empty values that shouldn't be
+//! written. `Document::sanitize` removes them according to a
+//! `SanitizePolicy`; `Document::strip_nulls` is the common case.
+//!
+//! ```
+//! use bsonrs::doc;
+//! use bsonrs::sanitize::SanitizePolicy;
+//!
+//! let mut doc = doc!{"name": "a", "email": null, "tags": [], "address": {"city": ""}};
+//!
+//! let policy = SanitizePolicy { empty_strings: true, empty_documents: true, empty_arrays: true, ..Default::default() };
+//! doc.sanitize(policy);
+//!
+//! assert_eq!(doc, doc!{"name": "a"});
+//! ```
+//!
+//! Only fields are removed. Arrays keep all their elements so that
+//! positions don't shift, though documents inside them are still
+//! sanitized when the policy is recursive.
+use crate::value::Value;
+use crate::doc::Document;
+
+/// Which fields `sanitize` removes. The default removes `Null` fields at
+/// every level, like `Document::strip_nulls(true)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// Remove `Null` fields. Defaults to `true`.
+    pub nulls: bool,
+    /// Remove fields holding `""`. Defaults to `false`.
+    pub empty_strings: bool,
+    /// Remove fields holding `{}`, including documents emptied by this
+    /// policy. Defaults to `false`.
+    pub empty_documents: bool,
+    /// Remove fields holding `[]`. Defaults to `false`.
+    pub empty_arrays: bool,
+    /// Apply the policy to nested documents as well. Defaults to `true`.
+    pub recursive: bool,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> SanitizePolicy {
+        SanitizePolicy {
+            nulls: true,
+            empty_strings: false,
+            empty_documents: false,
+            empty_arrays: false,
+            recursive: true,
+        }
+    }
+}
+
+/// Remove the fields of `doc` that `policy` selects, the same as
+/// `Document::sanitize`.
+pub fn sanitize(doc: &mut Document, policy: SanitizePolicy) {
+    if policy.recursive {
+        for (_, value) in doc.iter_mut() {
+            sanitize_value(value, policy);
+        }
+    }
+
+    doc.retain(|_, value| !removed(value, policy));
+}
+
+fn sanitize_value(value: &mut Value, policy: SanitizePolicy) {
+    match value {
+        Value::Document(doc) => sanitize(doc, policy),
+        Value::Array(array) => {
+            for item in array.iter_mut() {
+                sanitize_value(item, policy);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn removed(value: &Value, policy: SanitizePolicy) -> bool {
+    match value {
+        Value::Null => policy.nulls,
+        Value::String(s) => policy.empty_strings && s.is_empty(),
+        Value::Document(doc) => policy.empty_documents && doc.is_empty(),
+        Value::Array(array) => policy.empty_arrays && array.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SanitizePolicy;
+    use crate::doc;
+
+    #[test]
+    fn sanitize() {
+        let mut doc = doc!{"a": {"b": null, "c": {}}, "d": [{"e": ""}], "f": "", "g": 0};
+
+        doc.sanitize(SanitizePolicy { empty_documents: true, ..Default::default() });
+        assert_eq!(doc, doc!{"d": [{"e": ""}], "f": "", "g": 0});
+
+        doc.sanitize(SanitizePolicy { empty_strings: true, empty_arrays: true, ..Default::default() });
+        assert_eq!(doc, doc!{"d": [{}], "g": 0});
+    }
+}
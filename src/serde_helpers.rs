@@ -0,0 +1,386 @@
+//! Helpers for use with `#[serde(with = "...")]`
+//!
+//! ```
+//! use std::net::IpAddr;
+//! use serde_derive::{Serialize, Deserialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Peer {
+//!     #[serde(with = "bsonrs::serde_helpers::ip_addr_as_binary")]
+//!     addr: IpAddr,
+//! }
+//! ```
+
+/// Serialize an `IpAddr` as a 4 or 16 byte binary instead of a string.
+pub mod ip_addr_as_binary {
+    use std::net::IpAddr;
+
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::Error;
+    use serde_bytes::ByteBuf;
+
+    use crate::value::{ip_octets, ip_from_octets};
+
+    pub fn serialize<S: Serializer>(addr: &IpAddr, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&ip_octets(*addr))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<IpAddr, D::Error> {
+        let bytes = ByteBuf::deserialize(deserializer)?;
+        ip_from_octets(&bytes).ok_or_else(|| D::Error::invalid_length(bytes.len(), &"4 or 16 bytes"))
+    }
+}
+
+/// Serialize a `SocketAddr` as the address octets followed by the port in
+/// big-endian order (6 or 18 bytes) instead of a string.
+pub mod socket_addr_as_binary {
+    use std::net::SocketAddr;
+
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::Error;
+    use serde_bytes::ByteBuf;
+
+    use crate::value::{Value, ip_octets};
+
+    pub fn serialize<S: Serializer>(addr: &SocketAddr, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = ip_octets(addr.ip());
+        bytes.extend_from_slice(&addr.port().to_be_bytes());
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SocketAddr, D::Error> {
+        let bytes = ByteBuf::deserialize(deserializer)?.into_vec();
+        let len = bytes.len();
+
+        Value::from(bytes).as_socket_addr().ok_or_else(|| D::Error::invalid_length(len, &"6 or 18 bytes"))
+    }
+}
+
+/// Serialize a `std::time::Duration` as `Int64` milliseconds.
+pub mod duration_as_millis {
+    use std::time::Duration;
+
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::{Error, Unexpected};
+
+    pub fn serialize<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(d.as_millis().min(i64::MAX as u128) as i64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let millis = i64::deserialize(deserializer)?;
+
+        if millis < 0 {
+            return Err(D::Error::invalid_value(Unexpected::Signed(millis), &"a non-negative duration"));
+        }
+
+        Ok(Duration::from_millis(millis as u64))
+    }
+}
+
+/// Serialize an `ObjectId` as its 24 character hex string.
+pub mod object_id_as_hex_string {
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::{Error, Unexpected};
+
+    use crate::object_id::ObjectId;
+
+    pub fn serialize<S: Serializer>(oid: &ObjectId, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&oid.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ObjectId, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        ObjectId::with_string(&hex).map_err(|_| D::Error::invalid_value(Unexpected::Str(&hex), &"an ObjectId hex string"))
+    }
+}
+
+/// Serialize a `chrono::DateTime<Utc>` as a BSON datetime instead of a
+/// string.
+pub mod chrono_datetime_as_bson_datetime {
+    use chrono::{DateTime, Utc};
+    use serde::{Serialize, Serializer, Deserializer, Deserialize};
+
+    use crate::value::UTCDateTime;
+
+    pub fn serialize<S: Serializer>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        UTCDateTime(*dt).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        UTCDateTime::deserialize(deserializer).map(|dt| dt.0)
+    }
+}
+
+/// Store an RFC 3339 `String` as a BSON datetime. Plain `String` fields
+/// don't accept datetimes.
+pub mod datetime_as_rfc3339_string {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{Serialize, Serializer, Deserializer, Deserialize};
+    use serde::ser::Error;
+
+    use crate::value::UTCDateTime;
+
+    pub fn serialize<S: Serializer>(s: &str, serializer: S) -> Result<S::Ok, S::Error> {
+        let dt = DateTime::parse_from_rfc3339(s).map_err(S::Error::custom)?;
+        UTCDateTime(dt.with_timezone(&Utc)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        UTCDateTime::deserialize(deserializer).map(|dt| dt.0.to_rfc3339_opts(SecondsFormat::Millis, true))
+    }
+}
+
+/// Serialize a `u32` as `Int64`, which always holds it.
+pub mod u32_as_i64 {
+    use std::convert::TryFrom;
+
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::{Error, Unexpected};
+
+    pub fn serialize<S: Serializer>(v: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(i64::from(*v))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        let v = i64::deserialize(deserializer)?;
+        u32::try_from(v).map_err(|_| D::Error::invalid_value(Unexpected::Signed(v), &"a u32"))
+    }
+}
+
+/// Serialize a `u64` as `Int64`, failing for values above `i64::MAX`.
+pub mod u64_as_i64 {
+    use std::convert::TryFrom;
+
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::ser::Error as _;
+    use serde::de::{Error, Unexpected};
+
+    pub fn serialize<S: Serializer>(v: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        match i64::try_from(*v) {
+            Ok(v) => serializer.serialize_i64(v),
+            Err(_) => Err(S::Error::custom(format!("{} does not fit in Int64", v))),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let v = i64::deserialize(deserializer)?;
+        u64::try_from(v).map_err(|_| D::Error::invalid_value(Unexpected::Signed(v), &"a u64"))
+    }
+}
+
+/// Serialize a `Vec<u8>` as generic `Binary` instead of an array of
+/// integers.
+pub mod bytes_as_binary {
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde_bytes::ByteBuf;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        ByteBuf::deserialize(deserializer).map(ByteBuf::into_vec)
+    }
+}
+
+/// Serialize a `u64` as a BSON timestamp, the high 32 bits being the time
+/// and the low 32 bits the increment.
+pub mod u64_as_timestamp {
+    use serde::{Serialize, Serializer, Deserializer, Deserialize};
+
+    use crate::value::TimeStamp;
+
+    pub fn serialize<S: Serializer>(v: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        TimeStamp::from(*v).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        TimeStamp::deserialize(deserializer).map(u64::from)
+    }
+}
+
+/// Serialize a `Uuid` as a `BinarySubtype::Uuid` binary instead of a string.
+///
+/// This is the only way to get subtype 4 out of serde: `Uuid`'s own
+/// `Serialize` impl writes a string, so plain `Uuid` fields are stored as
+/// strings. Plain fields do read either form.
+#[cfg(feature = "uuid")]
+pub mod uuid_as_binary {
+    use serde::{Serialize, Serializer, Deserializer, Deserialize};
+    use serde::de::Error;
+    use uuid::Uuid;
+
+    use crate::value::{Binary, Value};
+    use crate::spec::BinarySubtype;
+
+    pub fn serialize<S: Serializer>(u: &Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+        Binary { subtype: BinarySubtype::Uuid, bytes: u.as_bytes().to_vec() }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Uuid, D::Error> {
+        let binary = Binary::deserialize(deserializer)?;
+        let len = binary.bytes.len();
+
+        Value::from(binary).as_uuid().ok_or_else(|| D::Error::invalid_length(len, &"a 16 byte UUID binary"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, SocketAddr};
+
+    use serde_derive::{Serialize, Deserialize};
+
+    use crate::encode::to_bson;
+    use crate::decode::from_bson;
+    use crate::spec::BinarySubtype;
+    use crate::doc;
+    use crate::Value;
+    use crate::value::TimeStamp;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Peer {
+        #[serde(with = "super::ip_addr_as_binary")]
+        ip: IpAddr,
+        #[serde(with = "super::socket_addr_as_binary")]
+        addr: SocketAddr,
+        plain: IpAddr,
+    }
+
+    #[test]
+    fn network_addresses() {
+        let peer = Peer {
+            ip: "10.0.0.1".parse().unwrap(),
+            addr: "10.0.0.1:80".parse().unwrap(),
+            plain: "::1".parse().unwrap(),
+        };
+
+        let value = to_bson(&peer).unwrap();
+        assert_eq!(value, Value::Document(doc!{
+            "ip": (BinarySubtype::Generic, vec![10, 0, 0, 1]),
+            "addr": (BinarySubtype::Generic, vec![10, 0, 0, 1, 0, 80]),
+            "plain": "::1"
+        }));
+
+        assert_eq!(from_bson::<Peer>(value).unwrap(), peer);
+    }
+
+    #[test]
+    fn duration_as_millis() {
+        use std::time::Duration;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Timeout {
+            #[serde(with = "super::duration_as_millis")]
+            timeout: Duration,
+        }
+
+        let timeout = Timeout { timeout: Duration::from_millis(1500) };
+        let value = to_bson(&timeout).unwrap();
+
+        assert_eq!(value, Value::Document(doc!{"timeout": 1500i64}));
+        assert_eq!(from_bson::<Timeout>(value).unwrap(), timeout);
+        assert!(from_bson::<Timeout>(Value::Document(doc!{"timeout": -1i64})).is_err());
+    }
+
+    #[test]
+    fn bson_types() {
+        use chrono::{TimeZone, Utc, DateTime};
+        use crate::object_id::ObjectId;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Record {
+            #[serde(with = "super::object_id_as_hex_string")]
+            id: ObjectId,
+            #[serde(with = "super::chrono_datetime_as_bson_datetime")]
+            at: DateTime<Utc>,
+            #[serde(with = "super::u32_as_i64")]
+            small: u32,
+            #[serde(with = "super::u64_as_i64")]
+            big: u64,
+            #[serde(with = "super::bytes_as_binary")]
+            data: Vec<u8>,
+            #[serde(with = "super::u64_as_timestamp")]
+            ts: u64,
+        }
+
+        let id = ObjectId::new();
+        let at = Utc.timestamp_millis_opt(1_500_000_000_123).unwrap();
+        let record = Record { id: id.clone(), at, small: u32::MAX, big: 5, data: vec![1, 2], ts: 7 << 32 | 3 };
+
+        let value = to_bson(&record).unwrap();
+        assert_eq!(value, Value::Document(doc!{
+            "id": (id.to_hex()),
+            "at": at,
+            "small": (u32::MAX as i64),
+            "big": 5i64,
+            "data": (BinarySubtype::Generic, vec![1, 2]),
+            "ts": (Value::TimeStamp(TimeStamp::new(7, 3)))
+        }));
+        assert_eq!(from_bson::<Record>(value).unwrap(), record);
+
+        let record = Record { big: u64::MAX, ..record };
+        assert!(to_bson(&record).is_err());
+    }
+
+    #[test]
+    fn datetime_as_rfc3339_string() {
+        use chrono::{TimeZone, Utc};
+        use crate::decode::from_slice_borrowed;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Event {
+            #[serde(with = "super::datetime_as_rfc3339_string")]
+            at: String,
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct Plain {
+            #[allow(dead_code)]
+            at: String,
+        }
+
+        let at = Utc.timestamp_millis_opt(1_500_000_000_123).unwrap();
+        let event = Event { at: "2017-07-14T02:40:00.123Z".to_string() };
+
+        let value = to_bson(&event).unwrap();
+        assert_eq!(value, Value::Document(doc!{"at": at}));
+        assert_eq!(from_bson::<Event>(value.clone()).unwrap(), event);
+        assert!(to_bson(&Event { at: "yesterday".to_string() }).is_err());
+
+        // Without the helper a datetime is no string
+        let bytes = value.as_document().unwrap().to_vec().unwrap();
+        assert!(from_bson::<Plain>(value).is_err());
+        assert!(from_slice_borrowed::<Plain>(&bytes).is_err());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_as_binary() {
+        use uuid::Uuid;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct User {
+            #[serde(with = "super::uuid_as_binary")]
+            id: Uuid,
+            plain: Uuid,
+        }
+
+        let id = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        let user = User { id, plain: id };
+
+        // The plain field is written as a string, only the helper gives binary
+        let value = to_bson(&user).unwrap();
+        assert_eq!(value, Value::Document(doc!{"id": id, "plain": (id.to_string())}));
+        assert_eq!(from_bson::<User>(value).unwrap(), user);
+
+        let doc = doc!{"id": id, "plain": id};
+        assert_eq!(doc.get_uuid("plain").unwrap(), id);
+        assert_eq!(from_bson::<User>(Value::Document(doc)).unwrap(), user);
+
+        let doc = doc!{"id": (BinarySubtype::Generic, id.as_bytes().to_vec()), "plain": id};
+        assert!(from_bson::<User>(Value::Document(doc.clone())).is_err());
+        assert!(doc.get_uuid("id").is_err());
+    }
+}
@@ -6,26 +6,33 @@ use std::{i32, u32};
 
 use serde::de::{self, Deserialize, Deserializer, Visitor, MapAccess, SeqAccess, VariantAccess,
                 DeserializeSeed, EnumAccess};
-use serde::de::{Error, Expected, Unexpected};
+use serde::de::{Error, Expected, Unexpected, IntoDeserializer};
+use serde::de::value::StrDeserializer;
 
 use indexmap::IndexMap;
+use chrono::{DateTime, Utc, SecondsFormat};
 
-use crate::value::{Value, Array, UTCDateTime, TimeStamp};
+use crate::value::{Value, Array, UTCDateTime, TimeStamp, Binary};
+use crate::spec::{BinarySubtype, ElementType};
+use crate::decimal128::Decimal128;
 use crate::doc::{Document, IntoIter};
 use crate::decode::DecodeError;
 use crate::decode::DecodeResult;
+use crate::decode::{DecodeOptions, DuplicateKeys};
+use crate::decode::{check_depth, decode_value_slice};
+use crate::raw::{RawValue, RawIter};
 
 impl de::Error for DecodeError {
     fn custom<T: fmt::Display>(msg: T) -> DecodeError {
         DecodeError::Unknown(msg.to_string())
     }
 
-    fn invalid_type(_unexp: Unexpected, exp: &dyn Expected) -> DecodeError {
-        DecodeError::InvalidType(exp.to_string())
+    fn invalid_type(unexp: Unexpected, exp: &dyn Expected) -> DecodeError {
+        DecodeError::InvalidType(format!("invalid type: {}, expected {}", unexp, exp))
     }
 
-    fn invalid_value(_unexp: Unexpected, exp: &dyn Expected) -> DecodeError {
-        DecodeError::InvalidValue(exp.to_string())
+    fn invalid_value(unexp: Unexpected, exp: &dyn Expected) -> DecodeError {
+        DecodeError::InvalidValue(format!("invalid value: {}, expected {}", unexp, exp))
     }
 
     fn invalid_length(len: usize, exp: &dyn Expected) -> DecodeError {
@@ -181,6 +188,16 @@ impl<'de> Visitor<'de> for ValueVisitor {
         Ok(Value::Null)
     }
 
+    #[inline]
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Value, E> {
+        Ok(Value::Binary(BinarySubtype::Generic, value.to_vec()))
+    }
+
+    #[inline]
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Binary(BinarySubtype::Generic, value))
+    }
+
     #[inline]
     fn visit_seq<V>(self, mut visitor: V) -> Result<Value, V::Error>
         where V: SeqAccess<'de>
@@ -245,17 +262,104 @@ impl<'de> Visitor<'de> for DocumentVisitor {
     }
 }
 
+/// Prefix the path of `err` with `segment`, a key or an `[index]`, so an
+/// error surfacing from deep inside nested documents says where it came from.
+fn in_field(err: DecodeError, segment: String) -> DecodeError {
+    match err {
+        DecodeError::Field { path, error } => {
+            let sep = if path.starts_with('[') { "" } else { "." };
+            DecodeError::Field { path: format!("{}{}{}", segment, sep, path), error }
+        }
+        err => DecodeError::Field { path: segment, error: Box::new(err) },
+    }
+}
+
 /// Serde Decoder
 pub struct Decoder {
     value: Option<Value>,
+    options: DecodeOptions,
 }
 
 impl Decoder {
     pub fn new(value: Value) -> Decoder {
-        Decoder { value: Some(value) }
+        Decoder::with_options(value, DecodeOptions::default())
+    }
+
+    pub fn with_options(value: Value, options: DecodeOptions) -> Decoder {
+        Decoder { value: Some(value), options }
+    }
+
+    #[inline]
+    fn null_as_default(&self) -> bool {
+        self.options.null_as_default && self.value == Some(Value::Null)
+    }
+}
+
+macro_rules! null_as_default {
+    ($(
+        $name:ident => $visit:ident ( $default:expr );
+    )*) => {
+        $(
+            #[inline]
+            fn $name<V>(self, visitor: V) -> DecodeResult<V::Value>
+                where V: Visitor<'de>
+            {
+                if self.null_as_default() {
+                    return visitor.$visit($default);
+                }
+
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+macro_rules! lenient_integer {
+    ($double:path; $(
+        $name:ident => $visit:ident;
+    )*) => {
+        $(
+            #[inline]
+            fn $name<V>(self, visitor: V) -> DecodeResult<V::Value>
+                where V: Visitor<'de>
+            {
+                if self.null_as_default() {
+                    return visitor.$visit(0);
+                }
+
+                match self.value {
+                    Some($double(v)) if self.options.lenient_numerics => visit_integral(v, visitor),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+/// Hand a whole `f64` to the visitor as the integer it equals. Anything else
+/// is visited as a float, for the visitor to reject.
+fn visit_integral<'de, V>(v: f64, visitor: V) -> DecodeResult<V::Value>
+    where V: Visitor<'de>
+{
+    // 2^64 and -2^63, both exact as f64
+    const U64_END: f64 = 18_446_744_073_709_551_616.0;
+    const I64_MIN: f64 = -9_223_372_036_854_775_808.0;
+
+    if v.fract() != 0.0 {
+        visitor.visit_f64(v)
+    } else if (0.0..U64_END).contains(&v) {
+        visitor.visit_u64(v as u64)
+    } else if (I64_MIN..0.0).contains(&v) {
+        visitor.visit_i64(v as i64)
+    } else {
+        visitor.visit_f64(v)
     }
 }
 
+fn datetime_string(dt: &DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
 macro_rules! forward_to_deserialize {
     ($(
         $name:ident ( $( $arg:ident : $ty:ty ),* );
@@ -314,6 +418,8 @@ impl<'de> Deserializer<'de> for Decoder {
                     SeqDecoder {
                         iter: v.into_iter(),
                         len,
+                        index: 0,
+                        options: self.options,
                     }
                 )
             }
@@ -324,6 +430,7 @@ impl<'de> Deserializer<'de> for Decoder {
                         iter: v.into_iter(),
                         value: None,
                         len,
+                        options: self.options,
                     }
                 )
             }
@@ -340,6 +447,7 @@ impl<'de> Deserializer<'de> for Decoder {
                         iter: doc.into_iter(),
                         value: None,
                         len,
+                        options: self.options,
                     }
                 )
             }
@@ -351,7 +459,7 @@ impl<'de> Deserializer<'de> for Decoder {
         where V: Visitor<'de>
     {
         match self.value {
-            Some(Value::Null) => visitor.visit_none(),
+            Some(Value::Null) | Some(Value::Undefined) => visitor.visit_none(),
             Some(_) => visitor.visit_some(self),
             None => Err(DecodeError::EndOfStream),
         }
@@ -372,7 +480,7 @@ impl<'de> Deserializer<'de> for Decoder {
                 return visitor.visit_enum(
                     EnumDecoder {
                         val: Value::String(variant),
-                        decoder: VariantDecoder { val: None },
+                        decoder: VariantDecoder { val: None, options: self.options },
                     }
                 );
             }
@@ -400,7 +508,7 @@ impl<'de> Deserializer<'de> for Decoder {
                 visitor.visit_enum(
                     EnumDecoder {
                         val: Value::String(variant),
-                        decoder: VariantDecoder { val: Some(value) },
+                        decoder: VariantDecoder { val: Some(value), options: self.options },
                     }
                 )
             }
@@ -409,41 +517,142 @@ impl<'de> Deserializer<'de> for Decoder {
 
     #[inline]
     fn deserialize_newtype_struct<V>(
-        self,
-        _name: &'static str,
+        mut self,
+        name: &'static str,
         visitor: V
     ) -> DecodeResult<V::Value>
         where V: Visitor<'de>
     {
+        // `Binary` needs the subtype, which visiting bytes would drop
+        if name == BINARY_NEWTYPE {
+            if let Some(value @ Value::Binary(..)) = self.value.take() {
+                let doc = value.to_extended_document();
+                let len = doc.len();
+                return visitor.visit_map(
+                    MapDecoder {
+                        iter: doc.into_iter(),
+                        value: None,
+                        len,
+                        options: self.options,
+                    }
+                );
+            }
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
+    #[inline]
+    fn deserialize_seq<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        if self.null_as_default() {
+            return visitor.visit_seq(SeqDecoder::empty(self.options));
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        if self.null_as_default() {
+            return visitor.visit_map(MapDecoder::empty(self.options));
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    null_as_default!{
+        deserialize_bool => visit_bool(false);
+        deserialize_f32 => visit_f32(0.0);
+        deserialize_f64 => visit_f64(0.0);
+        deserialize_bytes => visit_bytes(&[]);
+        deserialize_byte_buf => visit_byte_buf(Vec::new());
+    }
+
+    /// Datetimes are handed over as RFC 3339 strings, which is what chrono's
+    /// `Deserialize` impls parse. `String` goes through `deserialize_string`
+    /// instead, so a datetime stays a type mismatch for `String` fields.
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        match self.value {
+            Some(Value::UTCDatetime(dt)) => visitor.visit_string(datetime_string(&dt)),
+            _ => self.deserialize_string(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        if self.null_as_default() {
+            return visitor.visit_str("");
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    lenient_integer!{
+        Value::Double;
+        deserialize_u8 => visit_u8;
+        deserialize_u16 => visit_u16;
+        deserialize_u32 => visit_u32;
+        deserialize_u64 => visit_u64;
+        deserialize_i8 => visit_i8;
+        deserialize_i16 => visit_i16;
+        deserialize_i32 => visit_i32;
+        deserialize_i64 => visit_i64;
+    }
+
+    /// Also accepts the `Decimal128` and string forms `i128` is encoded as.
+    fn deserialize_i128<V>(mut self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        match self.value.take() {
+            Some(Value::Decimal128(v)) => match v.to_i128() {
+                Some(v) => visitor.visit_i128(v),
+                None => Err(DecodeError::InvalidValue(format!("{} is not an i128", v))),
+            },
+            Some(Value::String(v)) => match v.parse() {
+                Ok(v) => visitor.visit_i128(v),
+                Err(_) => Err(DecodeError::InvalidValue(format!("{:?} is not an i128", v))),
+            },
+            Some(Value::Null) if self.options.null_as_default => visitor.visit_i128(0),
+            value => Decoder { value, options: self.options }.deserialize_any(visitor),
+        }
+    }
+
+    /// Also accepts the `Decimal128` and string forms `u128` is encoded as.
+    fn deserialize_u128<V>(mut self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        match self.value.take() {
+            Some(Value::Decimal128(v)) => match v.to_u128() {
+                Some(v) => visitor.visit_u128(v),
+                None => Err(DecodeError::InvalidValue(format!("{} is not a u128", v))),
+            },
+            Some(Value::String(v)) => match v.parse() {
+                Ok(v) => visitor.visit_u128(v),
+                Err(_) => Err(DecodeError::InvalidValue(format!("{:?} is not a u128", v))),
+            },
+            Some(Value::Null) if self.options.null_as_default => visitor.visit_u128(0),
+            value => Decoder { value, options: self.options }.deserialize_any(visitor),
+        }
+    }
+
     forward_to_deserialize!{
-        deserialize_bool();
-        deserialize_u8();
-        deserialize_u16();
-        deserialize_u32();
-        deserialize_u64();
-        deserialize_i8();
-        deserialize_i16();
-        deserialize_i32();
-        deserialize_i64();
-        deserialize_f32();
-        deserialize_f64();
         deserialize_char();
-        deserialize_str();
-        deserialize_string();
         deserialize_unit();
-        deserialize_seq();
-        deserialize_bytes();
-        deserialize_map();
         deserialize_unit_struct(name: &'static str);
         deserialize_tuple_struct(name: &'static str, len: usize);
         deserialize_struct(name: &'static str, fields: &'static [&'static str]);
         deserialize_tuple(len: usize);
         deserialize_identifier();
         deserialize_ignored_any();
-        deserialize_byte_buf();
     }
 }
 
@@ -458,7 +667,7 @@ impl<'de> EnumAccess<'de> for EnumDecoder {
     fn variant_seed<V>(self, seed: V) -> DecodeResult<(V::Value, Self::Variant)>
         where V: DeserializeSeed<'de>
     {
-        let dec = Decoder::new(self.val);
+        let dec = Decoder::with_options(self.val, self.decoder.options);
         let value = seed.deserialize(dec)?;
         Ok((value, self.decoder))
     }
@@ -466,6 +675,7 @@ impl<'de> EnumAccess<'de> for EnumDecoder {
 
 struct VariantDecoder {
     val: Option<Value>,
+    options: DecodeOptions,
 }
 
 impl<'de> VariantAccess<'de> for VariantDecoder {
@@ -475,7 +685,7 @@ impl<'de> VariantAccess<'de> for VariantDecoder {
         match self.val.take() {
             None => Ok(()),
             Some(val) => {
-                Value::deserialize(Decoder::new(val)).map(|_| ())
+                Value::deserialize(Decoder::with_options(val, self.options)).map(|_| ())
             }
         }
     }
@@ -483,7 +693,7 @@ impl<'de> VariantAccess<'de> for VariantDecoder {
     fn newtype_variant_seed<T>(mut self, seed: T) -> DecodeResult<T::Value>
         where T: DeserializeSeed<'de>
     {
-        let dec = Decoder::new(self.val.take().ok_or(DecodeError::EndOfStream)?);
+        let dec = Decoder::with_options(self.val.take().ok_or(DecodeError::EndOfStream)?, self.options);
         seed.deserialize(dec)
     }
 
@@ -495,6 +705,8 @@ impl<'de> VariantAccess<'de> for VariantDecoder {
             let de = SeqDecoder {
                 len: fields.len(),
                 iter: fields.into_iter(),
+                index: 0,
+                options: self.options,
             };
             de.deserialize_any(visitor)
         } else {
@@ -514,6 +726,7 @@ impl<'de> VariantAccess<'de> for VariantDecoder {
                 len: fields.len(),
                 iter: fields.into_iter(),
                 value: None,
+                options: self.options,
             };
             de.deserialize_any(visitor)
         } else {
@@ -525,6 +738,19 @@ impl<'de> VariantAccess<'de> for VariantDecoder {
 struct SeqDecoder {
     iter: vec::IntoIter<Value>,
     len: usize,
+    index: usize,
+    options: DecodeOptions,
+}
+
+impl SeqDecoder {
+    fn empty(options: DecodeOptions) -> SeqDecoder {
+        SeqDecoder {
+            iter: Vec::new().into_iter(),
+            len: 0,
+            index: 0,
+            options,
+        }
+    }
 }
 
 impl<'de> Deserializer<'de> for SeqDecoder {
@@ -583,10 +809,11 @@ impl<'de> SeqAccess<'de> for SeqDecoder {
             None => Ok(None),
             Some(value) => {
                 self.len -= 1;
-                let de = Decoder::new(value);
+                self.index += 1;
+                let de = Decoder::with_options(value, self.options);
                 match seed.deserialize(de) {
                     Ok(value) => Ok(Some(value)),
-                    Err(err) => Err(err),
+                    Err(err) => Err(in_field(err, format!("[{}]", self.index - 1))),
                 }
             }
         }
@@ -599,8 +826,22 @@ impl<'de> SeqAccess<'de> for SeqDecoder {
 
 struct MapDecoder {
     iter: IntoIter<String, Value>,
-    value: Option<Value>,
+    /// The value for the key just read, and that key, which names the field
+    /// if the value fails to deserialize.
+    value: Option<(String, Value)>,
     len: usize,
+    options: DecodeOptions,
+}
+
+impl MapDecoder {
+    fn empty(options: DecodeOptions) -> MapDecoder {
+        MapDecoder {
+            iter: Document::new().into_iter(),
+            value: None,
+            len: 0,
+            options,
+        }
+    }
 }
 
 impl<'de> MapAccess<'de> for MapDecoder {
@@ -612,9 +853,10 @@ impl<'de> MapAccess<'de> for MapDecoder {
         match self.iter.next() {
             Some((key, value)) => {
                 self.len -= 1;
-                self.value = Some(value);
 
-                let de = Decoder::new(Value::String(key));
+                // The key is lent to the seed so it can be kept for the path
+                let (key, _) = self.value.insert((key, value));
+                let de: StrDeserializer<'_, DecodeError> = key.as_str().into_deserializer();
                 match seed.deserialize(de) {
                     Ok(val) => Ok(Some(val)),
                     Err(DecodeError::UnknownField(_)) => Ok(None),
@@ -628,9 +870,9 @@ impl<'de> MapAccess<'de> for MapDecoder {
     fn next_value_seed<V>(&mut self, seed: V) -> DecodeResult<V::Value>
         where V: DeserializeSeed<'de>
     {
-        let value = self.value.take().ok_or(DecodeError::EndOfStream)?;
-        let de = Decoder::new(value);
-        seed.deserialize(de)
+        let (key, value) = self.value.take().ok_or(DecodeError::EndOfStream)?;
+        let de = Decoder::with_options(value, self.options);
+        seed.deserialize(de).map_err(|err| in_field(err, key))
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -680,6 +922,447 @@ impl<'de> Deserializer<'de> for MapDecoder {
     }
 }
 
+/// Serde Decoder that reads straight from an encoded buffer. Strings and
+/// binary data are handed to the visitor as borrows of the input, so
+/// `&'de str` and `&'de [u8]` fields don't copy.
+pub struct RawDecoder<'de> {
+    value: Option<RawValue<'de>>,
+    options: DecodeOptions,
+    /// How deeply the value is nested if it is a document or array,
+    /// counting the outermost document as 1.
+    depth: usize,
+}
+
+impl<'de> RawDecoder<'de> {
+    pub fn new(value: RawValue<'de>) -> RawDecoder<'de> {
+        RawDecoder::with_options(value, DecodeOptions::default())
+    }
+
+    /// Documents and arrays nested deeper than `options.max_depth` fail
+    /// with `DecodeError::DepthLimitExceeded`.
+    pub fn with_options(value: RawValue<'de>, options: DecodeOptions) -> RawDecoder<'de> {
+        RawDecoder::nested(value, options, 1)
+    }
+
+    fn nested(value: RawValue<'de>, options: DecodeOptions, depth: usize) -> RawDecoder<'de> {
+        RawDecoder { value: Some(value), options, depth }
+    }
+
+    #[inline]
+    fn null_as_default(&self) -> bool {
+        self.options.null_as_default && self.value == Some(RawValue::Null)
+    }
+}
+
+impl<'de> Deserializer<'de> for RawDecoder<'de> {
+    type Error = DecodeError;
+
+    #[inline]
+    fn deserialize_any<V>(mut self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        let value = match self.value.take() {
+            Some(value) => value,
+            None => return Err(DecodeError::EndOfStream),
+        };
+
+        match value {
+            RawValue::Double(v) => visitor.visit_f64(v),
+            RawValue::String(v) => visitor.visit_borrowed_str(v),
+            RawValue::Array(v) => {
+                check_depth(self.depth, self.options)?;
+
+                visitor.visit_seq(
+                    RawSeqDecoder {
+                        iter: RawIter::new(v.as_bytes()),
+                        index: 0,
+                        options: self.options,
+                        depth: self.depth,
+                    }
+                )
+            }
+            RawValue::Document(v) => {
+                check_depth(self.depth, self.options)?;
+
+                visitor.visit_map(RawMapDecoder::new(v.as_bytes(), self.options, self.depth)?)
+            }
+            RawValue::Boolean(v) => visitor.visit_bool(v),
+            RawValue::Null => visitor.visit_unit(),
+            RawValue::Int32(v) => visitor.visit_i32(v),
+            RawValue::Int64(v) => visitor.visit_i64(v),
+            RawValue::Binary(_, v) => visitor.visit_borrowed_bytes(v),
+            // Everything else goes through its extended JSON form, which
+            // needs an owned value anyway
+            _ => Decoder::with_options(value.to_value_at(self.options, self.depth)?, self.options).deserialize_any(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        match self.value {
+            Some(RawValue::Null) | Some(RawValue::Undefined) => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+            None => Err(DecodeError::EndOfStream),
+        }
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(
+        mut self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V
+    ) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        let value = match self.value.take() {
+            Some(RawValue::Document(value)) => value,
+            Some(RawValue::String(variant)) => {
+                return visitor.visit_enum(
+                    RawEnumDecoder { variant, value: None, options: self.options, depth: self.depth }
+                );
+            }
+            Some(_) => {
+                return Err(DecodeError::InvalidType("expected an enum".to_string()));
+            }
+            None => {
+                return Err(DecodeError::EndOfStream);
+            }
+        };
+
+        check_depth(self.depth, self.options)?;
+
+        let mut iter = RawIter::new(value.as_bytes());
+
+        let (variant, element_type, bytes) = match iter.next() {
+            Some(v) => v?,
+            None => return Err(DecodeError::SyntaxError("expected a variant name".to_string())),
+        };
+
+        // enums are encoded as maps with a single key:value pair
+        match iter.next() {
+            Some(_) => {
+                Err(DecodeError::InvalidType("expected a single key:value pair".to_string()))
+            }
+            None => {
+                visitor.visit_enum(
+                    RawEnumDecoder { variant, value: Some((element_type, bytes)), options: self.options, depth: self.depth + 1 }
+                )
+            }
+        }
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V
+    ) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        if let (BINARY_NEWTYPE, Some(RawValue::Binary(subtype, bytes))) = (name, &self.value) {
+            let value = Value::Binary(*subtype, bytes.to_vec());
+            return Decoder::with_options(value, self.options).deserialize_newtype_struct(name, visitor);
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    #[inline]
+    fn deserialize_seq<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        if self.null_as_default() {
+            return visitor.visit_seq(SeqDecoder::empty(self.options));
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        if self.null_as_default() {
+            return visitor.visit_map(MapDecoder::empty(self.options));
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    null_as_default!{
+        deserialize_bool => visit_bool(false);
+        deserialize_f32 => visit_f32(0.0);
+        deserialize_f64 => visit_f64(0.0);
+        deserialize_bytes => visit_borrowed_bytes(&[]);
+        deserialize_byte_buf => visit_byte_buf(Vec::new());
+    }
+
+    /// Datetimes are handed over as RFC 3339 strings, which is what chrono's
+    /// `Deserialize` impls parse. `String` goes through `deserialize_string`
+    /// instead, so a datetime stays a type mismatch for `String` fields.
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        match self.value {
+            Some(RawValue::UTCDatetime(dt)) => visitor.visit_string(datetime_string(&dt)),
+            _ => self.deserialize_string(visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        if self.null_as_default() {
+            return visitor.visit_borrowed_str("");
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    lenient_integer!{
+        RawValue::Double;
+        deserialize_u8 => visit_u8;
+        deserialize_u16 => visit_u16;
+        deserialize_u32 => visit_u32;
+        deserialize_u64 => visit_u64;
+        deserialize_i8 => visit_i8;
+        deserialize_i16 => visit_i16;
+        deserialize_i32 => visit_i32;
+        deserialize_i64 => visit_i64;
+    }
+
+    fn deserialize_i128<V>(mut self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        match self.value.take() {
+            Some(value) => Decoder::with_options(value.to_value_at(self.options, self.depth)?, self.options).deserialize_i128(visitor),
+            None => Err(DecodeError::EndOfStream),
+        }
+    }
+
+    fn deserialize_u128<V>(mut self, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        match self.value.take() {
+            Some(value) => Decoder::with_options(value.to_value_at(self.options, self.depth)?, self.options).deserialize_u128(visitor),
+            None => Err(DecodeError::EndOfStream),
+        }
+    }
+
+    forward_to_deserialize!{
+        deserialize_char();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_tuple(len: usize);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+}
+
+struct RawEnumDecoder<'de> {
+    variant: &'de str,
+    value: Option<(ElementType, &'de [u8])>,
+    options: DecodeOptions,
+    /// That of `value`.
+    depth: usize,
+}
+
+impl<'de> EnumAccess<'de> for RawEnumDecoder<'de> {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> DecodeResult<(V::Value, Self::Variant)>
+        where V: DeserializeSeed<'de>
+    {
+        let dec = RawDecoder::with_options(RawValue::String(self.variant), self.options);
+        let value = seed.deserialize(dec)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for RawEnumDecoder<'de> {
+    type Error = DecodeError;
+
+    fn unit_variant(mut self) -> DecodeResult<()> {
+        match self.value.take() {
+            None => Ok(()),
+            Some((element_type, bytes)) => {
+                deserialize_element(PhantomData::<de::IgnoredAny>, element_type, bytes, self.options, self.depth).map(|_| ())
+            }
+        }
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> DecodeResult<T::Value>
+        where T: DeserializeSeed<'de>
+    {
+        let (element_type, bytes) = self.value.take().ok_or(DecodeError::EndOfStream)?;
+        deserialize_element(seed, element_type, bytes, self.options, self.depth)
+    }
+
+    fn tuple_variant<V>(mut self, _len: usize, visitor: V) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        let (element_type, bytes) = self.value.take().ok_or(DecodeError::EndOfStream)?;
+
+        match RawValue::from_bytes(element_type, bytes)? {
+            RawValue::Array(fields) => {
+                check_depth(self.depth, self.options)?;
+                visitor.visit_seq(RawSeqDecoder { iter: RawIter::new(fields.as_bytes()), index: 0, options: self.options, depth: self.depth })
+            }
+            _ => Err(DecodeError::InvalidType("expected a tuple".to_string())),
+        }
+    }
+
+    fn struct_variant<V>(
+        mut self,
+        _fields: &'static [&'static str],
+        visitor: V
+    ) -> DecodeResult<V::Value>
+        where V: Visitor<'de>
+    {
+        let (element_type, bytes) = self.value.take().ok_or(DecodeError::EndOfStream)?;
+
+        match RawValue::from_bytes(element_type, bytes)? {
+            RawValue::Document(fields) => {
+                check_depth(self.depth, self.options)?;
+                visitor.visit_map(RawMapDecoder::new(fields.as_bytes(), self.options, self.depth)?)
+            }
+            _ => Err(DecodeError::InvalidType("expected a struct".to_string())),
+        }
+    }
+}
+
+struct RawSeqDecoder<'de> {
+    iter: RawIter<'de>,
+    index: usize,
+    options: DecodeOptions,
+    /// That of the array.
+    depth: usize,
+}
+
+impl<'de> SeqAccess<'de> for RawSeqDecoder<'de> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> DecodeResult<Option<T::Value>>
+        where T: DeserializeSeed<'de>
+    {
+        match self.iter.next() {
+            None => Ok(None),
+            Some(element) => {
+                let (_, element_type, bytes) = element?;
+                let index = self.index;
+                self.index += 1;
+
+                deserialize_element(seed, element_type, bytes, self.options, self.depth + 1)
+                    .map(Some)
+                    .map_err(|err| in_field(err, format!("[{}]", index)))
+            }
+        }
+    }
+}
+
+struct RawMapDecoder<'de> {
+    iter: indexmap::map::IntoIter<&'de str, (ElementType, &'de [u8])>,
+    value: Option<(&'de str, ElementType, &'de [u8])>,
+    options: DecodeOptions,
+    /// That of the document.
+    depth: usize,
+}
+
+impl<'de> RawMapDecoder<'de> {
+    /// The elements are collected up front so that repeated keys are
+    /// resolved by `options.duplicate_keys`, as `decode_document` does.
+    fn new(bytes: &'de [u8], options: DecodeOptions, depth: usize) -> DecodeResult<RawMapDecoder<'de>> {
+        let mut elements = IndexMap::new();
+
+        for element in RawIter::new(bytes) {
+            let (key, element_type, bytes) = element?;
+
+            match options.duplicate_keys {
+                DuplicateKeys::LastWins => {
+                    elements.insert(key, (element_type, bytes));
+                }
+                DuplicateKeys::FirstWins => {
+                    elements.entry(key).or_insert((element_type, bytes));
+                }
+                DuplicateKeys::Error => {
+                    if elements.insert(key, (element_type, bytes)).is_some() {
+                        return Err(DecodeError::DuplicateKey(key.to_string()));
+                    }
+                }
+            }
+        }
+
+        Ok(RawMapDecoder {
+            iter: elements.into_iter(),
+            value: None,
+            options,
+            depth,
+        })
+    }
+}
+
+impl<'de> MapAccess<'de> for RawMapDecoder<'de> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> DecodeResult<Option<K::Value>>
+        where K: DeserializeSeed<'de>
+    {
+        match self.iter.next() {
+            Some((key, (element_type, bytes))) => {
+                self.value = Some((key, element_type, bytes));
+
+                let de = RawDecoder::new(RawValue::String(key));
+                match seed.deserialize(de) {
+                    Ok(val) => Ok(Some(val)),
+                    Err(DecodeError::UnknownField(_)) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> DecodeResult<V::Value>
+        where V: DeserializeSeed<'de>
+    {
+        let (key, element_type, bytes) = self.value.take().ok_or(DecodeError::EndOfStream)?;
+
+        deserialize_element(seed, element_type, bytes, self.options, self.depth + 1)
+            .map_err(|err| in_field(err, key.to_string()))
+    }
+}
+
+/// Deserialize the value of an element from its bytes, at `depth`. Strings
+/// that aren't valid UTF-8 can't be borrowed, so an element that doesn't
+/// read as a `RawValue` is decoded to an owned `Value` instead, applying
+/// `options.utf8` as `from_slice` does. A malformed element fails there
+/// with the same error it would from `from_slice`.
+fn deserialize_element<'de, T>(
+    seed: T,
+    element_type: ElementType,
+    bytes: &'de [u8],
+    options: DecodeOptions,
+    depth: usize
+) -> DecodeResult<T::Value>
+    where T: DeserializeSeed<'de>
+{
+    match RawValue::from_bytes(element_type, bytes) {
+        Ok(value) => seed.deserialize(RawDecoder::nested(value, options, depth)),
+        Err(_) => {
+            let value = decode_value_slice(element_type, bytes, options, depth - 1)?;
+            seed.deserialize(Decoder::with_options(value, options))
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for UTCDateTime {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where D: Deserializer<'de>
@@ -691,20 +1374,75 @@ impl<'de> Deserialize<'de> for UTCDateTime {
     }
 }
 
-impl<'de> Deserialize<'de> for TimeStamp {
+/// Name `Binary` deserializes as a newtype struct under, so the decoders
+/// know to hand over the subtype along with the bytes.
+const BINARY_NEWTYPE: &str = "$__bsonrs_binary";
+
+impl<'de> Deserialize<'de> for Binary {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_newtype_struct(BINARY_NEWTYPE, BinaryVisitor)
+    }
+}
+
+struct BinaryVisitor;
+
+impl BinaryVisitor {
+    fn from_value<E: Error>(value: Value) -> Result<Binary, E> {
+        match value {
+            Value::Binary(subtype, bytes) => Ok(Binary { subtype, bytes }),
+            _ => Err(E::custom("expecting Binary")),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for BinaryVisitor {
+    type Value = Binary;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expecting Binary")
+    }
+
+    fn visit_bytes<E: Error>(self, value: &[u8]) -> Result<Binary, E> {
+        Ok(Binary { subtype: BinarySubtype::Generic, bytes: value.to_vec() })
+    }
+
+    fn visit_byte_buf<E: Error>(self, value: Vec<u8>) -> Result<Binary, E> {
+        Ok(Binary { subtype: BinarySubtype::Generic, bytes: value })
+    }
+
+    fn visit_map<V>(self, visitor: V) -> Result<Binary, V::Error>
+        where V: MapAccess<'de>
+    {
+        BinaryVisitor::from_value(ValueVisitor.visit_map(visitor)?)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Binary, D::Error>
+        where D: Deserializer<'de>
+    {
+        BinaryVisitor::from_value(Value::deserialize(deserializer)?)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal128 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where D: Deserializer<'de>
     {
         match Value::deserialize(deserializer)? {
-            Value::TimeStamp(ts) => {
-                let ts = ts.to_le();
+            Value::Decimal128(d) => Ok(d),
+            _ => Err(D::Error::custom("expecting Decimal128")),
+        }
+    }
+}
 
-                Ok(TimeStamp {
-                    timestamp: ((ts as u64) >> 32) as u32,
-                    increment: (ts & 0xFFFF_FFFF) as u32,
-                })
-            }
-            _ => Err(D::Error::custom("expecting UtcDateTime")),
+impl<'de> Deserialize<'de> for TimeStamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        match Value::deserialize(deserializer)? {
+            Value::TimeStamp(ts) => Ok(ts),
+            _ => Err(D::Error::custom("expecting TimeStamp")),
         }
     }
 }
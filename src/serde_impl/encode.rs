@@ -1,14 +1,20 @@
 use std::{u32, i32, f64};
+use std::convert::TryFrom;
+use std::io::Write;
 
 use serde::ser::{Serialize, Serializer, SerializeSeq, SerializeTuple, SerializeTupleStruct,
                  SerializeTupleVariant, SerializeMap, SerializeStruct, SerializeStructVariant};
 
 use crate::doc::Document;
-use crate::value::{Value, Array, UTCDateTime, TimeStamp};
-use crate::encode::to_bson;
+use crate::value::{Value, Array, UTCDateTime, TimeStamp, Binary};
+use crate::decimal128::Decimal128;
+use crate::encode::{to_bson, to_bson_with_options};
 use crate::encode::EncodeError;
 use crate::encode::EncodeResult;
-use crate::spec::BinarySubtype;
+use crate::encode::{EncodeOptions, UnsignedPolicy, Int128Policy, encode_value_with_options, write_cstring, write_string, write_i32, binary_len, begin_document, end_document};
+use crate::spec::{BinarySubtype, ElementType};
+use crate::raw::RawDocument;
+use crate::util::hex::ToHex;
 
 impl Serialize for Document {
      #[inline]
@@ -46,12 +52,59 @@ impl Serialize for Value {
     }
 }
 
+/// Serializes Rust values into a `Value`. Build one with `Encoder::new()`,
+/// `Encoder::default()` or `Encoder::with_options`.
 #[derive(Default)]
-pub struct Encoder;
+pub struct Encoder {
+    options: EncodeOptions,
+}
 
 impl Encoder {
     pub fn new() -> Encoder {
-        Encoder
+        Encoder::default()
+    }
+
+    /// An `Encoder` applying `options`, e.g. to choose how unsigned
+    /// integers are represented.
+    pub fn with_options(options: EncodeOptions) -> Encoder {
+        Encoder { options }
+    }
+
+    fn serialize_unsigned(self, value: u64) -> EncodeResult<Value> {
+        match self.options.unsigned {
+            UnsignedPolicy::Error => Err(EncodeError::UnsupportedUnsignedType),
+            UnsignedPolicy::Fit => {
+                if let Ok(v) = i32::try_from(value) {
+                    Ok(Value::Int32(v))
+                } else if let Ok(v) = i64::try_from(value) {
+                    Ok(Value::Int64(v))
+                } else {
+                    Err(EncodeError::UnsignedOverflow(value))
+                }
+            }
+            UnsignedPolicy::Int64Wrapping => Ok(Value::Int64(value as i64)),
+            UnsignedPolicy::Decimal128 => {
+                // Any u64 is well within the 34 digits Decimal128 holds
+                Decimal128::from_parts(false, u128::from(value), 0)
+                    .map(Value::Decimal128)
+                    .ok_or(EncodeError::UnsignedOverflow(value))
+            }
+            UnsignedPolicy::String => Ok(Value::String(value.to_string())),
+        }
+    }
+
+    fn serialize_int128(self, negative: bool, magnitude: u128) -> EncodeResult<Value> {
+        let digits = || if negative { format!("-{}", magnitude) } else { magnitude.to_string() };
+
+        match self.options.int128 {
+            Int128Policy::Decimal128 => {
+                Decimal128::from_parts(negative, magnitude, 0)
+                    .map(Value::Decimal128)
+                    .ok_or_else(|| EncodeError::Int128Overflow(digits()))
+            }
+            Int128Policy::String => Ok(Value::String(digits())),
+            Int128Policy::Error => Err(EncodeError::UnsupportedInt128Type),
+        }
     }
 }
 
@@ -78,8 +131,12 @@ impl Serializer for Encoder {
     }
 
     #[inline]
-    fn serialize_u8(self, _value: u8) -> EncodeResult<Value> {
-        Err(EncodeError::UnsupportedUnsignedType)
+    fn serialize_u8(self, value: u8) -> EncodeResult<Value> {
+        if self.options.strict_unsigned {
+            return self.serialize_unsigned(u64::from(value));
+        }
+
+        self.serialize_i32(i32::from(value))
     }
 
     #[inline]
@@ -88,8 +145,12 @@ impl Serializer for Encoder {
     }
 
     #[inline]
-    fn serialize_u16(self, _value: u16) -> EncodeResult<Value> {
-        Err(EncodeError::UnsupportedUnsignedType)
+    fn serialize_u16(self, value: u16) -> EncodeResult<Value> {
+        if self.options.strict_unsigned {
+            return self.serialize_unsigned(u64::from(value));
+        }
+
+        self.serialize_i32(i32::from(value))
     }
 
     #[inline]
@@ -98,8 +159,12 @@ impl Serializer for Encoder {
     }
 
     #[inline]
-    fn serialize_u32(self, _value: u32) -> EncodeResult<Value> {
-        Err(EncodeError::UnsupportedUnsignedType)
+    fn serialize_u32(self, value: u32) -> EncodeResult<Value> {
+        if self.options.strict_unsigned {
+            return self.serialize_unsigned(u64::from(value));
+        }
+
+        self.serialize_i64(i64::from(value))
     }
 
     #[inline]
@@ -108,8 +173,18 @@ impl Serializer for Encoder {
     }
 
     #[inline]
-    fn serialize_u64(self, _value: u64) -> EncodeResult<Value> {
-        Err(EncodeError::UnsupportedUnsignedType)
+    fn serialize_u64(self, value: u64) -> EncodeResult<Value> {
+        self.serialize_unsigned(value)
+    }
+
+    #[inline]
+    fn serialize_i128(self, value: i128) -> EncodeResult<Value> {
+        self.serialize_int128(value < 0, value.unsigned_abs())
+    }
+
+    #[inline]
+    fn serialize_u128(self, value: u128) -> EncodeResult<Value> {
+        self.serialize_int128(false, value)
     }
 
     #[inline]
@@ -192,18 +267,18 @@ impl Serializer for Encoder {
         where T: Serialize
     {
         let mut newtype_variant = Document::new();
-        newtype_variant.insert(variant, to_bson(value)?);
+        newtype_variant.insert(variant, to_bson_with_options(value, self.options)?);
         Ok(newtype_variant.into())
     }
 
     #[inline]
     fn serialize_seq(self, len: Option<usize>) -> EncodeResult<Self::SerializeSeq> {
-        Ok(ArraySerializer { inner: Array::with_capacity(len.unwrap_or(0)) })
+        Ok(ArraySerializer { inner: Array::with_capacity(len.unwrap_or(0)), options: self.options })
     }
 
     #[inline]
     fn serialize_tuple(self, len: usize) -> EncodeResult<Self::SerializeTuple> {
-        Ok(TupleSerializer { inner: Array::with_capacity(len) })
+        Ok(TupleSerializer { inner: Array::with_capacity(len), options: self.options })
     }
 
     #[inline]
@@ -212,7 +287,7 @@ impl Serializer for Encoder {
         _name: &'static str,
         len: usize
     ) -> EncodeResult<Self::SerializeTupleStruct> {
-        Ok(TupleStructSerializer { inner: Array::with_capacity(len) })
+        Ok(TupleStructSerializer { inner: Array::with_capacity(len), options: self.options })
     }
 
     #[inline]
@@ -226,6 +301,7 @@ impl Serializer for Encoder {
         Ok(TupleVariantSerializer {
             inner: Array::with_capacity(len),
             name: variant,
+            options: self.options,
         })
     }
 
@@ -234,6 +310,7 @@ impl Serializer for Encoder {
         Ok(MapSerializer {
             inner: Document::new(),
             next_key: None,
+            options: self.options,
         })
     }
 
@@ -243,7 +320,7 @@ impl Serializer for Encoder {
         _name: &'static str,
         _len: usize
     ) -> EncodeResult<Self::SerializeStruct> {
-        Ok(StructSerializer { inner: Document::new() })
+        Ok(StructSerializer { inner: Document::new(), options: self.options })
     }
 
     #[inline]
@@ -257,13 +334,14 @@ impl Serializer for Encoder {
         Ok(StructVariantSerializer {
             name: variant,
             inner: Document::new(),
+            options: self.options,
         })
     }
 }
 
-
 pub struct ArraySerializer {
-    inner: Array
+    inner: Array,
+    options: EncodeOptions,
 }
 
 impl SerializeSeq for ArraySerializer {
@@ -271,7 +349,7 @@ impl SerializeSeq for ArraySerializer {
     type Error = EncodeError;
 
     fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
-        self.inner.push(to_bson(value)?);
+        self.inner.push(to_bson_with_options(value, self.options)?);
         Ok(())
     }
 
@@ -281,7 +359,8 @@ impl SerializeSeq for ArraySerializer {
 }
 
 pub struct TupleSerializer {
-    inner: Array
+    inner: Array,
+    options: EncodeOptions,
 }
 
 impl SerializeTuple for TupleSerializer {
@@ -289,7 +368,7 @@ impl SerializeTuple for TupleSerializer {
     type Error = EncodeError;
 
     fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
-        self.inner.push(to_bson(value)?);
+        self.inner.push(to_bson_with_options(value, self.options)?);
         Ok(())
     }
 
@@ -299,7 +378,8 @@ impl SerializeTuple for TupleSerializer {
 }
 
 pub struct TupleStructSerializer {
-    inner: Array
+    inner: Array,
+    options: EncodeOptions,
 }
 
 impl SerializeTupleStruct for TupleStructSerializer {
@@ -307,7 +387,7 @@ impl SerializeTupleStruct for TupleStructSerializer {
     type Error = EncodeError;
 
     fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
-        self.inner.push(to_bson(value)?);
+        self.inner.push(to_bson_with_options(value, self.options)?);
         Ok(())
     }
 
@@ -318,7 +398,8 @@ impl SerializeTupleStruct for TupleStructSerializer {
 
 pub struct TupleVariantSerializer {
     inner: Array,
-    name: &'static str
+    name: &'static str,
+    options: EncodeOptions,
 }
 
 impl SerializeTupleVariant for TupleVariantSerializer {
@@ -326,7 +407,7 @@ impl SerializeTupleVariant for TupleVariantSerializer {
     type Error = EncodeError;
 
     fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
-        self.inner.push(to_bson(value)?);
+        self.inner.push(to_bson_with_options(value, self.options)?);
         Ok(())
     }
 
@@ -339,7 +420,8 @@ impl SerializeTupleVariant for TupleVariantSerializer {
 
 pub struct MapSerializer {
     inner: Document,
-    next_key: Option<String>
+    next_key: Option<String>,
+    options: EncodeOptions,
 }
 
 impl SerializeMap for MapSerializer {
@@ -356,7 +438,7 @@ impl SerializeMap for MapSerializer {
 
     fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
         let key = self.next_key.take().unwrap_or_else(|| "".to_string());
-        self.inner.insert(key, to_bson(&value)?);
+        self.inner.insert(key, to_bson_with_options(value, self.options)?);
         Ok(())
     }
 
@@ -366,7 +448,8 @@ impl SerializeMap for MapSerializer {
 }
 
 pub struct StructSerializer {
-    inner: Document
+    inner: Document,
+    options: EncodeOptions,
 }
 
 impl SerializeStruct for StructSerializer {
@@ -378,7 +461,7 @@ impl SerializeStruct for StructSerializer {
         key: &'static str,
         value: &T
     ) -> EncodeResult<()> {
-        self.inner.insert(key, to_bson(value)?);
+        self.inner.insert(key, to_bson_with_options(value, self.options)?);
         Ok(())
     }
 
@@ -389,7 +472,8 @@ impl SerializeStruct for StructSerializer {
 
 pub struct StructVariantSerializer {
     inner: Document,
-    name: &'static str
+    name: &'static str,
+    options: EncodeOptions,
 }
 
 impl SerializeStructVariant for StructVariantSerializer {
@@ -401,7 +485,7 @@ impl SerializeStructVariant for StructVariantSerializer {
         key: &'static str,
         value: &T
     ) -> EncodeResult<()> {
-        self.inner.insert(key, to_bson(value)?);
+        self.inner.insert(key, to_bson_with_options(value, self.options)?);
         Ok(())
     }
 
@@ -415,6 +499,484 @@ impl SerializeStructVariant for StructVariantSerializer {
     }
 }
 
+/// Serde Serializer that writes BSON straight into a buffer, without
+/// building a `Value` first. Scalars go through `Encoder` so both encode
+/// numbers the same way.
+///
+/// Types like `ObjectId` serialize as their extended JSON document. As in
+/// `to_bson`, documents small enough to be one of those are read back once
+/// written and replaced if `Value::from_extended_document` recognises them.
+pub(crate) struct RawEncoder<'a, 'k> {
+    buf: &'a mut Vec<u8>,
+    key: Key<'k>,
+    options: EncodeOptions,
+}
+
+#[derive(Clone, Copy)]
+enum Key<'k> {
+    Str(&'k str),
+    Index(usize),
+}
+
+impl<'k> Key<'k> {
+    fn write(self, buf: &mut Vec<u8>) -> EncodeResult<()> {
+        match self {
+            Key::Str(key) => write_cstring(buf, key),
+            Key::Index(i) => {
+                write!(buf, "{}", i)?;
+                buf.push(0);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a, 'k> RawEncoder<'a, 'k> {
+    pub(crate) fn new(buf: &'a mut Vec<u8>, key: &'k str, options: EncodeOptions) -> RawEncoder<'a, 'k> {
+        RawEncoder { buf, key: Key::Str(key), options }
+    }
+
+    fn header(&mut self, element_type: ElementType) -> EncodeResult<()> {
+        self.buf.push(element_type as u8);
+        self.key.write(self.buf)
+    }
+
+    fn value(mut self, value: &Value) -> EncodeResult<()> {
+        self.header(value.element_type())?;
+        encode_value_with_options(self.buf, value, self.options)
+    }
+
+    fn document(mut self, outer: Option<usize>) -> EncodeResult<RawDocumentSerializer<'a, 'k>> {
+        let header = self.buf.len();
+        self.header(ElementType::Document)?;
+        let start = begin_document(self.buf);
+
+        Ok(RawDocumentSerializer {
+            buf: self.buf,
+            key: self.key,
+            options: self.options,
+            header,
+            start,
+            outer,
+            len: 0,
+            next_key: None,
+        })
+    }
+
+    fn array(mut self, outer: Option<usize>) -> EncodeResult<RawArraySerializer<'a>> {
+        self.header(ElementType::Array)?;
+        let start = begin_document(self.buf);
+
+        Ok(RawArraySerializer {
+            buf: self.buf,
+            start,
+            index: 0,
+            outer,
+            options: self.options,
+        })
+    }
+}
+
+/// Serialize one element of a document, leaving it out if it is null and
+/// `omit_null` is set.
+fn serialize_field<T>(buf: &mut Vec<u8>, key: &str, value: &T, options: EncodeOptions) -> EncodeResult<()>
+    where T: ?Sized + Serialize
+{
+    let start = buf.len();
+    value.serialize(RawEncoder::new(buf, key, options))?;
+
+    if options.omit_null && buf[start] == ElementType::NullValue as u8 {
+        buf.truncate(start);
+    }
+
+    Ok(())
+}
+
+impl<'a, 'k> Serializer for RawEncoder<'a, 'k> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    type SerializeSeq = RawArraySerializer<'a>;
+    type SerializeTuple = RawArraySerializer<'a>;
+    type SerializeTupleStruct = RawArraySerializer<'a>;
+    type SerializeTupleVariant = RawArraySerializer<'a>;
+    type SerializeMap = RawDocumentSerializer<'a, 'k>;
+    type SerializeStruct = RawDocumentSerializer<'a, 'k>;
+    type SerializeStructVariant = RawDocumentSerializer<'a, 'k>;
+
+    fn serialize_bool(self, value: bool) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_bool(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_i8(self, value: i8) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_i8(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_u8(self, value: u8) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_u8(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_i16(self, value: i16) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_i16(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_u16(self, value: u16) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_u16(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_i32(self, value: i32) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_i32(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_u32(self, value: u32) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_u32(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_i64(self, value: i64) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_i64(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_u64(self, value: u64) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_u64(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_i128(self, value: i128) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_i128(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_u128(self, value: u128) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_u128(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_f32(self, value: f32) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_f32(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_f64(self, value: f64) -> EncodeResult<()> {
+        let value = Encoder::with_options(self.options).serialize_f64(value)?;
+        self.value(&value)
+    }
+
+    fn serialize_char(self, value: char) -> EncodeResult<()> {
+        self.serialize_str(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(mut self, value: &str) -> EncodeResult<()> {
+        self.header(ElementType::Utf8String)?;
+        write_string(self.buf, value)
+    }
+
+    fn serialize_bytes(mut self, value: &[u8]) -> EncodeResult<()> {
+        self.header(ElementType::Binary)?;
+        write_i32(self.buf, binary_len(value, 0)?)?;
+        self.buf.push(From::from(BinarySubtype::Generic));
+        self.buf.extend_from_slice(value);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> EncodeResult<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<V>(self, value: &V) -> EncodeResult<()>
+        where V: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(mut self) -> EncodeResult<()> {
+        self.header(ElementType::NullValue)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> EncodeResult<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str
+    ) -> EncodeResult<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T
+    ) -> EncodeResult<()>
+        where T: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        mut self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T
+    ) -> EncodeResult<()>
+        where T: ?Sized + Serialize
+    {
+        self.header(ElementType::Document)?;
+        let start = begin_document(self.buf);
+        serialize_field(self.buf, variant, value, self.options)?;
+        end_document(self.buf, start)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> EncodeResult<Self::SerializeSeq> {
+        self.array(None)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> EncodeResult<Self::SerializeTuple> {
+        self.array(None)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize
+    ) -> EncodeResult<Self::SerializeTupleStruct> {
+        self.array(None)
+    }
+
+    fn serialize_tuple_variant(
+        mut self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize
+    ) -> EncodeResult<Self::SerializeTupleVariant> {
+        self.header(ElementType::Document)?;
+        let outer = begin_document(self.buf);
+        RawEncoder::new(self.buf, variant, self.options).array(Some(outer))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> EncodeResult<Self::SerializeMap> {
+        self.document(None)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize
+    ) -> EncodeResult<Self::SerializeStruct> {
+        self.document(None)
+    }
+
+    fn serialize_struct_variant(
+        mut self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize
+    ) -> EncodeResult<Self::SerializeStructVariant> {
+        self.header(ElementType::Document)?;
+        let outer = begin_document(self.buf);
+
+        RawEncoder::new(self.buf, variant, self.options).document(Some(outer))
+    }
+}
+
+pub(crate) struct RawArraySerializer<'a> {
+    buf: &'a mut Vec<u8>,
+    start: usize,
+    index: usize,
+    // Start of the document wrapping a tuple variant
+    outer: Option<usize>,
+    options: EncodeOptions,
+}
+
+impl<'a> RawArraySerializer<'a> {
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
+        value.serialize(RawEncoder { buf: self.buf, key: Key::Index(self.index), options: self.options })?;
+        self.index += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> EncodeResult<()> {
+        end_document(self.buf, self.start)?;
+
+        if let Some(outer) = self.outer {
+            end_document(self.buf, outer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> SerializeSeq for RawArraySerializer<'a> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        self.finish()
+    }
+}
+
+impl<'a> SerializeTuple for RawArraySerializer<'a> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        self.finish()
+    }
+}
+
+impl<'a> SerializeTupleStruct for RawArraySerializer<'a> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        self.finish()
+    }
+}
+
+impl<'a> SerializeTupleVariant for RawArraySerializer<'a> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        self.finish()
+    }
+}
+
+pub(crate) struct RawDocumentSerializer<'a, 'k> {
+    buf: &'a mut Vec<u8>,
+    key: Key<'k>,
+    options: EncodeOptions,
+    // Where the element header and the length prefix are
+    header: usize,
+    start: usize,
+    // Start of the document wrapping a struct variant
+    outer: Option<usize>,
+    len: usize,
+    next_key: Option<String>,
+}
+
+impl<'a, 'k> RawDocumentSerializer<'a, 'k> {
+    fn entry<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> EncodeResult<()> {
+        let before = self.buf.len();
+        serialize_field(self.buf, key, value, self.options)?;
+
+        if self.buf.len() > before {
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> EncodeResult<()> {
+        end_document(self.buf, self.start)?;
+
+        // Small documents may be the extended form of a type like ObjectId
+        if self.len <= 2 {
+            let doc = RawDocument::new(&self.buf[self.start..])
+                .and_then(|raw| raw.to_document())
+                .map_err(|err| EncodeError::Unknown(err.to_string()))?;
+
+            let value = Value::from_extended_document(doc);
+
+            if value.element_type() != ElementType::Document {
+                self.buf.truncate(self.header);
+                RawEncoder { buf: self.buf, key: self.key, options: self.options }.value(&value)?;
+            }
+        }
+
+        if let Some(outer) = self.outer {
+            end_document(self.buf, outer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, 'k> SerializeMap for RawDocumentSerializer<'a, 'k> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> EncodeResult<()> {
+        self.next_key = match to_bson(&key)? {
+            Value::String(s) => Some(s),
+            other => return Err(EncodeError::InvalidMapKeyType(other)),
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> EncodeResult<()> {
+        let key = self.next_key.take().unwrap_or_default();
+        self.entry(&key, value)
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        self.finish()
+    }
+}
+
+impl<'a, 'k> SerializeStruct for RawDocumentSerializer<'a, 'k> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T
+    ) -> EncodeResult<()> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        self.finish()
+    }
+}
+
+impl<'a, 'k> SerializeStructVariant for RawDocumentSerializer<'a, 'k> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T
+    ) -> EncodeResult<()> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> EncodeResult<()> {
+        self.finish()
+    }
+}
+
 impl Serialize for UTCDateTime {
     #[inline]
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -431,8 +993,29 @@ impl Serialize for TimeStamp {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where S: Serializer
     {
-        let ts = ((self.timestamp.to_le() as u64) << 32) | (self.increment.to_le() as u64);
-        let doc = Value::TimeStamp(ts);
-        doc.serialize(serializer)
+        Value::TimeStamp(*self).serialize(serializer)
+    }
+}
+
+impl Serialize for Binary {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        // The same extended form as `Value::Binary`, written from the borrowed
+        // bytes rather than a copy of them
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("$binary", &self.bytes.to_hex())?;
+        map.serialize_entry("type", &i32::from(u8::from(self.subtype)))?;
+        map.end()
+    }
+}
+
+impl Serialize for Decimal128 {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        Value::Decimal128(*self).serialize(serializer)
     }
 }
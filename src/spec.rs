@@ -17,6 +17,7 @@ pub const JAVASCRIPT_CODE_WITH_SCOPE: u8 = 0x0F;
 pub const INT_32BIT: u8                  = 0x10;
 pub const TIMESTAMP: u8                  = 0x11;
 pub const INT_64BIT: u8                  = 0x12;
+pub const DECIMAL128: u8                 = 0x13;
 pub const MINKEY: u8                     = 0xFF;
 pub const MAXKEY: u8                     = 0x7F;
 
@@ -27,10 +28,11 @@ pub const BINARY_OLD: u8                 = 0x02;
 pub const UUID_OLD: u8                   = 0x03;
 pub const UUID: u8                       = 0x04;
 pub const MD5: u8                        = 0x05;
+pub const SENSITIVE: u8                  = 0x08;
 // pub const USER_DEFINED: u8               = 0x80;
 
 #[repr(u8)]
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum ElementType {
     Double                  = DOUBLE,
     Utf8String              = UTF8_STRING,
@@ -50,6 +52,7 @@ pub enum ElementType {
     Int32                   = INT_32BIT,
     TimeStamp               = TIMESTAMP,
     Int64                   = INT_64BIT,
+    Decimal128              = DECIMAL128,
     MinKey                  = MINKEY,
     MaxKey                  = MAXKEY,
 }
@@ -75,6 +78,7 @@ impl ElementType {
             INT_32BIT                  => ElementType::Int32,
             TIMESTAMP                  => ElementType::TimeStamp,
             INT_64BIT                  => ElementType::Int64,
+            DECIMAL128                 => ElementType::Decimal128,
             MINKEY                     => ElementType::MinKey,
             MAXKEY                     => ElementType::MaxKey,
             _                          => return None,
@@ -90,6 +94,7 @@ pub enum BinarySubtype {
     UuidOld,
     Uuid,
     Md5,
+    Sensitive,
     UserDefined(u8),
 }
 
@@ -102,6 +107,7 @@ impl From<BinarySubtype> for u8 {
             BinarySubtype::UuidOld => UUID_OLD,
             BinarySubtype::Uuid => UUID,
             BinarySubtype::Md5 => MD5,
+            BinarySubtype::Sensitive => SENSITIVE,
             BinarySubtype::UserDefined(x) => x,
         }
     }
@@ -117,6 +123,7 @@ impl From<u8> for BinarySubtype {
             UUID_OLD => BinarySubtype::UuidOld,
             UUID => BinarySubtype::Uuid,
             MD5 => BinarySubtype::Md5,
+            SENSITIVE => BinarySubtype::Sensitive,
             _ => BinarySubtype::UserDefined(t),
         }
     }
@@ -0,0 +1,135 @@
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::doc::Document;
+use crate::encode::{EncodeError, EncodeResult};
+use crate::decode::{DecodeError, DecodeResult, MAX_BSON_SIZE};
+
+/// Frames documents by their length prefix, for use with
+/// `tokio_util::codec::Framed` and friends.
+///
+/// Frames longer than the maximum are rejected in both directions; on the
+/// decoding side this happens as soon as the length prefix arrives, before
+/// any of the frame is buffered. The default maximum is 16MiB, the largest
+/// document MongoDB accepts.
+#[derive(Debug, Clone, Copy)]
+pub struct BsonCodec {
+    max_frame_len: usize,
+}
+
+impl BsonCodec {
+    pub fn new() -> BsonCodec {
+        BsonCodec {
+            max_frame_len: MAX_BSON_SIZE as usize,
+        }
+    }
+
+    pub fn with_max_frame_len(max_frame_len: usize) -> BsonCodec {
+        BsonCodec { max_frame_len }
+    }
+
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+}
+
+impl Default for BsonCodec {
+    fn default() -> BsonCodec {
+        BsonCodec::new()
+    }
+}
+
+impl Decoder for BsonCodec {
+    type Item = Document;
+    type Error = DecodeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> DecodeResult<Option<Document>> {
+        if src.len() < 4 {
+            return Ok(None);
+        }
+
+        let len = i32::from_le_bytes([src[0], src[1], src[2], src[3]]);
+
+        if len < 5 || len as usize > self.max_frame_len {
+            return Err(DecodeError::InvalidLength(
+                len as usize,
+                format!("invalid length {} for a frame of at most {} bytes", len, self.max_frame_len)
+            ));
+        }
+
+        let len = len as usize;
+
+        if src.len() < len {
+            src.reserve(len - src.len());
+            return Ok(None);
+        }
+
+        let frame = src.split_to(len);
+        Document::from_slice(frame.chunk()).map(Some)
+    }
+}
+
+impl<'a> Encoder<&'a Document> for BsonCodec {
+    type Error = EncodeError;
+
+    fn encode(&mut self, doc: &'a Document, dst: &mut BytesMut) -> EncodeResult<()> {
+        let bytes = doc.to_vec()?;
+
+        if bytes.len() > self.max_frame_len {
+            return Err(EncodeError::Unknown(format!(
+                "document of {} bytes is larger than the maximum frame of {} bytes", bytes.len(), self.max_frame_len
+            )));
+        }
+
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+impl Encoder<Document> for BsonCodec {
+    type Error = EncodeError;
+
+    fn encode(&mut self, doc: Document, dst: &mut BytesMut) -> EncodeResult<()> {
+        self.encode(&doc, dst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use super::BsonCodec;
+    use crate::doc;
+
+    #[test]
+    fn round_trip() {
+        let mut codec = BsonCodec::new();
+        let mut buf = BytesMut::new();
+
+        codec.encode(doc!{"a": 1}, &mut buf).unwrap();
+        codec.encode(&doc!{"b": "two"}, &mut buf).unwrap();
+
+        let mut partial = buf.split_to(7);
+        assert_eq!(codec.decode(&mut partial).unwrap(), None);
+        partial.unsplit(buf);
+        let mut buf = partial;
+
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(doc!{"a": 1}));
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(doc!{"b": "two"}));
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn max_frame_len() {
+        let mut codec = BsonCodec::with_max_frame_len(16);
+        let mut buf = BytesMut::new();
+
+        assert!(codec.encode(doc!{"a": "a long string value"}, &mut buf).is_err());
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(&[17, 0, 0, 0]);
+        assert!(codec.decode(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::doc::Document;
+use crate::decode::{DecodeError, DecodeResult};
+
+use super::frame_len;
+
+/// The async counterpart of `DocumentReader`: a `Stream` of back-to-back
+/// documents read from an `AsyncRead`.
+///
+/// Each document is buffered in full before it is decoded. Running out of
+/// input between two documents ends the stream; running out in the middle
+/// of one yields `EndOfStream`. The stream ends after the first error.
+#[derive(Debug)]
+pub struct DocumentStream<R> {
+    reader: R,
+    buf: Vec<u8>,
+    filled: usize,
+    // Length of the document being read, once its prefix is in
+    len: Option<usize>,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> DocumentStream<R> {
+    pub fn new(reader: R) -> DocumentStream<R> {
+        DocumentStream {
+            reader,
+            buf: vec![0; 4],
+            filled: 0,
+            len: None,
+            done: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn poll_document(&mut self, cx: &mut Context) -> Poll<DecodeResult<Option<Document>>> {
+        loop {
+            let want = self.len.unwrap_or(4);
+
+            while self.filled < want {
+                let mut read_buf = ReadBuf::new(&mut self.buf[self.filled..want]);
+                ready!(Pin::new(&mut self.reader).poll_read(cx, &mut read_buf))?;
+
+                let n = read_buf.filled().len();
+
+                if n == 0 {
+                    if self.filled == 0 && self.len.is_none() {
+                        return Poll::Ready(Ok(None));
+                    }
+
+                    return Poll::Ready(Err(DecodeError::EndOfStream));
+                }
+
+                self.filled += n;
+            }
+
+            match self.len {
+                None => {
+                    let len = frame_len([self.buf[0], self.buf[1], self.buf[2], self.buf[3]])?;
+                    self.buf.resize(len, 0);
+                    self.len = Some(len);
+                }
+                Some(len) => {
+                    let doc = Document::from_slice(&self.buf[..len]);
+
+                    self.buf.truncate(4);
+                    self.filled = 0;
+                    self.len = None;
+
+                    return Poll::Ready(doc.map(Some));
+                }
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for DocumentStream<R> {
+    type Item = DecodeResult<Document>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        let next = ready!(self.poll_document(cx));
+
+        if !matches!(next, Ok(Some(_))) {
+            self.done = true;
+        }
+
+        Poll::Ready(next.transpose())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::pin::Pin;
+    use std::future::poll_fn;
+
+    use futures_core::Stream;
+
+    use super::DocumentStream;
+    use crate::decode::DecodeError;
+    use crate::doc;
+
+    async fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn stream_documents() {
+        let docs = vec![doc!{"a": 1}, doc!{"b": "two"}, doc!{}];
+
+        let mut buf = Vec::new();
+        for doc in &docs {
+            buf.extend(doc.to_vec().unwrap());
+        }
+
+        // Hand the bytes over a few at a time
+        let (mut client, server) = tokio::io::duplex(3);
+        let writer = tokio::spawn({
+            let buf = buf.clone();
+            async move {
+                use tokio::io::AsyncWriteExt;
+                client.write_all(&buf).await.unwrap();
+            }
+        });
+
+        let mut stream = DocumentStream::new(server);
+        let mut read = Vec::new();
+        while let Some(doc) = next(&mut stream).await {
+            read.push(doc.unwrap());
+        }
+
+        writer.await.unwrap();
+        assert_eq!(read, docs);
+
+        let mut stream = DocumentStream::new(&buf[..buf.len() - 1]);
+        assert!(next(&mut stream).await.unwrap().is_ok());
+        assert!(next(&mut stream).await.unwrap().is_ok());
+        assert!(matches!(next(&mut stream).await, Some(Err(DecodeError::EndOfStream))));
+        assert!(next(&mut stream).await.is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::doc::Document;
+use crate::decode::DecodeResult;
+
+use super::frame_len;
+
+/// A push-based parser for non-blocking I/O. Bytes are handed over in
+/// whatever chunks they arrive in with `feed`, and complete documents are
+/// taken out with `next_document`; a document split across several reads is
+/// held until the rest of it arrives.
+///
+/// An invalid length prefix leaves the parser unable to find the next
+/// document, so once `next_document` has returned an error for one it keeps
+/// doing so.
+///
+/// ```
+/// use bsonrs::doc;
+/// use bsonrs::stream::FeedParser;
+///
+/// let bytes = doc!{"a": 1}.to_vec().unwrap();
+/// let mut parser = FeedParser::new();
+///
+/// parser.feed(&bytes[..5]);
+/// assert_eq!(parser.next_document().unwrap(), None);
+///
+/// parser.feed(&bytes[5..]);
+/// assert_eq!(parser.next_document().unwrap(), Some(doc!{"a": 1}));
+/// ```
+#[derive(Debug, Default)]
+pub struct FeedParser {
+    buf: Vec<u8>,
+    // Start of the unparsed bytes in `buf`
+    pos: usize,
+}
+
+impl FeedParser {
+    pub fn new() -> FeedParser {
+        FeedParser::default()
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        // Drop what has already been parsed before growing the buffer
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Take the next complete document, or `Ok(None)` if more bytes are
+    /// needed first.
+    pub fn next_document(&mut self) -> DecodeResult<Option<Document>> {
+        match self.next_bytes()? {
+            Some(bytes) => Document::from_slice(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Like `next_document`, but hands back the encoded document without
+    /// decoding it.
+    pub fn next_bytes(&mut self) -> DecodeResult<Option<&[u8]>> {
+        let rest = &self.buf[self.pos..];
+
+        if rest.len() < 4 {
+            return Ok(None);
+        }
+
+        let len = frame_len([rest[0], rest[1], rest[2], rest[3]])?;
+
+        if rest.len() < len {
+            return Ok(None);
+        }
+
+        let start = self.pos;
+        self.pos += len;
+
+        Ok(Some(&self.buf[start..start + len]))
+    }
+
+    /// Number of bytes fed in but not yet returned as documents.
+    pub fn buffered(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FeedParser;
+    use crate::doc;
+
+    #[test]
+    fn feed_chunks() {
+        let docs = vec![doc!{"a": 1}, doc!{"b": ["x", "y"]}, doc!{}];
+
+        let mut buf = Vec::new();
+        for doc in &docs {
+            buf.extend(doc.to_vec().unwrap());
+        }
+
+        for chunk_size in &[1, 3, 7, buf.len()] {
+            let mut parser = FeedParser::new();
+            let mut read = Vec::new();
+
+            for chunk in buf.chunks(*chunk_size) {
+                parser.feed(chunk);
+
+                while let Some(doc) = parser.next_document().unwrap() {
+                    read.push(doc);
+                }
+            }
+
+            assert_eq!(read, docs);
+            assert_eq!(parser.buffered(), 0);
+        }
+
+        let mut parser = FeedParser::new();
+        parser.feed(&[1, 0, 0, 0, 0]);
+        assert!(parser.next_document().is_err());
+        assert!(parser.next_document().is_err());
+    }
+}
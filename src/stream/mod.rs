@@ -0,0 +1,38 @@
+//! Reading and writing sequences of documents
+//!
+//! BSON documents carry their own length, so a file or socket can hold any
+//! number of them back to back with no other framing. `DocumentReader` reads
+//! such a sequence one document at a time, and `DocumentWriter` writes a
+//! single document element by element. `FeedParser` is for non-blocking
+//! code: it takes bytes as they arrive and hands back documents once they
+//! are complete. With the `tokio` feature, `DocumentStream` reads documents
+//! from an `AsyncRead` as a `Stream`, and with the `codec` feature
+//! `BsonCodec` frames documents for `tokio_util::codec`.
+use crate::decode::{DecodeError, DecodeResult, MAX_BSON_SIZE};
+
+mod feed;
+mod reader;
+mod writer;
+#[cfg(feature = "tokio")]
+mod document_stream;
+#[cfg(feature = "codec")]
+mod codec;
+
+pub use self::feed::FeedParser;
+pub use self::reader::DocumentReader;
+pub use self::writer::DocumentWriter;
+#[cfg(feature = "tokio")]
+pub use self::document_stream::DocumentStream;
+#[cfg(feature = "codec")]
+pub use self::codec::BsonCodec;
+
+/// Check a document's length prefix, returning the whole document's size.
+pub(crate) fn frame_len(prefix: [u8; 4]) -> DecodeResult<usize> {
+    let len = i32::from_le_bytes(prefix);
+
+    if !(5..=MAX_BSON_SIZE).contains(&len) {
+        return Err(DecodeError::InvalidLength(len as usize, format!("invalid length {} for document", len)));
+    }
+
+    Ok(len as usize)
+}
@@ -0,0 +1,138 @@
+use std::io::{self, Read};
+
+use crate::doc::Document;
+use crate::decode::{DecodeError, DecodeResult};
+
+use super::frame_len;
+
+/// Reads back-to-back documents from a `Read`, such as a mongodump `.bson`
+/// file or a socket.
+///
+/// Running out of input between two documents ends the iteration; running
+/// out in the middle of one is an `EndOfStream` error. Each document is read
+/// in full using its length prefix before it is decoded, so a malformed
+/// document doesn't throw the reader out of step with the input. Iteration
+/// stops after the first error.
+///
+/// ```
+/// use bsonrs::doc;
+/// use bsonrs::stream::DocumentReader;
+///
+/// let mut buf = Vec::new();
+/// buf.extend(doc!{"a": 1}.to_vec().unwrap());
+/// buf.extend(doc!{"b": 2}.to_vec().unwrap());
+///
+/// let docs = DocumentReader::new(&buf[..]).collect::<Result<Vec<_>, _>>().unwrap();
+///
+/// assert_eq!(docs, vec![doc!{"a": 1}, doc!{"b": 2}]);
+/// ```
+#[derive(Debug)]
+pub struct DocumentReader<R> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: Read> DocumentReader<R> {
+    pub fn new(reader: R) -> DocumentReader<R> {
+        DocumentReader {
+            reader,
+            done: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read the next encoded document without decoding it. Returns
+    /// `Ok(None)` at a clean end of input.
+    pub fn read_bytes(&mut self) -> DecodeResult<Option<Vec<u8>>> {
+        let mut prefix = [0; 4];
+        let mut read = 0;
+
+        while read < prefix.len() {
+            match self.reader.read(&mut prefix[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(DecodeError::EndOfStream),
+                Ok(n) => read += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let len = frame_len(prefix)?;
+
+        let mut buf = vec![0; len];
+        buf[..4].copy_from_slice(&prefix);
+
+        match self.reader.read_exact(&mut buf[4..]) {
+            Ok(()) => Ok(Some(buf)),
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Err(DecodeError::EndOfStream),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl<R: Read> Iterator for DocumentReader<R> {
+    type Item = DecodeResult<Document>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let next = self.read_bytes().and_then(|bytes| bytes.map(|b| Document::from_slice(&b)).transpose());
+
+        if !matches!(next, Ok(Some(_))) {
+            self.done = true;
+        }
+
+        next.transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DocumentReader;
+    use crate::decode::DecodeError;
+    use crate::doc;
+
+    #[test]
+    fn read_sequence() {
+        let docs = vec![doc!{"a": 1}, doc!{"b": [1, 2]}, doc!{}];
+
+        let mut buf = Vec::new();
+        for doc in &docs {
+            buf.extend(doc.to_vec().unwrap());
+        }
+
+        let read: Vec<_> = DocumentReader::new(&buf[..]).map(Result::unwrap).collect();
+        assert_eq!(read, docs);
+
+        assert_eq!(DocumentReader::new(&[][..]).count(), 0);
+
+        // Truncated in the middle of the last document
+        let mut reader = DocumentReader::new(&buf[..buf.len() - 2]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(reader.next(), Some(Err(DecodeError::EndOfStream))));
+        assert!(reader.next().is_none());
+
+        // Truncated inside a length prefix
+        let mut reader = DocumentReader::new(&buf[..buf.len() - 3]);
+        reader.next();
+        reader.next();
+        assert!(matches!(reader.next(), Some(Err(DecodeError::EndOfStream))));
+
+        let mut reader = DocumentReader::new(&[1, 0, 0, 0, 0][..]);
+        assert!(matches!(reader.next(), Some(Err(DecodeError::InvalidLength(..)))));
+    }
+}
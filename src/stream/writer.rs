@@ -0,0 +1,210 @@
+use std::io::{Write, Seek, SeekFrom};
+
+use byteorder::WriteBytesExt;
+
+use crate::value::Value;
+use crate::spec::ElementType;
+use crate::encode::{EncodeError, EncodeResult, EncodeOptions, encode_bson_with_options, write_cstring, write_i32};
+
+/// Writes a document into a `Write + Seek` one element at a time, so large
+/// documents never have to be assembled as a `Document` first.
+///
+/// Each document and array gets a placeholder length prefix when it is
+/// opened; when it is closed the writer seeks back to fill in the real
+/// length and then returns to the end. Inside an array, keys passed to
+/// `write`, `begin_document` and `begin_array` are ignored and the element's
+/// index is used instead.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use bsonrs::{doc, Value};
+/// use bsonrs::stream::DocumentWriter;
+///
+/// let mut writer = DocumentWriter::new(Cursor::new(Vec::new())).unwrap();
+/// writer.write("name", &Value::from("bson")).unwrap();
+/// writer.begin_array("tags").unwrap();
+/// writer.write("", &Value::from("a")).unwrap();
+/// writer.write("", &Value::from("b")).unwrap();
+/// writer.end_array().unwrap();
+///
+/// let bytes = writer.finish().unwrap().into_inner();
+///
+/// assert_eq!(bytes, doc!{"name": "bson", "tags": ["a", "b"]}.to_vec().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct DocumentWriter<W> {
+    writer: W,
+    options: EncodeOptions,
+    stack: Vec<Frame>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    // Position of the length prefix
+    start: u64,
+    // Next index, for arrays
+    index: Option<usize>,
+}
+
+impl<W: Write + Seek> DocumentWriter<W> {
+    /// Start a top-level document at the writer's current position.
+    pub fn new(writer: W) -> EncodeResult<DocumentWriter<W>> {
+        DocumentWriter::with_options(writer, EncodeOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: EncodeOptions) -> EncodeResult<DocumentWriter<W>> {
+        let mut writer = DocumentWriter {
+            writer,
+            options,
+            stack: Vec::new(),
+        };
+
+        writer.open(None)?;
+
+        Ok(writer)
+    }
+
+    /// Write a single element into the innermost open document or array.
+    pub fn write(&mut self, key: &str, value: &Value) -> EncodeResult<()> {
+        if self.options.omit_null && *value == Value::Null && !self.in_array() {
+            return Ok(());
+        }
+
+        let key = self.next_key(key);
+        encode_bson_with_options(&mut self.writer, &key, value, self.options)
+    }
+
+    pub fn begin_document(&mut self, key: &str) -> EncodeResult<()> {
+        self.begin(key, ElementType::Document, None)
+    }
+
+    pub fn begin_array(&mut self, key: &str) -> EncodeResult<()> {
+        self.begin(key, ElementType::Array, Some(0))
+    }
+
+    /// Close the innermost document opened with `begin_document`.
+    pub fn end_document(&mut self) -> EncodeResult<()> {
+        if self.stack.len() < 2 || self.in_array() {
+            return Err(EncodeError::Unknown("end_document without a matching begin_document".to_string()));
+        }
+
+        self.close()
+    }
+
+    /// Close the innermost array opened with `begin_array`.
+    pub fn end_array(&mut self) -> EncodeResult<()> {
+        if !self.in_array() {
+            return Err(EncodeError::Unknown("end_array without a matching begin_array".to_string()));
+        }
+
+        self.close()
+    }
+
+    /// Close anything still open, including the top-level document, and
+    /// return the underlying writer.
+    pub fn finish(mut self) -> EncodeResult<W> {
+        while !self.stack.is_empty() {
+            self.close()?;
+        }
+
+        Ok(self.writer)
+    }
+
+    fn in_array(&self) -> bool {
+        matches!(self.stack.last(), Some(Frame { index: Some(_), .. }))
+    }
+
+    fn next_key(&mut self, key: &str) -> String {
+        match self.stack.last_mut() {
+            Some(Frame { index: Some(index), .. }) => {
+                *index += 1;
+                (*index - 1).to_string()
+            }
+            _ => key.to_string(),
+        }
+    }
+
+    fn begin(&mut self, key: &str, element_type: ElementType, index: Option<usize>) -> EncodeResult<()> {
+        let key = self.next_key(key);
+        self.writer.write_u8(element_type as u8)?;
+        write_cstring(&mut self.writer, &key)?;
+        self.open(index)
+    }
+
+    fn open(&mut self, index: Option<usize>) -> EncodeResult<()> {
+        let start = self.writer.stream_position()?;
+        write_i32(&mut self.writer, 0)?;
+        self.stack.push(Frame { start, index });
+        Ok(())
+    }
+
+    fn close(&mut self) -> EncodeResult<()> {
+        let frame = self.stack.pop().expect("no open document");
+
+        self.writer.write_u8(0)?;
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(frame.start))?;
+        write_i32(&mut self.writer, (end - frame.start) as i32)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::DocumentWriter;
+    use crate::encode::EncodeOptions;
+    use crate::{doc, Value};
+
+    #[test]
+    fn nested() {
+        let mut writer = DocumentWriter::new(Cursor::new(Vec::new())).unwrap();
+
+        writer.write("a", &Value::Int32(1)).unwrap();
+        writer.begin_document("b").unwrap();
+        writer.write("c", &Value::from("x")).unwrap();
+        writer.begin_array("d").unwrap();
+        writer.write("ignored", &Value::Int32(1)).unwrap();
+        writer.begin_document("ignored").unwrap();
+        writer.write("e", &Value::Null).unwrap();
+        writer.end_document().unwrap();
+        writer.begin_array("").unwrap();
+        writer.end_array().unwrap();
+        assert!(writer.end_document().is_err());
+        writer.end_array().unwrap();
+        writer.end_document().unwrap();
+        assert!(writer.end_document().is_err());
+        assert!(writer.end_array().is_err());
+        writer.write("f", &Value::Boolean(true)).unwrap();
+
+        let bytes = writer.finish().unwrap().into_inner();
+        let expected = doc!{"a": 1, "b": {"c": "x", "d": [1, {"e": null}, (Value::Array(crate::Array::new()))]}, "f": true};
+
+        assert_eq!(bytes, expected.to_vec().unwrap());
+    }
+
+    #[test]
+    fn finish_closes_and_omits_null() {
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.get_mut().extend(doc!{"first": 1}.to_vec().unwrap());
+        cursor.set_position(cursor.get_ref().len() as u64);
+
+        let options = EncodeOptions { omit_null: true, ..Default::default() };
+        let mut writer = DocumentWriter::with_options(cursor, options).unwrap();
+        writer.write("a", &Value::Null).unwrap();
+        writer.begin_array("b").unwrap();
+        writer.write("", &Value::Null).unwrap();
+
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut expected = doc!{"first": 1}.to_vec().unwrap();
+        expected.extend(doc!{"b": [null]}.to_vec().unwrap());
+
+        assert_eq!(bytes, expected);
+    }
+}
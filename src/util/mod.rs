@@ -1,2 +1,2 @@
 pub mod hex;
-pub mod md5;
+pub mod base64;
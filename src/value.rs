@@ -1,17 +1,23 @@
-use std::fmt;
-use std::ops::{Deref, DerefMut};
+use std::{fmt, error};
+use std::convert::TryFrom;
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::ops::{Range, RangeFrom, RangeTo, RangeFull, RangeInclusive, RangeToInclusive};
 use std::{f64, i64, u64};
-use std::iter::FromIterator;
+use std::iter::{FromIterator, Extend};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
 
-use chrono::{DateTime, Utc, Timelike};
+use chrono::{DateTime, Utc};
 use chrono::offset::TimeZone;
-use serde_json;
+#[cfg(feature = "json")]
 use serde_json::json;
 
-use crate::doc::Document;
+use crate::doc::{Document, GetError};
 use crate::spec::{ElementType, BinarySubtype};
 use crate::util::hex::{ToHex, FromHex};
+use crate::util::base64;
 use crate::object_id::ObjectId;
+use crate::decimal128::Decimal128;
 use crate::doc;
 
 #[derive(Clone, PartialEq)]
@@ -24,14 +30,22 @@ pub enum Value {
     Null,
     RegExp(String, String),
     JavaScriptCode(String),
-    JavaScriptCodeWithScope(String, Document),
+    /// The scope is boxed so that this rarely used variant doesn't make
+    /// every `Value` bigger.
+    JavaScriptCodeWithScope(String, Box<Document>),
     Int32(i32),
     Int64(i64),
-    TimeStamp(u64),
+    TimeStamp(TimeStamp),
     Binary(BinarySubtype, Vec<u8>),
     ObjectId(ObjectId),
     UTCDatetime(DateTime<Utc>),
-    Symbol(String)
+    Symbol(String),
+    Decimal128(Decimal128),
+    MinKey,
+    MaxKey,
+    /// Deprecated in the BSON spec; only kept so legacy documents can be
+    /// read and written back unchanged.
+    Undefined
 }
 
 impl Eq for Value {}
@@ -57,25 +71,37 @@ impl fmt::Debug for Value {
             }
             Value::Int32(v) => write!(fmt, "Int32({:?})", v),
             Value::Int64(v) => write!(fmt, "Int64({:?})", v),
-            Value::TimeStamp(i) => {
-                let time = (i >> 32) as u32;
-                let inc = (i & 0xFFFF_FFFF) as u32;
-
-                write!(fmt, "TimeStamp({}, {})", time, inc)
-            }
+            Value::TimeStamp(ts) => write!(fmt, "TimeStamp({}, {})", ts.timestamp, ts.increment),
             Value::Binary(t, ref vec) => write!(fmt, "BinData({}, 0x{})", u8::from(t), vec.to_hex()),
             Value::ObjectId(ref id) => write!(fmt, "ObjectId({})", id),
             Value::UTCDatetime(date_time) => write!(fmt, "UTCDatetime({:?})", date_time),
-            Value::Symbol(ref sym) => write!(fmt, "Symbol({:?})", sym)
+            Value::Symbol(ref sym) => write!(fmt, "Symbol({:?})", sym),
+            Value::Decimal128(ref d) => write!(fmt, "{:?}", d),
+            Value::MinKey => write!(fmt, "MinKey"),
+            Value::MaxKey => write!(fmt, "MaxKey"),
+            Value::Undefined => write!(fmt, "Undefined")
         }
     }
 }
 
 impl fmt::Display for Value {
+    /// Relaxed Extended JSON, which `Document::from_extjson_str` parses back
+    /// into the same value. Only `Int64`s that fit an `Int32` come back
+    /// narrower, as relaxed Extended JSON doesn't tell them apart. `{:#}`
+    /// lays nested documents and arrays out over indented lines.
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if fmt.alternate() {
+            return self.fmt_pretty(fmt, 0);
+        }
+
         match *self {
-            Value::Double(f) => write!(fmt, "{}", f),
-            Value::String(ref s) => write!(fmt, "\"{}\"", s),
+            // `{:?}` keeps the `.0` on whole numbers, so they stay doubles
+            Value::Double(f) if f.is_finite() => write!(fmt, "{:?}", f),
+            Value::Double(f) => {
+                let s = if f.is_nan() { "NaN" } else if f > 0.0 { "Infinity" } else { "-Infinity" };
+                write!(fmt, "{{ \"$numberDouble\": \"{}\" }}", s)
+            }
+            Value::String(ref s) => write_json_str(fmt, s),
             Value::Array(ref vec) => {
                 write!(fmt, "[")?;
 
@@ -94,27 +120,85 @@ impl fmt::Display for Value {
             Value::Document(ref doc) => write!(fmt, "{}", doc),
             Value::Boolean(b) => write!(fmt, "{}", b),
             Value::Null => write!(fmt, "null"),
-            Value::RegExp(ref pat, ref opt) => write!(fmt, "/{}/{}", pat, opt),
-            Value::JavaScriptCode(ref s) |
-            Value::JavaScriptCodeWithScope(ref s, _) => fmt.write_str(&s),
+            Value::RegExp(ref pat, ref opt) => {
+                write!(fmt, "{{ \"$regularExpression\": {{ \"pattern\": ")?;
+                write_json_str(fmt, pat)?;
+                write!(fmt, ", \"options\": ")?;
+                write_json_str(fmt, opt)?;
+                write!(fmt, " }} }}")
+            }
+            Value::JavaScriptCode(ref code) => {
+                write!(fmt, "{{ \"$code\": ")?;
+                write_json_str(fmt, code)?;
+                write!(fmt, " }}")
+            }
+            Value::JavaScriptCodeWithScope(ref code, ref scope) => {
+                write!(fmt, "{{ \"$code\": ")?;
+                write_json_str(fmt, code)?;
+                write!(fmt, ", \"$scope\": {} }}", scope)
+            }
             Value::Int32(i) => write!(fmt, "{}", i),
             Value::Int64(i) => write!(fmt, "{}", i),
-            Value::TimeStamp(i) => {
-                let time = (i >> 32) as u32;
-                let inc = (i & 0xFFFF_FFFF) as u32;
-
-                write!(fmt, "Timestamp({}, {})", time, inc)
+            Value::TimeStamp(ts) => {
+                write!(fmt, "{{ \"$timestamp\": {{ \"t\": {}, \"i\": {} }} }}", ts.timestamp, ts.increment)
             }
             Value::Binary(t, ref vec) => {
-                write!(fmt, "BinData({}, 0x{})", u8::from(t), vec.to_hex())
+                write!(
+                    fmt,
+                    "{{ \"$binary\": {{ \"base64\": \"{}\", \"subType\": \"{:02x}\" }} }}",
+                    base64::encode(vec),
+                    u8::from(t)
+                )
+            }
+            Value::ObjectId(ref id) => write!(fmt, "{{ \"$oid\": \"{}\" }}", id),
+            Value::UTCDatetime(date_time) => {
+                // Relaxed form only covers years 1970 through 9999
+                if (0..=253_402_300_799_999).contains(&date_time.timestamp_millis()) {
+                    write!(fmt, "{{ \"$date\": \"{}\" }}", date_time.format("%Y-%m-%dT%H:%M:%S%.3fZ"))
+                } else {
+                    write!(fmt, "{{ \"$date\": {{ \"$numberLong\": \"{}\" }} }}", date_time.timestamp_millis())
+                }
+            }
+            Value::Symbol(ref sym) => {
+                write!(fmt, "{{ \"$symbol\": ")?;
+                write_json_str(fmt, sym)?;
+                write!(fmt, " }}")
             }
-            Value::ObjectId(ref id) => write!(fmt, "ObjectId(\"{}\")", id),
-            Value::UTCDatetime(date_time) => write!(fmt, "Date(\"{}\")", date_time),
-            Value::Symbol(ref sym) => write!(fmt, "Symbol(\"{}\")", sym)
+            Value::Decimal128(ref d) => write!(fmt, "{{ \"$numberDecimal\": \"{}\" }}", d),
+            Value::MinKey => write!(fmt, "{{ \"$minKey\": 1 }}"),
+            Value::MaxKey => write!(fmt, "{{ \"$maxKey\": 1 }}"),
+            Value::Undefined => write!(fmt, "{{ \"$undefined\": true }}")
         }
     }
 }
 
+/// Write `s` as a quoted JSON string, escaping quotes, backslashes and
+/// control characters so the output parses back and can't break a line.
+pub(crate) fn write_json_str(fmt: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    fmt.write_str("\"")?;
+
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c != '"' && c != '\\' && c >= ' ' {
+            continue;
+        }
+
+        fmt.write_str(&s[start..i])?;
+        match c {
+            '"' => fmt.write_str("\\\"")?,
+            '\\' => fmt.write_str("\\\\")?,
+            '\n' => fmt.write_str("\\n")?,
+            '\r' => fmt.write_str("\\r")?,
+            '\t' => fmt.write_str("\\t")?,
+            _ => write!(fmt, "\\u{:04x}", c as u32)?,
+        }
+        start = i + 1;
+    }
+
+    fmt.write_str(&s[start..])?;
+    fmt.write_str("\"")
+}
+
 impl From<f32> for Value {
     fn from(f: f32) -> Value {
         Value::Double(f64::from(f))
@@ -217,12 +301,51 @@ impl From<ObjectId> for Value {
     }
 }
 
+impl From<Decimal128> for Value {
+    fn from(d: Decimal128) -> Value {
+        Value::Decimal128(d)
+    }
+}
+
 impl From<DateTime<Utc>> for Value {
     fn from(d: DateTime<Utc>) -> Value {
         Value::UTCDatetime(d)
     }
 }
 
+impl From<IpAddr> for Value {
+    fn from(addr: IpAddr) -> Value {
+        Value::from_ip_addr(addr, AddrFormat::String)
+    }
+}
+
+impl From<SocketAddr> for Value {
+    fn from(addr: SocketAddr) -> Value {
+        Value::from_socket_addr(addr, AddrFormat::String)
+    }
+}
+
+impl From<Duration> for Value {
+    fn from(d: Duration) -> Value {
+        Value::from_duration(d, DurationFormat::Millis)
+    }
+}
+
+impl From<chrono::Duration> for Value {
+    fn from(d: chrono::Duration) -> Value {
+        Value::Int64(d.num_milliseconds())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(o: Option<T>) -> Value {
+        match o {
+            Some(v) => v.into(),
+            None => Value::Null,
+        }
+    }
+}
+
 macro_rules! value_from_impls {
     ($($T:ty)+) => {
         $(
@@ -238,6 +361,7 @@ macro_rules! value_from_impls {
 value_from_impls! {
     f32 f64 i32 i64 &str String &String Array
     Document bool DateTime<Utc> Vec<u8> ObjectId
+    Decimal128
 }
 
 impl Value {
@@ -258,7 +382,64 @@ impl Value {
             Value::Binary(..) => ElementType::Binary,
             Value::ObjectId(..) => ElementType::ObjectId,
             Value::UTCDatetime(..) => ElementType::UTCDatetime,
-            Value::Symbol(..) => ElementType::Symbol
+            Value::Symbol(..) => ElementType::Symbol,
+            Value::Decimal128(..) => ElementType::Decimal128,
+            Value::MinKey => ElementType::MinKey,
+            Value::MaxKey => ElementType::MaxKey,
+            Value::Undefined => ElementType::Undefiend
+        }
+    }
+
+    /// Pretty-print at nesting level `indent`; anything that isn't a
+    /// document or array is written as its plain `Display` form.
+    pub(crate) fn fmt_pretty(&self, fmt: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        match self {
+            Value::Document(doc) => doc.fmt_pretty(fmt, indent),
+            Value::Array(arr) if arr.is_empty() => fmt.write_str("[]"),
+            Value::Array(arr) => {
+                writeln!(fmt, "[")?;
+
+                for (i, v) in arr.iter().enumerate() {
+                    write!(fmt, "{:1$}", "", (indent + 1) * 4)?;
+                    v.fmt_pretty(fmt, indent + 1)?;
+                    writeln!(fmt, "{}", if i + 1 < arr.len() { "," } else { "" })?;
+                }
+
+                write!(fmt, "{:1$}]", "", indent * 4)
+            }
+            other => write!(fmt, "{}", other),
+        }
+    }
+
+    /// Size in bytes of this value encoded as an element named `key`,
+    /// computed without encoding it.
+    pub fn encoded_len(&self, key: &str) -> usize {
+        1 + key.len() + 1 + self.value_len()
+    }
+
+    /// Size of the encoded value alone, without the element header.
+    pub(crate) fn value_len(&self) -> usize {
+        match self {
+            Value::Double(..) | Value::Int64(..) | Value::TimeStamp(..) | Value::UTCDatetime(..) => 8,
+            Value::String(s) | Value::JavaScriptCode(s) | Value::Symbol(s) => 4 + s.len() + 1,
+            Value::Array(array) => {
+                // Keys are the decimal indexes
+                let elements: usize = array.iter().enumerate()
+                    .map(|(i, v)| 1 + decimal_len(i) + 1 + v.value_len())
+                    .sum();
+
+                4 + elements + 1
+            }
+            Value::Document(doc) => doc.encoded_len(),
+            Value::Boolean(..) => 1,
+            Value::RegExp(pat, opt) => pat.len() + 1 + opt.len() + 1,
+            Value::JavaScriptCodeWithScope(code, scope) => 4 + 4 + code.len() + 1 + scope.encoded_len(),
+            Value::Int32(..) => 4,
+            Value::Binary(BinarySubtype::BinaryOld, data) => 4 + 1 + 4 + data.len(),
+            Value::Binary(_, data) => 4 + 1 + data.len(),
+            Value::ObjectId(..) => 12,
+            Value::Decimal128(..) => 16,
+            Value::Null | Value::MinKey | Value::MaxKey | Value::Undefined => 0,
         }
     }
 
@@ -325,6 +506,26 @@ impl Value {
         }
     }
 
+    /// Equality where the keys of documents, at any depth, may be in a
+    /// different order, while arrays have to match element by element. This
+    /// is just `==`, see `Document::eq_unordered`.
+    pub fn eq_unordered(&self, other: &Value) -> bool {
+        self == other
+    }
+
+    /// Milliseconds since the Unix epoch of a `UTCDatetime`, negative before
+    /// 1970. This is exactly what is stored in BSON.
+    pub fn datetime_millis(&self) -> Option<i64> {
+        self.as_utc_date_time().map(DateTime::timestamp_millis)
+    }
+
+    /// A `UTCDatetime` from milliseconds since the Unix epoch, or `None` if
+    /// it is outside the range `DateTime<Utc>` can represent. Decoding
+    /// clamps such values to that range instead.
+    pub fn datetime_from_millis(millis: i64) -> Option<Value> {
+        Utc.timestamp_millis_opt(millis).single().map(Value::UTCDatetime)
+    }
+
     pub fn as_symbol(&self) -> Option<&str> {
         match self {
             Value::Symbol(ref v) => Some(v),
@@ -332,7 +533,14 @@ impl Value {
         }
     }
 
-    pub fn as_timestamp(&self) -> Option<u64> {
+    pub fn as_decimal128(&self) -> Option<&Decimal128> {
+        match self {
+            Value::Decimal128(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_timestamp(&self) -> Option<TimeStamp> {
         match self {
             Value::TimeStamp(v) => Some(*v),
             _ => None,
@@ -353,16 +561,329 @@ impl Value {
         }
     }
 
+    /// The pattern and options of a regular expression.
+    pub fn as_regexp(&self) -> Option<(&str, &str)> {
+        match self {
+            Value::RegExp(pat, opt) => Some((pat, opt)),
+            _ => None,
+        }
+    }
+
+    pub fn as_javascript_code(&self) -> Option<&str> {
+        match self {
+            Value::JavaScriptCode(code) => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn as_code_with_scope(&self) -> Option<(&str, &Document)> {
+        match self {
+            Value::JavaScriptCodeWithScope(code, scope) => Some((code, scope)),
+            _ => None,
+        }
+    }
+
+    pub fn as_document_mut(&mut self) -> Option<&mut Document> {
+        match self {
+            Value::Document(doc) => Some(doc),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Array> {
+        match self {
+            Value::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    /// A 16 byte `BinarySubtype::Uuid` binary as a `Uuid`.
+    #[cfg(feature = "uuid")]
+    pub fn as_uuid(&self) -> Option<uuid::Uuid> {
+        match self {
+            Value::Binary(BinarySubtype::Uuid, d) => uuid::Uuid::from_slice(d).ok(),
+            _ => None
+        }
+    }
+
+    /// Store an IP address as its text form or as a 4 or 16 byte binary.
+    pub fn from_ip_addr(addr: IpAddr, format: AddrFormat) -> Value {
+        match format {
+            AddrFormat::String => Value::String(addr.to_string()),
+            AddrFormat::Binary => Value::Binary(BinarySubtype::Generic, ip_octets(addr)),
+        }
+    }
+
+    /// Store a socket address as its text form, or as the address octets
+    /// followed by the port in big-endian order (6 or 18 bytes).
+    pub fn from_socket_addr(addr: SocketAddr, format: AddrFormat) -> Value {
+        match format {
+            AddrFormat::String => Value::String(addr.to_string()),
+            AddrFormat::Binary => {
+                let mut bytes = ip_octets(addr.ip());
+                bytes.extend_from_slice(&addr.port().to_be_bytes());
+                Value::Binary(BinarySubtype::Generic, bytes)
+            }
+        }
+    }
+
+    /// Read an IP address stored in either `AddrFormat`.
+    pub fn as_ip_addr(&self) -> Option<IpAddr> {
+        match self {
+            Value::String(s) => s.parse().ok(),
+            Value::Binary(BinarySubtype::Generic, bytes) => ip_from_octets(bytes),
+            _ => None
+        }
+    }
+
+    /// Read a socket address stored in either `AddrFormat`.
+    pub fn as_socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Value::String(s) => s.parse().ok(),
+            Value::Binary(BinarySubtype::Generic, bytes) if bytes.len() == 6 || bytes.len() == 18 => {
+                let (ip, port) = bytes.split_at(bytes.len() - 2);
+                Some(SocketAddr::new(ip_from_octets(ip)?, u16::from_be_bytes([port[0], port[1]])))
+            }
+            _ => None
+        }
+    }
+
+    /// Store a duration as `Int64` milliseconds (saturating, and dropping
+    /// sub-millisecond precision) or as a `{secs, nanos}` document.
+    pub fn from_duration(d: Duration, format: DurationFormat) -> Value {
+        match format {
+            DurationFormat::Millis => {
+                Value::Int64(d.as_millis().min(i64::MAX as u128) as i64)
+            }
+            DurationFormat::SecsNanos => {
+                Value::Document(doc!{
+                    "secs": d.as_secs().min(i64::MAX as u64) as i64,
+                    "nanos": d.subsec_nanos() as i32
+                })
+            }
+        }
+    }
+
+    /// Read a duration stored in either `DurationFormat`. Negative values
+    /// aren't valid durations.
+    pub fn as_duration(&self) -> Option<Duration> {
+        match self {
+            Value::Int32(v) if *v >= 0 => Some(Duration::from_millis(*v as u64)),
+            Value::Int64(v) if *v >= 0 => Some(Duration::from_millis(*v as u64)),
+            Value::Document(doc) if doc.len() == 2 => {
+                let secs = doc.get_i64("secs").ok()?;
+                let nanos = doc.get_i32("nanos").ok()?;
+
+                if secs < 0 || !(0..1_000_000_000).contains(&nanos) {
+                    return None;
+                }
+
+                Some(Duration::new(secs as u64, nanos as u32))
+            }
+            _ => None
+        }
+    }
+
+    /// Read a (possibly negative) number of milliseconds as a chrono duration.
+    pub fn as_chrono_duration(&self) -> Option<chrono::Duration> {
+        match self {
+            Value::Int32(v) => Some(chrono::Duration::milliseconds(i64::from(*v))),
+            Value::Int64(v) => chrono::Duration::try_milliseconds(*v),
+            _ => None
+        }
+    }
+
+    /// Move the string out, or give the value back if it isn't one. The
+    /// `into_*` accessors are the consuming counterparts of `as_*`, for
+    /// taking large decoded values apart without cloning.
+    pub fn into_string(self) -> Result<String, Value> {
+        match self {
+            Value::String(s) => Ok(s),
+            other => Err(other),
+        }
+    }
+
+    pub fn into_document(self) -> Result<Document, Value> {
+        match self {
+            Value::Document(doc) => Ok(doc),
+            other => Err(other),
+        }
+    }
+
+    pub fn into_array(self) -> Result<Array, Value> {
+        match self {
+            Value::Array(arr) => Ok(arr),
+            other => Err(other),
+        }
+    }
+
+    pub fn into_binary(self) -> Result<(BinarySubtype, Vec<u8>), Value> {
+        match self {
+            Value::Binary(t, d) => Ok((t, d)),
+            other => Err(other),
+        }
+    }
+
+    pub fn into_symbol(self) -> Result<String, Value> {
+        match self {
+            Value::Symbol(s) => Ok(s),
+            other => Err(other),
+        }
+    }
+
+    /// The pattern and options of a regular expression.
+    pub fn into_regexp(self) -> Result<(String, String), Value> {
+        match self {
+            Value::RegExp(pat, opt) => Ok((pat, opt)),
+            other => Err(other),
+        }
+    }
+
+    pub fn into_javascript_code(self) -> Result<String, Value> {
+        match self {
+            Value::JavaScriptCode(code) => Ok(code),
+            other => Err(other),
+        }
+    }
+
+    pub fn into_code_with_scope(self) -> Result<(String, Document), Value> {
+        match self {
+            Value::JavaScriptCodeWithScope(code, scope) => Ok((code, *scope)),
+            other => Err(other),
+        }
+    }
+
+    /// Compare two values in constant time with respect to the contents of
+    /// `Binary` and `String` data, so secrets such as tokens (typically stored
+    /// as `BinarySubtype::Sensitive`) can be checked without leaking timing
+    /// information. Lengths and subtypes are not treated as secret, and all
+    /// other variants fall back to ordinary equality.
+    #[cfg(feature = "subtle")]
+    pub fn ct_eq(&self, other: &Value) -> bool {
+        use subtle::ConstantTimeEq;
+
+        match (self, other) {
+            (Value::Binary(t1, a), Value::Binary(t2, b)) => {
+                t1 == t2 && bool::from(a.as_slice().ct_eq(b.as_slice()))
+            }
+            (Value::String(a), Value::String(b)) => {
+                bool::from(a.as_bytes().ct_eq(b.as_bytes()))
+            }
+            _ => self == other
+        }
+    }
+
+    /// Convert to JSON using the extended forms for BSON-only types. NaN and
+    /// infinite doubles become `null`; use `to_json_with` to reject them or
+    /// keep them as Extended JSON instead. The JSON is built straight from
+    /// references, so nothing is cloned on the way.
+    #[cfg(feature = "json")]
     pub fn to_json(&self) -> serde_json::Value {
-        self.clone().into()
+        match self {
+            Value::Double(v) => json!(v),
+            Value::String(v) => json!(v),
+            Value::Array(v) => json!(**v),
+            Value::Document(v) => json!(v),
+            Value::Boolean(v) => json!(v),
+            Value::Null => serde_json::Value::Null,
+            Value::RegExp(pat, opt) => {
+                json!({
+                    "$regex": pat,
+                    "$options": opt
+                })
+            }
+            Value::JavaScriptCode(code) => json!({"$code": code}),
+            Value::JavaScriptCodeWithScope(code, scope) => {
+                json!({
+                    "$code": code,
+                    "scope": scope
+                })
+            }
+            Value::Int32(v) => (*v).into(),
+            Value::Int64(v) => (*v).into(),
+            Value::TimeStamp(v) => {
+                json!({
+                    "t": v.timestamp,
+                    "i": v.increment
+                })
+            }
+            Value::Binary(t, v) => {
+                let tval: u8 = From::from(*t);
+                json!({
+                    "type": tval,
+                    "$binary": v.to_hex()
+                })
+            }
+            Value::ObjectId(v) => json!({"$oid": v.to_string()}),
+            Value::UTCDatetime(v) => {
+                json!({
+                    "$date": {
+                        "$numberLong": v.timestamp_millis()
+                    }
+                })
+            }
+            // FIXME: Don't know what is the best way to encode Symbol type
+            Value::Symbol(v) => json!({"$symbol": v}),
+            Value::Decimal128(v) => json!({"$numberDecimal": v.to_string()}),
+            Value::MinKey => json!({"$minKey": 1}),
+            Value::MaxKey => json!({"$maxKey": 1}),
+            Value::Undefined => json!({"$undefined": true}),
+        }
+    }
+
+    /// Like `to_json`, with NaN and infinite doubles, including those inside
+    /// documents, arrays and code scopes, handled as `non_finite` says
+    /// instead of always becoming `null`.
+    #[cfg(feature = "json")]
+    pub fn to_json_with(&self, non_finite: NonFiniteFloats) -> Result<serde_json::Value, JsonError> {
+        Ok(match self {
+            Value::Double(f) if !f.is_finite() => match non_finite {
+                NonFiniteFloats::Null => serde_json::Value::Null,
+                NonFiniteFloats::Error => return Err(JsonError::NonFiniteNumber(*f)),
+                NonFiniteFloats::ExtendedJson => {
+                    let s = if f.is_nan() { "NaN" } else if *f > 0.0 { "Infinity" } else { "-Infinity" };
+                    json!({"$numberDouble": s})
+                }
+            },
+            Value::Array(arr) => {
+                serde_json::Value::Array(
+                    arr.iter().map(|v| v.to_json_with(non_finite)).collect::<Result<_, _>>()?
+                )
+            }
+            Value::Document(doc) => doc.to_json_with(non_finite)?,
+            Value::JavaScriptCodeWithScope(code, scope) => {
+                json!({
+                    "$code": code,
+                    "scope": scope.to_json_with(non_finite)?
+                })
+            }
+            other => other.to_json(),
+        })
     }
 
+    /// Like `to_json`, consuming the value.
+    #[cfg(feature = "json")]
     pub fn into_json(self) -> serde_json::Value {
-        self.into()
+        self.to_json()
     }
 
+    /// Convert from JSON, recognising extended forms such as `{"$oid": ...}`.
+    /// Malformed wrappers are kept as plain documents; use
+    /// `Value::try_from` to reject them instead.
+    #[cfg(feature = "json")]
     pub fn from_json(val: serde_json::Value) -> Value {
-        val.into()
+        match val {
+            serde_json::Value::Number(x) => from_json_number(&x.to_string()),
+            serde_json::Value::String(x) => x.into(),
+            serde_json::Value::Bool(x) => x.into(),
+            serde_json::Value::Array(x) => Value::Array(x.into_iter().map(Value::from_json).collect()),
+            serde_json::Value::Object(x) => {
+                Value::from_extended_document(
+                    x.into_iter().map(|(k, v)| (k, Value::from_json(v))).collect()
+                )
+            }
+            serde_json::Value::Null => Value::Null,
+        }
     }
 
     pub fn to_extended_document(&self) -> Document {
@@ -381,16 +902,13 @@ impl Value {
             Value::JavaScriptCodeWithScope(ref code, ref scope) => {
                 doc!{
                     "$code": code.clone(),
-                    "$scope": scope.clone()
+                    "$scope": (**scope).clone()
                 }
             }
             Value::TimeStamp(v) => {
-                let time = (v >> 32) as i64;
-                let inc = (v & 0xFFFF_FFFF) as i64;
-
                 doc!{
-                    "t": time,
-                    "i": inc
+                    "t": i64::from(v.timestamp),
+                    "i": i64::from(v.increment)
                 }
             }
             Value::Binary(t, ref v) => {
@@ -408,7 +926,7 @@ impl Value {
             Value::UTCDatetime(ref v) => {
                 doc!{
                     "$date": {
-                        "$numberLong": v.timestamp() * 1000 + i64::from(v.nanosecond()) / 1_000_000
+                        "$numberLong": v.timestamp_millis()
                     }
                 }
             }
@@ -417,125 +935,243 @@ impl Value {
                     "$symbol": v.to_owned()
                 }
             }
+            Value::Decimal128(ref v) => {
+                doc!{
+                    "$numberDecimal": v.to_string()
+                }
+            }
+            Value::MinKey => {
+                doc!{
+                    "$minKey": 1
+                }
+            }
+            Value::MaxKey => {
+                doc!{
+                    "$maxKey": 1
+                }
+            }
+            Value::Undefined => {
+                doc!{
+                    "$undefined": true
+                }
+            }
             _ => panic!("Attempted conversion of invalid data type: {}", self)
         }
     }
 
     pub fn from_extended_document(values: Document) -> Value {
+        match Value::parse_extended_document(&values) {
+            Ok(Some(value)) => value,
+            _ => Value::Document(values)
+        }
+    }
+
+    /// Like `from_extended_document`, but fails on a recognised wrapper with
+    /// malformed contents (bad hex, an out of range date, ...) instead of
+    /// keeping it as a plain document.
+    pub fn try_from_extended_document(values: Document) -> Result<Value, JsonError> {
+        Ok(Value::parse_extended_document(&values)?.unwrap_or(Value::Document(values)))
+    }
+
+    fn parse_extended_document(values: &Document) -> Result<Option<Value>, JsonError> {
+        let invalid = |key: &str| JsonError::InvalidExtendedJson(key.to_string());
+
         if values.len() == 2 {
             if let (Ok(pat), Ok(opt)) = (values.get_str("$regex"), values.get_str("$options")) {
-                return Value::RegExp(pat.to_owned(), opt.to_owned());
+                return Ok(Some(Value::RegExp(pat.to_owned(), opt.to_owned())));
 
             } else if let (Ok(code), Ok(scope)) =
                 (values.get_str("$code"), values.get_document("$scope")) {
-                return Value::JavaScriptCodeWithScope(code.to_owned(), scope.clone());
+                return Ok(Some(Value::JavaScriptCodeWithScope(code.to_owned(), Box::new(scope.clone()))));
 
             } else if let (Ok(t), Ok(i)) = (values.get_i32("t"), values.get_i32("i")) {
                 let timestamp = (i64::from(t) << 32) + i64::from(i);
-                return Value::TimeStamp(timestamp as u64);
+                return Ok(Some(Value::TimeStamp(TimeStamp::from(timestamp as u64))));
 
             } else if let (Ok(t), Ok(i)) = (values.get_i64("t"), values.get_i64("i")) {
                 let timestamp = (t << 32) + i;
-                return Value::TimeStamp(timestamp as u64);
-
-            } else if let (Ok(hex), Ok(t)) = (values.get_str("$binary"), values.get_i32("type")) {
-                let ttype = t as u8;
-                return Value::Binary(From::from(ttype), FromHex::from_hex(hex.as_bytes()).unwrap());
+                return Ok(Some(Value::TimeStamp(TimeStamp::from(timestamp as u64))));
+
+            } else if let (Ok(hex), Some(t)) = (values.get_str("$binary"), values.get("type")) {
+                let ttype = match *t {
+                    Value::Int32(t) => t as u8,
+                    Value::Int64(t) => t as u8,
+                    _ => return Err(invalid("type")),
+                };
+                let data = FromHex::from_hex(hex.as_bytes()).map_err(|_| invalid("$binary"))?;
+                return Ok(Some(Value::Binary(From::from(ttype), data)));
             }
 
         } else if values.len() == 1 {
             if let Ok(code) = values.get_str("$code") {
-                return Value::JavaScriptCode(code.to_string());
+                return Ok(Some(Value::JavaScriptCode(code.to_string())));
 
             } else if let Ok(hex) = values.get_str("$oid") {
-                return Value::ObjectId(ObjectId::with_string(hex).unwrap());
+                let id = ObjectId::with_string(hex).map_err(|_| invalid("$oid"))?;
+                return Ok(Some(Value::ObjectId(id)));
 
             } else if let Ok(long) = values.get_document("$date").and_then(|inner| inner.get_i64("$numberLong")) {
-                return Value::UTCDatetime(Utc.timestamp(long / 1000, ((long % 1000) * 1_000_000) as u32));
+                return Ok(Some(Value::UTCDatetime(clamped_datetime(long))));
+
+            } else if let Ok(number) = values.get_str(JSON_NUMBER_TOKEN) {
+                return Ok(Some(from_json_number(number)));
+
             } else if let Ok(sym) = values.get_str("$symbol") {
-                return Value::Symbol(sym.to_string());
-            }
-        }
+                return Ok(Some(Value::Symbol(sym.to_string())));
 
-        Value::Document(values)
-    }
-}
+            } else if let Ok(s) = values.get_str("$numberDouble") {
+                let f = match s {
+                    "Infinity" => f64::INFINITY,
+                    "-Infinity" => f64::NEG_INFINITY,
+                    "NaN" => f64::NAN,
+                    _ => s.parse().map_err(|_| invalid("$numberDouble"))?,
+                };
+                return Ok(Some(Value::Double(f)));
 
-impl From<serde_json::Value> for Value {
-    fn from(a: serde_json::Value) -> Value {
-        match a {
-            serde_json::Value::Number(x) => {
-                x.as_i64().map(Value::from)
-                    .or_else(|| x.as_u64().map(Value::from))
-                    .or_else(|| x.as_f64().map(Value::from))
-                    .unwrap_or_else(|| panic!("Invalid number value: {}", x))
-            }
-            serde_json::Value::String(x) => x.into(),
-            serde_json::Value::Bool(x) => x.into(),
-            serde_json::Value::Array(x) => Value::Array(x.into_iter().map(Value::from).collect()),
-            serde_json::Value::Object(x) => {
-                Value::from_extended_document(
-                    x.into_iter().map(|(k, v)| (k.clone(), v.into())).collect()
-                )
+            } else if let Ok(d) = values.get_str("$numberDecimal") {
+                let d = d.parse().map_err(|_| invalid("$numberDecimal"))?;
+                return Ok(Some(Value::Decimal128(d)));
+
+            } else if let Some(Value::Int32(1)) | Some(Value::Int64(1)) = values.get("$minKey") {
+                return Ok(Some(Value::MinKey));
+
+            } else if let Some(Value::Int32(1)) | Some(Value::Int64(1)) = values.get("$maxKey") {
+                return Ok(Some(Value::MaxKey));
+
+            } else if let Ok(true) = values.get_bool("$undefined") {
+                return Ok(Some(Value::Undefined));
             }
-            serde_json::Value::Null => Value::Null,
         }
+
+        Ok(None)
     }
 }
 
-impl Into<serde_json::Value> for Value {
-    fn into(self) -> serde_json::Value {
-        match self {
-            Value::Double(v) => json!(v),
-            Value::String(v) => json!(v),
-            Value::Array(v) => json!(v.into_inner()),
-            Value::Document(v) => json!(v),
-            Value::Boolean(v) => json!(v),
-            Value::Null => serde_json::Value::Null,
-            Value::RegExp(pat, opt) => {
-                json!({
-                    "$regex": pat,
-                    "$options": opt
-                })
-            }
-            Value::JavaScriptCode(code) => json!({"$code": code}),
-            Value::JavaScriptCodeWithScope(code, scope) => {
-                json!({
-                    "$code": code,
-                    "scope": scope
-                })
+#[cfg(feature = "json")]
+impl TryFrom<serde_json::Value> for Value {
+    type Error = JsonError;
+
+    fn try_from(a: serde_json::Value) -> Result<Value, JsonError> {
+        Ok(match a {
+            serde_json::Value::Number(x) => match from_json_number(&x.to_string()) {
+                Value::String(_) => return Err(JsonError::InvalidNumber(x.to_string())),
+                number => number,
+            },
+            serde_json::Value::Array(x) => {
+                Value::Array(x.into_iter().map(Value::try_from).collect::<Result<_, _>>()?)
             }
-            Value::Int32(v) => v.into(),
-            Value::Int64(v) => v.into(),
-            Value::TimeStamp(v) => {
-                let time = v >> 32;
-                let inc = v & 0x0000_FFFF;
-                json!({
-                    "t": time,
-                    "i": inc
-                })
+            serde_json::Value::Object(x) => {
+                let doc = x.into_iter()
+                    .map(|(k, v)| Ok((k, Value::try_from(v)?)))
+                    .collect::<Result<Document, JsonError>>()?;
+
+                Value::try_from_extended_document(doc)?
             }
-            Value::Binary(t, ref v) => {
-                let tval: u8 = From::from(t);
-                json!({
-                    "type": tval,
-                    "$binary": v.to_hex()
-                })
+            other => Value::from_json(other),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl<'a> From<&'a Value> for serde_json::Value {
+    fn from(v: &'a Value) -> serde_json::Value {
+        v.to_json()
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<Value> for serde_json::Value {
+    type Error = JsonError;
+
+    /// Unlike the `Into` conversion, which quietly turns NaN and infinite
+    /// doubles into `null`, this fails on them.
+    fn try_from(v: Value) -> Result<serde_json::Value, JsonError> {
+        v.to_json_with(NonFiniteFloats::Error)
+    }
+}
+
+/// Errors from the fallible conversions between `Value` and
+/// `serde_json::Value`, and from `Value::try_from_extended_document`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    /// A JSON number that fits none of `i64`, `u64` and `f64`.
+    InvalidNumber(String),
+    /// NaN or an infinity, which JSON can't represent.
+    NonFiniteNumber(f64),
+    /// A recognised `$`-wrapper such as `$oid` with malformed contents.
+    InvalidExtendedJson(String),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JsonError::InvalidNumber(ref n) => write!(fmt, "invalid number value: {}", n),
+            JsonError::NonFiniteNumber(f) => write!(fmt, "non-finite number {} can't be represented in JSON", f),
+            JsonError::InvalidExtendedJson(ref key) => write!(fmt, "malformed extended JSON `{}` value", key),
+        }
+    }
+}
+
+impl error::Error for JsonError {}
+
+/// The datetime `millis` after the Unix epoch. BSON datetimes span the
+/// whole `i64` range but `DateTime<Utc>` only about 262,000 years either
+/// way, so values beyond that are clamped to `DateTime::<Utc>::MIN_UTC` or
+/// `MAX_UTC`, and no longer encode to the same millisecond count.
+pub(crate) fn clamped_datetime(millis: i64) -> DateTime<Utc> {
+    match Utc.timestamp_millis_opt(millis).single() {
+        Some(dt) => dt,
+        None if millis < 0 => DateTime::<Utc>::MIN_UTC,
+        None => DateTime::<Utc>::MAX_UTC,
+    }
+}
+
+// Look up an index and return its value unwrapped, like the typed getters
+// on `Document`. Indexes past the end are `NotPresent`.
+macro_rules! typed_gets {
+    ($($name:ident -> $ty:ty, $pat:pat => $value:expr;)+) => {
+        $(
+            pub fn $name(&self, index: usize) -> doc::Result<$ty> {
+                match self.inner.get(index) {
+                    Some($pat) => Ok($value),
+                    Some(_) => Err(doc::Error::UnexpectedType),
+                    None => Err(doc::Error::NotPresent),
+                }
             }
-            Value::ObjectId(v) => json!({"$oid": v.to_string()}),
-            Value::UTCDatetime(v) => {
-                json!({
-                    "$date": {
-                        "$numberLong": (v.timestamp() * 1000) + i64::from(v.nanosecond() / 1_000_000)
+        )+
+    };
+}
+
+macro_rules! try_from_value_impls {
+    ($($T:ty => $pat:pat => $v:ident, $expected:ident;)+) => {
+        $(
+            impl TryFrom<Value> for $T {
+                type Error = GetError;
+
+                fn try_from(value: Value) -> Result<$T, GetError> {
+                    match value {
+                        $pat => Ok($v),
+                        other => Err(GetError::new("", ElementType::$expected, Some(&other))),
                     }
-                })
+                }
             }
-            // FIXME: Don't know what is the best way to encode Symbol type
-            Value::Symbol(v) => json!({"$symbol": v}),
-        }
+        )+
     }
 }
 
+try_from_value_impls! {
+    String => Value::String(v) => v, Utf8String;
+    i32 => Value::Int32(v) => v, Int32;
+    i64 => Value::Int64(v) => v, Int64;
+    f64 => Value::Double(v) => v, Double;
+    bool => Value::Boolean(v) => v, Boolean;
+    Vec<u8> => Value::Binary(BinarySubtype::Generic, v) => v, Binary;
+    ObjectId => Value::ObjectId(v) => v, ObjectId;
+    DateTime<Utc> => Value::UTCDatetime(v) => v, UTCDatetime;
+    Document => Value::Document(v) => v, Document;
+    Array => Value::Array(v) => v, Array;
+}
+
 impl Array {
     pub fn new() -> Array {
         Array {
@@ -563,6 +1199,12 @@ impl Array {
         self.inner.push(value);
     }
 
+    /// Push anything convertible to a `Value`, such as `&str`, numbers or
+    /// `ObjectId`s.
+    pub fn push_value(&mut self, value: impl Into<Value>) {
+        self.inner.push(value.into());
+    }
+
     pub fn inner(&self) -> &Vec<Value> {
         &self.inner
     }
@@ -582,6 +1224,214 @@ impl Array {
     pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Value> {
         self.into_iter()
     }
+
+    typed_gets! {
+        get_f64 -> f64, &Value::Double(v) => v;
+        get_i32 -> i32, &Value::Int32(v) => v;
+        get_i64 -> i64, &Value::Int64(v) => v;
+        get_str -> &str, Value::String(v) => v;
+        get_array -> &Array, Value::Array(v) => v;
+        get_document -> &Document, Value::Document(v) => v;
+        get_bool -> bool, &Value::Boolean(v) => v;
+        get_binary -> &Vec<u8>, Value::Binary(BinarySubtype::Generic, v) => v;
+        get_object_id -> &ObjectId, Value::ObjectId(v) => v;
+        get_decimal128 -> Decimal128, &Value::Decimal128(v) => v;
+        get_time_stamp -> TimeStamp, &Value::TimeStamp(v) => v;
+        get_utc_datetime -> &DateTime<Utc>, Value::UTCDatetime(v) => v;
+    }
+
+    /// Collect every element as an `f64`, converting integers according to
+    /// `coercion`. Returns `None` if any element can't be converted.
+    pub fn to_f64_vec(&self, coercion: NumericCoercion) -> Option<Vec<f64>> {
+        self.iter().map(|v| coercion.to_f64(v)).collect()
+    }
+
+    /// Collect every element as an `i64`. `Int32` always widens; doubles are
+    /// converted according to `coercion`. Returns `None` if any element can't
+    /// be converted.
+    pub fn to_i64_vec(&self, coercion: NumericCoercion) -> Option<Vec<i64>> {
+        self.iter().map(|v| coercion.to_i64(v)).collect()
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn to_ndarray_f64(&self, coercion: NumericCoercion) -> Option<ndarray::Array1<f64>> {
+        self.to_f64_vec(coercion).map(ndarray::Array1::from)
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn to_ndarray_i64(&self, coercion: NumericCoercion) -> Option<ndarray::Array1<i64>> {
+        self.to_i64_vec(coercion).map(ndarray::Array1::from)
+    }
+}
+
+/// How network addresses are stored by `Value::from_ip_addr` and
+/// `Value::from_socket_addr`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum AddrFormat {
+    /// The usual text form, such as `"10.0.0.1"` or `"[::1]:80"`.
+    #[default]
+    String,
+    /// A generic binary holding the address octets (and port, if any).
+    Binary,
+}
+
+/// How `Value::from_duration` stores a duration.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum DurationFormat {
+    /// `Int64` milliseconds.
+    #[default]
+    Millis,
+    /// A `{secs: Int64, nanos: Int32}` document, keeping full precision.
+    SecsNanos,
+}
+
+/// What `Value::to_json_with` does with NaN and infinite doubles, which
+/// JSON has no numbers for.
+#[cfg(feature = "json")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum NonFiniteFloats {
+    /// Write `null`, as `to_json` does. The value is lost.
+    #[default]
+    Null,
+    /// Fail with `JsonError::NonFiniteNumber`.
+    Error,
+    /// Write Extended JSON such as `{"$numberDouble": "NaN"}`, which
+    /// `Value::from_json` reads back.
+    ExtendedJson,
+}
+
+/// The struct name `serde_json` gives its `Number` when its
+/// `arbitrary_precision` feature is on. Such numbers travel as a one-key map
+/// holding the number's text.
+pub(crate) const JSON_NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Convert the text of a JSON number without going through `f64` for
+/// integers: those that fit become `Int64`, larger ones `Decimal128`, and
+/// ones too long even for that are kept as a `String`. Other numbers become
+/// a `Double` when it holds them as written, and are otherwise kept exact
+/// the same way.
+pub(crate) fn from_json_number(text: &str) -> Value {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+
+    // `-0` has no integer form, JSON parsers read it as a double
+    if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) && text != "-0" {
+        if let Ok(i) = text.parse::<i64>() {
+            return Value::Int64(i);
+        }
+
+        return match text.parse::<Decimal128>() {
+            Ok(d) => Value::Decimal128(d),
+            Err(_) => Value::String(text.to_string()),
+        };
+    }
+
+    match text.parse::<f64>() {
+        Ok(f) if f.is_finite() && scientific(text) == scientific(&format!("{:e}", f)) => Value::Double(f),
+        _ => match text.parse::<Decimal128>() {
+            Ok(d) => Value::Decimal128(d),
+            Err(_) => Value::String(text.to_string()),
+        },
+    }
+}
+
+/// The sign, significant digits and decimal exponent of the leading digit
+/// of a number's text, so `0.50` and `5e-1` come out the same.
+fn scientific(text: &str) -> Option<(bool, String, i64)> {
+    let (negative, rest) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+
+    let (mantissa, exponent) = match rest.find(['e', 'E']) {
+        Some(i) => (&rest[..i], rest[i + 1..].parse::<i64>().ok()?),
+        None => (rest, 0),
+    };
+
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits: String = int.chars().chain(frac.chars()).collect();
+    let digits = digits.trim_start_matches('0');
+
+    if digits.is_empty() {
+        return Some((negative, String::new(), 0));
+    }
+
+    let exponent = exponent.checked_sub(frac.len() as i64)?.checked_add(digits.len() as i64 - 1)?;
+
+    Some((negative, digits.trim_end_matches('0').to_string(), exponent))
+}
+
+pub(crate) fn ip_octets(addr: IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+pub(crate) fn ip_from_octets(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => {
+            let mut octets = [0u8; 4];
+            octets.copy_from_slice(bytes);
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None
+    }
+}
+
+/// How `Array::to_f64_vec` and `Array::to_i64_vec` treat elements that
+/// aren't already of the requested numeric type.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum NumericCoercion {
+    /// Only accept `Double` for floats and `Int32`/`Int64` for integers.
+    Exact,
+    /// Convert between integers and doubles when no information is lost:
+    /// integers within ±2^53 become doubles, doubles with no fractional part
+    /// that fit in an `i64` become integers.
+    #[default]
+    Lossless,
+    /// Convert with `as` semantics, truncating and saturating as needed.
+    Lossy,
+}
+
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+impl NumericCoercion {
+    fn to_f64(self, value: &Value) -> Option<f64> {
+        match *value {
+            Value::Double(v) => Some(v),
+            Value::Int32(v) if self != NumericCoercion::Exact => Some(f64::from(v)),
+            Value::Int64(v) => match self {
+                NumericCoercion::Exact => None,
+                NumericCoercion::Lossless if v.unsigned_abs() > MAX_SAFE_INTEGER => None,
+                _ => Some(v as f64)
+            },
+            _ => None
+        }
+    }
+
+    fn to_i64(self, value: &Value) -> Option<i64> {
+        match *value {
+            Value::Int32(v) => Some(i64::from(v)),
+            Value::Int64(v) => Some(v),
+            Value::Double(v) => match self {
+                NumericCoercion::Exact => None,
+                NumericCoercion::Lossless => {
+                    if v.fract() == 0.0 && v >= i64::MIN as f64 && v < i64::MAX as f64 {
+                        Some(v as i64)
+                    } else {
+                        None
+                    }
+                }
+                NumericCoercion::Lossy => Some(v as i64)
+            },
+            _ => None
+        }
+    }
 }
 
 impl fmt::Debug for Array {
@@ -603,21 +1453,111 @@ impl DerefMut for Array {
     }
 }
 
-macro_rules! array_from_impls {
-    ($($T:ty)+) => {
+/// Returned by the `Index` impls for anything missing, like `serde_json`.
+pub(crate) static NULL: Value = Value::Null;
+
+/// Out of range indexes give `Null` rather than panicking; this takes
+/// precedence over indexing the `Vec` through `Deref`.
+impl Index<usize> for Array {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        self.inner.get(index).unwrap_or(&NULL)
+    }
+}
+
+/// Panics if `index` is out of range, like `Vec`.
+impl IndexMut<usize> for Array {
+    fn index_mut(&mut self, index: usize) -> &mut Value {
+        &mut self.inner[index]
+    }
+}
+
+// Keep slicing working now that `Index` no longer goes through `Deref`
+macro_rules! array_range_index {
+    ($($range:ty)+) => {
         $(
-            impl From<Vec<$T>> for Array {
-                fn from(vec: Vec<$T>) -> Array {
-                    vec.into_iter().map(|v| v.into()).collect()
+            impl Index<$range> for Array {
+                type Output = [Value];
+
+                fn index(&self, range: $range) -> &[Value] {
+                    &self.inner[range]
+                }
+            }
+
+            impl IndexMut<$range> for Array {
+                fn index_mut(&mut self, range: $range) -> &mut [Value] {
+                    &mut self.inner[range]
                 }
             }
         )+
+    };
+}
+
+array_range_index! {
+    Range<usize> RangeFrom<usize> RangeTo<usize> RangeFull
+    RangeInclusive<usize> RangeToInclusive<usize>
+}
+
+/// Looks up `key` if this is a document, giving `Null` otherwise.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        match self {
+            Value::Document(doc) => &doc[key],
+            _ => &NULL,
+        }
     }
 }
 
-array_from_impls! {
-    f32 f64 i32 i64 &str String &String Array
-    Document bool DateTime<Utc> Vec<u8> ObjectId
+/// Looks up `index` if this is an array, giving `Null` otherwise.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        match self {
+            Value::Array(array) => &array[index],
+            _ => &NULL,
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Array {
+    fn from(vec: Vec<T>) -> Array {
+        vec.into_iter().collect()
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl From<rust_decimal::Decimal> for Value {
+    fn from(d: rust_decimal::Decimal) -> Value {
+        Value::Decimal128(d.into())
+    }
+}
+
+/// Gives a `BinarySubtype::Uuid` binary. Serializing a `Uuid` through serde
+/// gives a string instead, unless the field uses
+/// `serde_helpers::uuid_as_binary`.
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for Value {
+    fn from(u: uuid::Uuid) -> Value {
+        Value::Binary(BinarySubtype::Uuid, u.as_bytes().to_vec())
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl From<ndarray::Array1<f64>> for Array {
+    fn from(arr: ndarray::Array1<f64>) -> Array {
+        arr.into_iter().map(Value::Double).collect()
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl From<ndarray::Array1<i64>> for Array {
+    fn from(arr: ndarray::Array1<i64>) -> Array {
+        arr.into_iter().map(Value::Int64).collect()
+    }
 }
 
 impl IntoIterator for Array {
@@ -647,18 +1587,24 @@ impl<'a> IntoIterator for &'a mut Array {
     }
 }
 
-impl FromIterator<Value> for Array {
-    fn from_iter<I: IntoIterator<Item=Value>>(iter: I) -> Self {
+impl<T: Into<Value>> FromIterator<T> for Array {
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> Self {
         let mut array = Array::new();
 
         for i in iter {
-            array.push(i);
+            array.push(i.into());
         }
 
         array
     }
 }
 
+impl<T: Into<Value>> Extend<T> for Array {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
+        self.inner.extend(iter.into_iter().map(Into::into));
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
 pub struct UTCDateTime(pub DateTime<Utc>);
 
@@ -688,8 +1634,396 @@ impl From<DateTime<Utc>> for UTCDateTime {
     }
 }
 
+/// A BSON timestamp: seconds since the epoch and an ordinal for operations
+/// within the same second. On the wire it is a single `u64` with the
+/// seconds in the high 32 bits, which is what the `u64` conversions give.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
 pub struct TimeStamp {
     pub timestamp: u32,
     pub increment: u32,
 }
+
+impl TimeStamp {
+    pub fn new(timestamp: u32, increment: u32) -> TimeStamp {
+        TimeStamp { timestamp, increment }
+    }
+}
+
+impl From<u64> for TimeStamp {
+    fn from(v: u64) -> TimeStamp {
+        TimeStamp {
+            timestamp: (v >> 32) as u32,
+            increment: v as u32,
+        }
+    }
+}
+
+impl From<TimeStamp> for u64 {
+    fn from(ts: TimeStamp) -> u64 {
+        u64::from(ts.timestamp) << 32 | u64::from(ts.increment)
+    }
+}
+
+impl From<TimeStamp> for Value {
+    fn from(ts: TimeStamp) -> Value {
+        Value::TimeStamp(ts)
+    }
+}
+
+/// Binary data along with its subtype. Unlike `serde_bytes`, which always
+/// comes back as `BinarySubtype::Generic`, the subtype survives a round trip
+/// through serde.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Binary {
+    pub subtype: BinarySubtype,
+    pub bytes: Vec<u8>,
+}
+
+impl From<Binary> for Value {
+    fn from(b: Binary) -> Value {
+        Value::Binary(b.subtype, b.bytes)
+    }
+}
+
+fn decimal_len(mut n: usize) -> usize {
+    let mut len = 1;
+
+    while n >= 10 {
+        n /= 10;
+        len += 1;
+    }
+
+    len
+}
+
+#[cfg(test)]
+mod test {
+    use crate::value::{Array, NumericCoercion, TimeStamp, Value};
+    use crate::ObjectId;
+    use crate::doc;
+
+    #[test]
+    fn numeric_vec() {
+        let arr = Array::from_vec(vec![Value::Int32(1), Value::Int64(2), Value::Double(3.0)]);
+
+        assert_eq!(arr.to_f64_vec(NumericCoercion::Exact), None);
+        assert_eq!(arr.to_f64_vec(NumericCoercion::Lossless), Some(vec![1.0, 2.0, 3.0]));
+        assert_eq!(arr.to_i64_vec(NumericCoercion::Exact), None);
+        assert_eq!(arr.to_i64_vec(NumericCoercion::Lossless), Some(vec![1, 2, 3]));
+
+        let arr = Array::from_vec(vec![Value::Double(1.5), Value::Int64(i64::MAX)]);
+
+        assert_eq!(arr.to_i64_vec(NumericCoercion::Lossless), None);
+        assert_eq!(arr.to_i64_vec(NumericCoercion::Lossy), Some(vec![1, i64::MAX]));
+        assert_eq!(arr.to_f64_vec(NumericCoercion::Lossless), None);
+
+        let arr = Array::from_vec(vec![Value::Int64(i64::MIN)]);
+        assert_eq!(arr.to_f64_vec(NumericCoercion::Lossless), None);
+        assert_eq!(arr.to_f64_vec(NumericCoercion::Lossy), Some(vec![i64::MIN as f64]));
+
+        let arr = Array::from_vec(vec![Value::Int64(-(1 << 53)), Value::Int64(1 << 53)]);
+        assert_eq!(arr.to_f64_vec(NumericCoercion::Lossless), Some(vec![-9007199254740992.0, 9007199254740992.0]));
+
+        let arr = Array::from_vec(vec![Value::Int32(1), Value::Null]);
+        assert_eq!(arr.to_f64_vec(NumericCoercion::Lossy), None);
+    }
+
+    #[test]
+    fn network_addresses() {
+        use std::net::{IpAddr, SocketAddr};
+        use crate::value::AddrFormat;
+
+        let ip: IpAddr = "192.168.0.1".parse().unwrap();
+        let sock: SocketAddr = "[::1]:8080".parse().unwrap();
+
+        assert_eq!(Value::from(ip), Value::String("192.168.0.1".to_string()));
+        assert_eq!(Value::from(ip).as_ip_addr(), Some(ip));
+        assert_eq!(Value::from(sock).as_socket_addr(), Some(sock));
+
+        let bin = Value::from_ip_addr(ip, AddrFormat::Binary);
+        assert_eq!(bin.as_binary().unwrap().1, &[192, 168, 0, 1]);
+        assert_eq!(bin.as_ip_addr(), Some(ip));
+
+        let bin = Value::from_socket_addr(sock, AddrFormat::Binary);
+        assert_eq!(bin.as_binary().unwrap().1.len(), 18);
+        assert_eq!(bin.as_socket_addr(), Some(sock));
+    }
+
+    #[test]
+    fn durations() {
+        use std::time::Duration;
+        use crate::value::DurationFormat;
+
+        let d = Duration::new(3, 500_000_123);
+
+        assert_eq!(Value::from(d), Value::Int64(3500));
+        assert_eq!(Value::from(d).as_duration(), Some(Duration::from_millis(3500)));
+        assert_eq!(Value::from_duration(d, DurationFormat::SecsNanos).as_duration(), Some(d));
+        assert_eq!(Value::Int64(-1).as_duration(), None);
+        assert_eq!(Value::from(chrono::Duration::seconds(-2)), Value::Int64(-2000));
+        assert_eq!(Value::Int64(-2000).as_chrono_duration(), Some(chrono::Duration::seconds(-2)));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_try_from() {
+        use std::convert::TryFrom;
+        use serde_json::json;
+        use crate::value::JsonError;
+        use crate::doc;
+
+        let json = json!({"a": [1, 2.5], "id": {"$oid": "5932a005b4b4b4ac168cd9e4"}});
+        let value = Value::try_from(json.clone()).unwrap();
+
+        assert_eq!(value, Value::from_json(json));
+        assert!(Value::try_from(json!({"id": {"$oid": "nope"}})).is_err());
+        assert!(Value::try_from(json!({"$binary": "zz", "type": 0})).is_err());
+
+        // The infallible conversion keeps malformed wrappers as documents
+        assert_eq!(Value::from_json(json!({"$oid": "nope"})), Value::Document(doc!{"$oid": "nope"}));
+
+        let nan = Value::Array(Array::from_vec(vec![Value::Double(f64::NAN)]));
+        assert!(matches!(serde_json::Value::try_from(nan), Err(JsonError::NonFiniteNumber(_))));
+        assert_eq!(serde_json::Value::try_from(Value::Int32(1)), Ok(json!(1)));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_from_ref() {
+        use serde_json::json;
+        use crate::doc;
+
+        let doc = doc!{"a": [1, {"b": "c"}], "t": (Value::TimeStamp(TimeStamp::new(1, 2))), "m": (Value::MinKey)};
+        let expected = json!({"a": [1, {"b": "c"}], "t": {"t": 1, "i": 2}, "m": {"$minKey": 1}});
+
+        assert_eq!(serde_json::Value::from(&doc), expected);
+        assert_eq!(doc.to_json(), expected);
+
+        let value = Value::Document(doc);
+        assert_eq!(serde_json::Value::from(&value), expected);
+        assert_eq!(value.clone().into_json(), expected);
+
+        let json: serde_json::Value = (&Value::TimeStamp(TimeStamp::new(1, 2))).into();
+        assert_eq!(json, json!({"t": 1, "i": 2}));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn ndarray_round_trip() {
+        let arr: Array = ndarray::Array1::from(vec![1.0, 2.5]).into();
+
+        assert_eq!(arr.to_ndarray_f64(NumericCoercion::Exact), Some(ndarray::Array1::from(vec![1.0, 2.5])));
+    }
+
+    #[test]
+    fn index() {
+        let value = Value::Document(doc!{"user": {"name": "a", "tags": ["x"]}});
+
+        assert_eq!(value["user"]["name"], Value::from("a"));
+        assert_eq!(value["user"]["tags"][0], Value::from("x"));
+        assert_eq!(value["user"]["tags"][1], Value::Null);
+        assert_eq!(value["missing"]["name"], Value::Null);
+        assert_eq!(value[0], Value::Null);
+
+        let arr = Array::from(vec![1, 2, 3]);
+        assert_eq!(arr[1], Value::Int32(2));
+        assert_eq!(arr[3], Value::Null);
+        assert_eq!(arr.get(3), None);
+        assert_eq!(arr[1..], [Value::Int32(2), Value::Int32(3)]);
+        assert_eq!(arr[..=0], [Value::Int32(1)]);
+
+        let mut arr = arr;
+        arr[0] = Value::Null;
+        arr[1..].swap(0, 1);
+        assert_eq!(*arr, [Value::Null, Value::Int32(3), Value::Int32(2)]);
+    }
+
+    #[test]
+    fn size() {
+        use std::mem::size_of;
+        use crate::Document;
+
+        // `Document` is the largest variant kept inline; bigger ones are boxed
+        assert!(size_of::<Value>() <= size_of::<Document>() + 8);
+    }
+
+    #[test]
+    fn typed_getters() {
+        let arr = Array::from_vec(vec![Value::from("a"), Value::Int64(1), Value::Document(doc!{"b": 2})]);
+
+        assert_eq!(arr.get_str(0), Ok("a"));
+        assert_eq!(arr.get_i64(1), Ok(1));
+        assert_eq!(arr.get_document(2), Ok(&doc!{"b": 2}));
+        assert_eq!(arr.get_i32(1), Err(crate::doc::Error::UnexpectedType));
+        assert_eq!(arr.get_binary(3), Err(crate::doc::Error::NotPresent));
+    }
+
+    #[test]
+    fn from_vec() {
+        let ids = vec![ObjectId::with_bytes([1; 12]), ObjectId::with_bytes([2; 12])];
+        let arr = Array::from(ids.clone());
+        assert_eq!(arr.get_object_id(1), Ok(&ids[1]));
+
+        let arr = Array::from(vec![1u32, 2]);
+        assert_eq!(*arr, [Value::Int32(1), Value::Int32(2)]);
+
+        let arr: Array = (0..3).collect();
+        assert_eq!(arr.len(), 3);
+        assert_eq!(Array::from(vec![Value::Null])[0], Value::Null);
+    }
+
+    #[test]
+    fn push_and_extend() {
+        let mut arr = Array::new();
+
+        arr.push_value("a");
+        arr.push_value(1);
+        arr.extend(vec![2.5, 3.5]);
+        arr.extend(vec![Value::Null]);
+
+        assert_eq!(*arr, [Value::from("a"), Value::Int32(1), Value::Double(2.5), Value::Double(3.5), Value::Null]);
+    }
+
+    #[test]
+    fn try_from_value() {
+        use std::convert::TryFrom;
+        use crate::doc::GetError;
+        use crate::spec::{ElementType, BinarySubtype};
+
+        assert_eq!(String::try_from(Value::from("a")), Ok("a".to_string()));
+        assert_eq!(i64::try_from(Value::Int64(5)), Ok(5));
+        assert_eq!(Vec::<u8>::try_from(Value::from(vec![1u8, 2])), Ok(vec![1, 2]));
+        assert_eq!(crate::Document::try_from(Value::from(doc!{"a": 1})), Ok(doc!{"a": 1}));
+
+        let err = i32::try_from(Value::Int64(5)).unwrap_err();
+        assert_eq!(err, GetError { key: String::new(), expected: ElementType::Int32, found: Some(ElementType::Int64), found_subtype: None });
+        assert_eq!(err.to_string(), "expected Int32, found Int64");
+
+        let err = Vec::<u8>::try_from(Value::Binary(BinarySubtype::Uuid, vec![0; 16])).unwrap_err();
+        assert_eq!(err.to_string(), "expected Binary(Generic), found Binary(Uuid)");
+    }
+
+    #[test]
+    fn into_accessors() {
+        let doc = doc!{"a": 1};
+        assert_eq!(Value::from(doc.clone()).into_document(), Ok(doc));
+        assert_eq!(Value::from("a").into_string(), Ok("a".to_string()));
+        assert_eq!(Value::from(vec![1u8]).into_binary(), Ok((crate::BinarySubtype::Generic, vec![1])));
+        assert_eq!(Value::Int32(1).into_array(), Err(Value::Int32(1)));
+    }
+
+    #[test]
+    fn as_accessors() {
+        let mut value = Value::from(doc!{"a": 1});
+        value.as_document_mut().unwrap().insert("b", 2);
+        assert_eq!(value, Value::from(doc!{"a": 1, "b": 2}));
+        assert!(value.as_array_mut().is_none());
+
+        let mut value = Value::Array(Array::new());
+        value.as_array_mut().unwrap().push_value(1);
+        assert_eq!(value.as_array().map(|a| a.len()), Some(1));
+
+        assert_eq!(Value::RegExp("^a".into(), "i".into()).as_regexp(), Some(("^a", "i")));
+        assert_eq!(Value::JavaScriptCode("f()".into()).as_javascript_code(), Some("f()"));
+
+        let scope = doc!{"x": 1};
+        let code = Value::JavaScriptCodeWithScope("x".into(), Box::new(scope.clone()));
+        assert_eq!(code.as_code_with_scope(), Some(("x", &scope)));
+    }
+
+    #[test]
+    fn timestamp() {
+        let ts = TimeStamp::new(7, 0x1_0001);
+        assert_eq!(u64::from(ts), 7 << 32 | 0x1_0001);
+        assert_eq!(TimeStamp::from(u64::from(ts)), ts);
+
+        let doc = doc!{"ts": ts};
+        assert_eq!(doc.get_time_stamp("ts"), Ok(ts));
+        assert_eq!(crate::Document::from_slice(&doc.to_vec().unwrap()).unwrap(), doc);
+        assert_eq!(Value::from(ts).to_string(), r#"{ "$timestamp": { "t": 7, "i": 65537 } }"#);
+
+        #[cfg(feature = "json")]
+        assert_eq!(Value::from(ts).to_json(), serde_json::json!({"t": 7, "i": 65537}));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn non_finite_json() {
+        use serde_json::json;
+        use crate::value::{NonFiniteFloats, JsonError};
+
+        let doc = doc!{"a": [1.5, (f64::NAN)], "b": (f64::NEG_INFINITY)};
+
+        assert_eq!(doc.to_json_with(NonFiniteFloats::Null).unwrap(), json!({"a": [1.5, null], "b": null}));
+        assert!(matches!(doc.to_json_with(NonFiniteFloats::Error), Err(JsonError::NonFiniteNumber(_))));
+
+        let json = doc.to_json_with(NonFiniteFloats::ExtendedJson).unwrap();
+        assert_eq!(json, json!({"a": [1.5, {"$numberDouble": "NaN"}], "b": {"$numberDouble": "-Infinity"}}));
+
+        let back = Value::from_json(json);
+        assert!(back["a"][1].as_f64().unwrap().is_nan());
+        assert_eq!(back["b"], Value::Double(f64::NEG_INFINITY));
+        assert_eq!(Value::Double(f64::INFINITY).to_json_with(NonFiniteFloats::ExtendedJson).unwrap(), json!({"$numberDouble": "Infinity"}));
+
+        let code = Value::JavaScriptCodeWithScope("f()".to_string(), Box::new(doc!{"x": (f64::NAN)}));
+        assert!(matches!(code.to_json_with(NonFiniteFloats::Error), Err(JsonError::NonFiniteNumber(_))));
+        assert_eq!(code.to_json_with(NonFiniteFloats::ExtendedJson).unwrap(), json!({"$code": "f()", "scope": {"x": {"$numberDouble": "NaN"}}}));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_big_integers() {
+        use serde_json::json;
+        use std::convert::TryFrom;
+        use crate::decimal128::Decimal128;
+        use crate::value::from_json_number;
+
+        let max = u64::MAX.to_string().parse::<Decimal128>().unwrap();
+        assert_eq!(Value::from_json(json!(u64::MAX)), Value::Decimal128(max));
+        assert_eq!(Value::try_from(json!(-7)).unwrap(), Value::Int64(-7));
+
+        assert_eq!(from_json_number("-9223372036854775808"), Value::Int64(i64::MIN));
+        assert_eq!(from_json_number("1e3"), Value::Double(1000.0));
+        assert_eq!(from_json_number("-0").as_f64().map(f64::is_sign_negative), Some(true));
+
+        let long = "1".repeat(40);
+        assert_eq!(from_json_number(&long), Value::String(long.clone()));
+
+        // Doubles only where they hold the number as written
+        assert_eq!(from_json_number("0.10"), Value::Double(0.1));
+        assert_eq!(from_json_number("2.5E+2"), Value::Double(250.0));
+        assert_eq!(from_json_number("1e400"), Value::Decimal128("1e400".parse().unwrap()));
+        assert_eq!(from_json_number("12345678901234567890.5"), Value::Decimal128("12345678901234567890.5".parse().unwrap()));
+        assert_eq!(from_json_number("0.1000000000000000055511151231257827"), Value::Decimal128("0.1000000000000000055511151231257827".parse().unwrap()));
+        assert_eq!(from_json_number(&format!("0.{}", long)), Value::String(format!("0.{}", long)));
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn arbitrary_precision_numbers() {
+        use std::convert::TryFrom;
+        use crate::decimal128::Decimal128;
+        use crate::value::JsonError;
+
+        let text = r#"{"big": 123456789012345678901234567890, "long": 1111111111111111111111111111111111111111, "f": 0.5, "huge": 1e400, "frac": 12345678901234567890.5}"#;
+        let big = "123456789012345678901234567890".parse::<Decimal128>().unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(text).unwrap();
+        let value = Value::from_json(json.clone());
+        assert_eq!(value["big"], Value::Decimal128(big));
+        assert_eq!(value["long"], Value::String("1".repeat(40)));
+        assert_eq!(value["f"], Value::Double(0.5));
+        assert_eq!(value["huge"], Value::Decimal128("1e400".parse().unwrap()));
+        assert_eq!(value["frac"], Value::Decimal128("12345678901234567890.5".parse().unwrap()));
+        assert!(matches!(Value::try_from(json.clone()), Err(JsonError::InvalidNumber(_))));
+
+        // serde_json's `Number` goes through serde as a one-key map
+        let decoded: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(crate::encode::to_bson(&json).unwrap(), value);
+
+        let doc = crate::Document::from_extjson_str(text).unwrap();
+        assert_eq!(doc.get("big"), Some(&Value::Decimal128(big)));
+        assert_eq!(doc.get_f64("f"), Ok(0.5));
+    }
+}
@@ -0,0 +1,170 @@
+//! Runs test vectors in the format of the MongoDB BSON corpus
+//! (https://github.com/mongodb/specifications/tree/master/source/bson-corpus).
+//!
+//! The files in `tests/corpus` are always run. They cover every BSON type in
+//! the official format, but with fewer cases than the official files. Point
+//! `BSON_CORPUS_DIR` at a checkout of the official `tests` directory to run
+//! those as well:
+//!
+//! ```text
+//! BSON_CORPUS_DIR=specifications/source/bson-corpus/tests cargo test --test corpus
+//! ```
+//!
+//! Cases this crate is known to fail are listed in `SKIPPED` with the
+//! reason, and skipped in both.
+#![cfg(feature = "json")]
+
+use std::env;
+use std::fs;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value as Json;
+
+use bsonrs::Document;
+use bsonrs::decimal128::Decimal128;
+use bsonrs::decode::{self, DecodeOptions};
+
+/// Known failures as `(bson_type, case description, reason)`, where a
+/// description of `*` skips the whole file.
+const SKIPPED: &[(&str, &str, &str)] = &[
+    ("0x0C", "*", "DBPointer is not decoded"),
+    ("0x0B", "flags not alphabetized", "regular expression options are kept in the order they are given"),
+];
+
+fn skipped(bson_type: &str, description: &str) -> bool {
+    SKIPPED.iter().any(|&(t, d, _)| t == bson_type && (d == "*" || d == description))
+}
+
+fn unhex(s: &str) -> Vec<u8> {
+    assert!(s.len() % 2 == 0, "odd length hex `{}`", s);
+
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap_or_else(|_| panic!("invalid hex `{}`", s)))
+        .collect()
+}
+
+fn field<'a>(case: &'a Json, name: &str) -> Option<&'a str> {
+    case.get(name).and_then(Json::as_str)
+}
+
+fn cases<'a>(file: &'a Json, name: &str) -> &'a [Json] {
+    file.get(name).and_then(Json::as_array).map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Run one corpus file, returning a description of every failing case.
+fn run_file(path: &Path) -> Vec<String> {
+    let text = fs::read_to_string(path).unwrap();
+    let file: Json = serde_json::from_str(&text).unwrap();
+    let bson_type = field(&file, "bson_type").unwrap_or_default();
+    let mut failures = Vec::new();
+
+    if skipped(bson_type, "*") {
+        return failures;
+    }
+
+    for case in cases(&file, "valid") {
+        let description = field(case, "description").unwrap_or_default();
+
+        if skipped(bson_type, description) {
+            continue;
+        }
+
+        let canonical = unhex(field(case, "canonical_bson").unwrap());
+        let mut fail = |what: String| failures.push(format!("{}: {}: {}", path.display(), description, what));
+
+        match Document::from_slice_with_options(&canonical, DecodeOptions::strict()) {
+            Ok(doc) if doc.to_vec().ok().as_ref() != Some(&canonical) => fail("canonical BSON does not round trip".to_string()),
+            Ok(_) => (),
+            Err(err) => fail(format!("canonical BSON does not decode: {}", err)),
+        }
+
+        if let Some(degenerate) = field(case, "degenerate_bson") {
+            match Document::from_slice_with_options(&unhex(degenerate), DecodeOptions::strict()) {
+                Ok(doc) if doc.to_vec().ok().as_ref() != Some(&canonical) => fail("degenerate BSON does not encode canonically".to_string()),
+                Ok(_) => (),
+                Err(err) => fail(format!("degenerate BSON does not decode: {}", err)),
+            }
+        }
+
+        let lossy = case.get("lossy").and_then(Json::as_bool).unwrap_or(false);
+
+        if let (Some(extjson), false) = (field(case, "canonical_extjson"), lossy) {
+            match Document::from_extjson_str(extjson) {
+                Ok(doc) if doc.to_vec().ok().as_ref() != Some(&canonical) => fail("canonical Extended JSON does not encode to canonical BSON".to_string()),
+                Ok(_) => (),
+                Err(err) => fail(format!("canonical Extended JSON does not parse: {}", err)),
+            }
+        }
+    }
+
+    for case in cases(&file, "decodeErrors") {
+        let description = field(case, "description").unwrap_or_default();
+
+        if skipped(bson_type, description) {
+            continue;
+        }
+
+        let bytes = unhex(field(case, "bson").unwrap());
+
+        if Document::from_slice_with_options(&bytes, DecodeOptions::strict()).is_ok() {
+            failures.push(format!("{}: {}: decodes", path.display(), description));
+        }
+
+        if decode::validate(&bytes, DecodeOptions::strict()).is_ok() {
+            failures.push(format!("{}: {}: passes validation", path.display(), description));
+        }
+    }
+
+    for case in cases(&file, "parseErrors") {
+        let description = field(case, "description").unwrap_or_default();
+
+        if skipped(bson_type, description) {
+            continue;
+        }
+
+        let string = field(case, "string").unwrap();
+
+        // Decimal128 parse errors are bare strings rather than documents
+        let parsed = if bson_type == "0x13" {
+            string.parse::<Decimal128>().is_ok()
+        } else {
+            Document::from_extjson_str(string).is_ok()
+        };
+
+        if parsed {
+            failures.push(format!("{}: {}: parses", path.display(), description));
+        }
+    }
+
+    failures
+}
+
+fn run_dir(dir: &Path) -> Vec<String> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some(OsStr::new("json")))
+        .collect();
+
+    paths.sort();
+    assert!(!paths.is_empty(), "no corpus files in {}", dir.display());
+
+    paths.iter().flat_map(|path| run_file(path)).collect()
+}
+
+#[test]
+fn bundled_corpus() {
+    let failures = run_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus"));
+    assert!(failures.is_empty(), "{} failures:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn official_corpus() {
+    let dir = match env::var_os("BSON_CORPUS_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+
+    let failures = run_dir(&dir);
+    assert!(failures.is_empty(), "{} failures:\n{}", failures.len(), failures.join("\n"));
+}